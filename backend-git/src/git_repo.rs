@@ -1,32 +1,176 @@
 use std::path::{Path, PathBuf};
-use std::process::{Command, Output};
+use std::io::{Read, Write};
+use std::process::{Child, Command, ExitStatus, Stdio};
 use std::error::Error;
+use std::fmt;
 use std::fs;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::{Duration, Instant, SystemTime};
 #[cfg(unix)]
 use std::os::unix::fs::symlink;
 #[cfg(windows)]
 use std::os::windows::fs::symlink_dir as symlink;
 
-use gpp_core::types::{NodeId, RemoteRef, Author};
-use gpp_core::backend::RepoBackend;
+use chrono::{DateTime, FixedOffset};
+
+use gpp_core::types::{local_remote_path, NodeId, RemoteRef, Author};
+use gpp_core::backend::{DiffStat, HistoryEntry, Progress, RepoBackend, SignatureStatus};
+use gpp_core::PushError;
+use gpp_core::text;
 use gpp_core::Node;
 
+/// Команды, которые берут index.lock
+const INDEX_COMMANDS: &[&str] = &["add", "read-tree", "update-index", "checkout", "reset", "rm", "mv"];
+
+/// Шаблоны в формате .gitignore, которые действуют только на деревья gpp, обычный git их не видит
+const GPP_IGNORE: &str = ".gitpp/ignore";
+
+/// Хеш пустого дерева в git (SHA-1) - им становится нода, созданная в пустой папке
+pub const EMPTY_TREE: &str = "4b825dc642cb6eb9a060e54bf8d69288fbee4904";
+
+/// Разделители полей и записей в выводе git log для list_history
+const FIELD_SEP: char = '\u{1f}';
+const RECORD_SEP: char = '\u{1e}';
+
+/// Сообщения git о временных сбоях сети: такой push можно повторить.
+/// Сверяются без учёта регистра, формулировки у curl/ssh/git разные.
+const TRANSIENT_NETWORK_ERRORS: &[&str] = &[
+    "could not resolve host",
+    "temporary failure in name resolution",
+    "connection reset",
+    "connection refused",
+    "connection timed out",
+    "operation timed out",
+    "network is unreachable",
+    "the remote end hung up unexpectedly",
+    "early eof",
+    "rpc failed",
+];
+
+/// Отказы, которые повтор не исправит, даже если рядом есть "hung up unexpectedly"
+const PERMANENT_PUSH_ERRORS: &[&str] = &[
+    "authentication failed",
+    "permission denied",
+    "could not read username",
+    "repository not found",
+    "[rejected]",
+    "[remote rejected]",
+];
+
+/// Строка из вывода git, по которой push признан временным сбоем сети (None - не сеть или отказ)
+fn transient_network_error(output: &str) -> Option<&str> {
+    let lower = output.to_lowercase();
+    if PERMANENT_PUSH_ERRORS.iter().any(|p| lower.contains(p)) {
+        return None;
+    }
+    output.lines().map(str::trim).find(|line| {
+        let line = line.to_lowercase();
+        TRANSIENT_NETWORK_ERRORS.iter().any(|p| line.contains(p))
+    })
+}
+
+/// Лок старше этого возраста считаем оставшимся от прерванной операции (Ctrl-C и т.п.)
+const STALE_LOCK_AGE: Duration = Duration::from_secs(60);
+
+/// Сетевая команда git (push, clone, fetch) не уложилась в таймаут и была убита
+#[derive(Debug)]
+pub struct TimedOut {
+    pub command: String,
+    pub after: Duration,
+}
+
+impl fmt::Display for TimedOut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "git {} timed out after {}s and was killed (gpp config net.timeout <seconds> to change, 0 - no limit)",
+            self.command,
+            self.after.as_secs()
+        )
+    }
+}
+
+impl Error for TimedOut {}
+
+/// Чем сейчас является .git в рабочей директории. Репозиторий могли создать на другой ОС
+/// (WSL, сетевой диск), поэтому тип определяется по факту, а не по текущей платформе.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GitLinkKind {
+    /// Относительный симлинк на .git_<name>, так его делает gpp на linux
+    Symlink,
+    /// Junction с windows: ссылка с абсолютным путём (из WSL видна как симлинк на /mnt/...)
+    Junction,
+    /// Обычная папка или файл .git, не ссылка на контекст
+    Plain,
+}
+
+impl GitLinkKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            GitLinkKind::Symlink => "symlink",
+            GitLinkKind::Junction => "junction",
+            GitLinkKind::Plain => "plain directory",
+        }
+    }
+
+    /// Ссылку сделали по правилам другой ОС, switch_context пересоздаст её по местным
+    pub fn is_foreign(self) -> bool {
+        match self {
+            GitLinkKind::Symlink => cfg!(windows),
+            GitLinkKind::Junction => !cfg!(windows),
+            GitLinkKind::Plain => false,
+        }
+    }
+}
+
 pub struct GitRepo {
     workdir: PathBuf,
+    /// Сколько ждать сетевые команды; None - сколько угодно. Локальные команды не ограничены.
+    network_timeout: Option<Duration>,
+    /// Контекст, за которым закреплён хендл (with_context). None - работаем через симлинк .git.
+    context: Option<String>,
 }
 
 impl GitRepo {
     pub fn new(workdir: impl AsRef<Path>) -> Self {
         Self {
             workdir: workdir.as_ref().to_path_buf(),
+            network_timeout: None,
+            context: None,
         }
     }
 
+    pub fn with_network_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.network_timeout = timeout;
+        self
+    }
+
     fn run_git_command(&self, args: &[&str]) -> Result<String, Box<dyn Error>> {
+        self.run_git_command_with_env(args, &[])
+    }
+
+    /// Текстовый вывод: невалидный UTF-8 заменяется, пробелы по краям срезаются.
+    /// Для путей и содержимого файлов это портит данные - там нужен run_git_command_bytes_with_env.
+    fn run_git_command_with_env(&self, args: &[&str], envs: &[(&str, String)]) -> Result<String, Box<dyn Error>> {
+        let stdout = self.run_git_command_bytes_with_env(args, envs)?;
+        Ok(String::from_utf8_lossy(&stdout).trim().to_string())
+    }
+
+    /// stdout git как есть, байт в байт
+    fn run_git_command_bytes_with_env(&self, args: &[&str], envs: &[(&str, String)]) -> Result<Vec<u8>, Box<dyn Error>> {
+        if args.first().is_some_and(|cmd| INDEX_COMMANDS.contains(cmd)) {
+            self.clear_stale_index_lock()?;
+        }
+
         let mut command = Command::new("git");
         command.current_dir(&self.workdir);
+        // закреплённый хендл: через переменные, чтобы явный --git-dir из run_git_in_context их перекрывал
+        if self.context.is_some() {
+            command.env("GIT_DIR", self.git_dir()).env("GIT_WORK_TREE", &self.workdir);
+        }
         // command.env("GIT_CONFIG_NOSYSTEM", "1");
         command.args(args);
+        command.envs(envs.iter().map(|(k, v)| (k, v)));
 
         let output = command.output()?;
 
@@ -39,56 +183,298 @@ impl GitRepo {
             return Err(error_msg.into());
         }
 
-        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+        Ok(output.stdout)
     }
 
-    pub fn switch_context(&self, remote_name: &str) -> Result<(), Box<dyn Error>> {
-        let git_link = self.workdir.join(".git");
-        let target_dir_name = format!(".git_{}", remote_name);
-        let target_path = self.workdir.join(&target_dir_name);
+    /// Выполняет f с временным индексом (GIT_INDEX_FILE), заполненным из base_tree, и удаляет его после
+    fn with_scratch_index<T>(
+        &self,
+        base_tree: Option<&str>,
+        f: impl FnOnce(&[(&str, String)]) -> Result<T, Box<dyn Error>>,
+    ) -> Result<T, Box<dyn Error>> {
+        let index = self.git_dir().join("index.gpp-scratch");
+        let envs = [("GIT_INDEX_FILE", index.to_string_lossy().to_string())];
+        let result = match base_tree {
+            Some(tree) => self.run_git_command_with_env(&["read-tree", tree], &envs),
+            None => self.run_git_command_with_env(&["read-tree", "--empty"], &envs),
+        }
+        .and_then(|_| f(&envs));
+        let _ = fs::remove_file(&index);
+        result
+    }
+
+    /// Выполняет f с переменными envs, к которым, если есть .gitpp/ignore, добавлен временный
+    /// core.excludesFile: глобальный excludesFile пользователя плюс шаблоны gpp. Так add пропускает
+    /// их по правилам .gitignore, а конфиг и info/exclude контекста не меняются.
+    fn with_gpp_excludes<T>(
+        &self,
+        envs: &[(&str, String)],
+        f: impl FnOnce(&[(&str, String)]) -> Result<T, Box<dyn Error>>,
+    ) -> Result<T, Box<dyn Error>> {
+        let ignore = self.workdir.join(GPP_IGNORE);
+        if !ignore.exists() {
+            return f(envs);
+        }
+        let mut patterns = self
+            .global_excludes_file()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .unwrap_or_default();
+        patterns.push('\n');
+        patterns.push_str(&fs::read_to_string(&ignore)?);
+
+        let excludes = self.git_dir().join("gpp-exclude");
+        fs::write(&excludes, patterns)?;
+        let mut all = envs.to_vec();
+        all.extend([
+            ("GIT_CONFIG_COUNT", "1".to_string()),
+            ("GIT_CONFIG_KEY_0", "core.excludesFile".to_string()),
+            ("GIT_CONFIG_VALUE_0", excludes.to_string_lossy().to_string()),
+        ]);
+        let result = f(&all);
+        let _ = fs::remove_file(&excludes);
+        result
+    }
 
-        // такой огород потому что симлинки удаляются на винде и в линуксе по-разному
-        if git_link.exists() || fs::symlink_metadata(&git_link).is_ok() {
-            if let Err(_) = fs::remove_file(&git_link) {
-                if let Err(e) = fs::remove_dir(&git_link) {
-                    return Err(format!("Failed to remove existing .git link: {}", e).into());
+    /// core.excludesFile, а если он не задан - путь по умолчанию, как его ищет git
+    fn global_excludes_file(&self) -> Option<PathBuf> {
+        if let Ok(path) = self.run_git_command(&["config", "--path", "--get", "core.excludesFile"]) {
+            return Some(PathBuf::from(path));
+        }
+        let config_home = std::env::var_os("XDG_CONFIG_HOME")
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+        Some(config_home.join("git").join("ignore"))
+    }
+
+    fn get_index_lock_path(&self) -> std::path::PathBuf {
+        self.git_dir().join("index.lock")
+    }
+
+    /// Удаляет index.lock, если он явно протух. Свежий лок не трогаем - его может держать живой git процесс.
+    fn clear_stale_index_lock(&self) -> Result<(), Box<dyn Error>> {
+        let lock_path = self.get_index_lock_path();
+        let metadata = match fs::metadata(&lock_path) {
+            Ok(m) => m,
+            Err(_) => return Ok(()),
+        };
+
+        let age = metadata
+            .modified()
+            .ok()
+            .and_then(|mtime| SystemTime::now().duration_since(mtime).ok())
+            .unwrap_or_default();
+
+        if age < STALE_LOCK_AGE {
+            return Err(format!(
+                "{} exists and is {}s old: another git process may be running. \
+                If not, remove it manually.",
+                lock_path.display(),
+                age.as_secs()
+            ).into());
+        }
+
+        fs::remove_file(&lock_path)?;
+        Ok(())
+    }
+
+    fn context_path(&self, name: &str) -> PathBuf {
+        self.workdir.join(format!(".git_{}", name))
+    }
+
+    /// Репозиторий, с которым работают команды: закреплённый контекст или то, на что указывает .git
+    fn git_dir(&self) -> PathBuf {
+        match &self.context {
+            Some(name) => self.context_path(name),
+            None => self.workdir.join(".git"),
+        }
+    }
+
+    /// Выполняет git-команду напрямую в указанном контексте, не трогая симлинк .git
+    fn run_git_in_context(&self, name: &str, args: &[&str]) -> Result<String, Box<dyn Error>> {
+        let git_dir = self.context_path(name);
+        let git_dir_str = git_dir.to_string_lossy().to_string();
+        let mut full_args = vec!["--git-dir", git_dir_str.as_str()];
+        full_args.extend_from_slice(args);
+        self.run_git_command(&full_args)
+    }
+
+    fn run_git_in_context_bytes(&self, name: &str, args: &[&str]) -> Result<Vec<u8>, Box<dyn Error>> {
+        let git_dir = self.context_path(name);
+        let git_dir_str = git_dir.to_string_lossy().to_string();
+        let mut full_args = vec!["--git-dir", git_dir_str.as_str()];
+        full_args.extend_from_slice(args);
+        self.run_git_command_bytes_with_env(&full_args, &[])
+    }
+
+    /// Сетевая команда git: в контексте git_dir (или без него, для clone) и с network_timeout.
+    /// stderr (туда git пишет прогресс) отдаётся в on_line построчно по мере поступления.
+    /// Прогресс git перерисовывает через '\r', поэтому режем и по нему.
+    fn run_git_network(
+        &self,
+        git_dir: Option<&Path>,
+        args: &[&str],
+        on_line: Option<&dyn Fn(&str)>,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut command = Command::new("git");
+        command.current_dir(&self.workdir);
+        if let Some(dir) = git_dir {
+            command.arg("--git-dir").arg(dir);
+        }
+        let mut child = command.args(args).stdout(Stdio::null()).stderr(Stdio::piped()).spawn()?;
+
+        // читаем в отдельном потоке: иначе висящий git держал бы нас в read() и таймаут бы не сработал
+        let mut stderr = child.stderr.take().ok_or("git stderr is not captured")?;
+        let (tx, rx) = mpsc::channel::<String>();
+        std::thread::spawn(move || {
+            let mut line = Vec::new();
+            let mut buf = [0u8; 1024];
+            let flush = |line: &mut Vec<u8>| {
+                let text = String::from_utf8_lossy(line).trim().to_string();
+                line.clear();
+                text.is_empty() || tx.send(text).is_ok()
+            };
+            while let Ok(n) = stderr.read(&mut buf) {
+                if n == 0 {
+                    break;
+                }
+                for &b in &buf[..n] {
+                    if b != b'\r' && b != b'\n' {
+                        line.push(b);
+                    } else if !flush(&mut line) {
+                        return;
+                    }
+                }
+            }
+            flush(&mut line);
+        });
+
+        // таймаут больше, чем помещается в Instant, - всё равно что без ограничения
+        let deadline = self.network_timeout.and_then(|t| Instant::now().checked_add(t));
+        let mut collected = String::new();
+        loop {
+            let received = match deadline {
+                Some(deadline) => rx.recv_timeout(deadline.saturating_duration_since(Instant::now())),
+                None => rx.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            };
+            match received {
+                Ok(text) => {
+                    if let Some(report) = on_line {
+                        report(&text);
+                    }
+                    collected.push_str(&text);
+                    collected.push('\n');
                 }
+                Err(RecvTimeoutError::Disconnected) => break,
+                Err(RecvTimeoutError::Timeout) => return Err(self.kill_timed_out(&mut child, args)),
             }
         }
 
-        if !target_path.exists() {
-            self.init_context(remote_name, None)?;
+        let status = match deadline {
+            Some(deadline) => match wait_until(&mut child, deadline)? {
+                Some(status) => status,
+                None => return Err(self.kill_timed_out(&mut child, args)),
+            },
+            None => child.wait()?,
+        };
+        if !status.success() {
+            return Err(format!("Git error cmd='git {:?}': {}", args, collected.trim()).into());
         }
+        Ok(())
+    }
 
-        #[cfg(unix)]
-        {
-            std::os::unix::fs::symlink(Path::new(&target_dir_name), &git_link)?;
+    fn kill_timed_out(&self, child: &mut Child, args: &[&str]) -> Box<dyn Error> {
+        let _ = child.kill();
+        let _ = child.wait();
+        Box::new(TimedOut {
+            command: args.first().copied().unwrap_or_default().to_string(),
+            after: self.network_timeout.unwrap_or_default(),
+        })
+    }
+
+    fn context_has_object(&self, name: &str, oid: &str) -> bool {
+        self.run_git_in_context(name, &["cat-file", "-e", oid]).is_ok()
+    }
+
+    /// Подтягивает объект коммита (со всей историей) из активного контекста в указанный.
+    /// Контексты - это отдельные хранилища объектов, поэтому без этого пуш из "чужого" контекста падает.
+    fn ensure_object_in_context(&self, name: &str, oid: &str) -> Result<(), Box<dyn Error>> {
+        if self.context_has_object(name, oid) {
+            return Ok(());
         }
+        let source = self.git_dir();
+        let source_str = source.to_string_lossy().to_string();
+        self.run_git_in_context(name, &["fetch", "--quiet", &source_str, oid])?;
+        Ok(())
+    }
 
-        #[cfg(windows)]
-        {
-            // для windows используем Junction Point через mklink /J.
-            // Мы вызываем cmd, так как в std нет нативной поддержки junction без сторонних крейтов.
-            // короче говоря сраная винда как всегда суёт костыли в колёса
-            let status = Command::new("cmd")
-                .args(["/C", "mklink", "/J", ".git", &target_dir_name])
-                .current_dir(&self.workdir)
-                .output()?
-                .status;
+    /// Тип ссылки .git; None - .git нет совсем
+    pub fn git_link_kind(&self) -> Result<Option<GitLinkKind>, Box<dyn Error>> {
+        let git_link = self.workdir.join(".git");
+        let meta = match fs::symlink_metadata(&git_link) {
+            Ok(m) => m,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        // на windows junction тоже считается симлинком, отличаем их по абсолютному пути в цели
+        let kind = if meta.file_type().is_symlink() {
+            match fs::read_link(&git_link) {
+                Ok(target) if is_absolute_on_any_os(&target) => GitLinkKind::Junction,
+                _ => GitLinkKind::Symlink,
+            }
+        } else if meta.is_dir() || !cfg!(windows) {
+            GitLinkKind::Plain
+        } else {
+            // симлинк, сделанный в WSL, windows видит как непонятный reparse point-файл
+            GitLinkKind::Symlink
+        };
+        Ok(Some(kind))
+    }
 
-            if !status.success() {
-                return Err(format!(
-                    "Failed to create junction for context '{}'. Ensure you are not blocking .git folder.",
-                    remote_name
-                ).into());
+    /// Вторая половина checkout: ставит .git на target и кладёт ему выложенный индекс staged.
+    /// При ошибке .git снова указывает на previous, а индекс target не тронут.
+    fn link_checked_out(&self, previous: Option<&str>, target: &str, staged: &Path) -> Result<(), Box<dyn Error>> {
+        let result = if previous == Some(target) { Ok(()) } else { self.switch_context(target) }
+            .and_then(|_| fs::rename(staged, self.context_path(target).join("index")).map_err(Into::into));
+        if result.is_err() && self.active_context()?.as_deref() != previous {
+            // без ссылки .git рабочая директория вообще не репозиторий - возвращаем прежнюю
+            match previous {
+                Some(previous) => self.switch_context(previous)?,
+                None => self.remove_git_link()?,
             }
         }
+        result
+    }
 
-        Ok(())
+    /// Выкладывает tree контекста context в рабочую директорию. index описывает то, что лежит там сейчас,
+    /// и сам не меняется: read-tree идёт в его временную копию.
+    fn restore_worktree(&self, context: &str, tree: &str, index: &Path) -> Result<(), Box<dyn Error>> {
+        let scratch = self.context_path(context).join("index.gpp-restore");
+        if index.exists() {
+            fs::copy(index, &scratch)?;
+        }
+        let git_dir = self.context_path(context).to_string_lossy().to_string();
+        let envs = [("GIT_INDEX_FILE", scratch.to_string_lossy().to_string())];
+        let result = self.run_git_command_with_env(&["--git-dir", &git_dir, "read-tree", "-u", "--reset", tree], &envs);
+        let _ = fs::remove_file(&scratch);
+        result.map(|_| ())
     }
 
-    fn get_index_lock_path(&self) -> std::path::PathBuf {
-        self.workdir.join(".git").join("index.lock")
+    /// Удаляет ссылку .git любого типа, не глядя на то, по правилам какой ОС она сделана.
+    /// Настоящую папку .git не трогает: это чужой репозиторий, а не контекст gpp.
+    fn remove_git_link(&self) -> Result<(), Box<dyn Error>> {
+        let git_link = self.workdir.join(".git");
+        let kind = match self.git_link_kind()? {
+            None => return Ok(()),
+            Some(GitLinkKind::Plain) => {
+                return Err(".git is a plain directory, not a link to a context. Move it to .git_<name> first".into());
+            }
+            Some(kind) => kind,
+        };
+        // симлинк на linux снимается remove_file, junction и директорный симлинк на windows - remove_dir
+        fs::remove_file(&git_link)
+            .or_else(|_| fs::remove_dir(&git_link))
+            .map_err(|e| format!("Failed to remove existing .git {}: {}", kind.name(), e).into())
     }
 
     pub fn init_context(&self, name: &str, url: Option<&str>) -> Result<(), Box<dyn Error>> {
@@ -126,21 +512,48 @@ impl GitRepo {
             .current_dir(&target_path)
             .output()?;
 
-        Ok(())
+        write_exclude(&target_path)
     }
-}
 
-impl RepoBackend for GitRepo {
-    fn run_cmd(&self, cmd: &str, args: Vec<&str>) -> Result<Output, Box<dyn Error>> {
-        let mut command = Command::new("git");
-        command.current_dir(&self.workdir);
-        command.arg(cmd);
-        command.args(&args);
-        Ok(command.output()?)
+    /// Создаёт контекст клонированием существующего репозитория (для gpp clone).
+    /// Ветки ремоута сразу записываются в tracking-ссылки, чтобы первый push не слал уже имеющееся.
+    pub fn clone_context(&self, name: &str, url: &str) -> Result<(), Box<dyn Error>> {
+        let target_path = self.context_path(name);
+        if target_path.exists() {
+            return Err(format!("Context '{}' already exists", name).into());
+        }
+
+        let target_str = target_path.to_string_lossy().to_string();
+        if let Err(e) = self.run_git_network(None, &["clone", "--bare", "--quiet", url, &target_str], None) {
+            // недоклонированный контекст мешал бы повторной попытке
+            let _ = fs::remove_dir_all(&target_path);
+            return Err(match e.downcast::<TimedOut>() {
+                Ok(timed_out) => timed_out,
+                Err(e) => format!("Git clone failed: {}", e).into(),
+            });
+        }
+
+        self.run_git_in_context(name, &["config", "core.bare", "false"])?;
+        let refspec = format!("+refs/heads/*:refs/remotes/{}/*", name);
+        self.run_git_network(Some(&target_path), &["fetch", "--quiet", url, &refspec], None)?;
+        write_exclude(&target_path)
     }
+}
+
+/// Прячем служебные папки gpp от самого git. В каждом контексте свой info/exclude, так что
+/// пишется при создании любого контекста, и ленивого тоже - иначе add в нём захватил бы .gitpp.
+fn write_exclude(context_path: &Path) -> Result<(), Box<dyn Error>> {
+    let info = context_path.join("info");
+    fs::create_dir_all(&info)?;
+    let mut file = fs::OpenOptions::new().append(true).create(true).open(info.join("exclude"))?;
+    writeln!(file, ".gitpp")?;
+    writeln!(file, ".git_*")?;
+    Ok(())
+}
 
-    fn read_ref(&self, refname: String) -> Result<Option<NodeId>, Box<dyn Error>> {
-        let args = vec!["rev-parse", "--verify", &refname];
+impl RepoBackend for GitRepo {
+    fn read_ref(&self, refname: &str) -> Result<Option<NodeId>, Box<dyn Error>> {
+        let args = vec!["rev-parse", "--verify", refname];
         match self.run_git_command(&args) {
             Ok(hash) => Ok(Some(NodeId(hash))),
             Err(_) => Ok(None),
@@ -148,39 +561,235 @@ impl RepoBackend for GitRepo {
     }
 
     fn create_tree(&self) -> Result<String, Box<dyn Error>> {
-        self.run_git_command(&vec!["add", "-A"])?;
+        self.with_gpp_excludes(&[], |envs| self.run_git_command_with_env(&["add", "-A"], envs))?;
+        // на пустом индексе write-tree отдаёт EMPTY_TREE, commit-tree с ним работает как с любым другим
         let tree_hash = self.run_git_command(&vec!["write-tree"])?;
         Ok(tree_hash)
     }
 
+    fn create_tree_isolated(&self) -> Result<String, Box<dyn Error>> {
+        let index = self.git_dir().join("index");
+        self.with_scratch_index(None, |envs| {
+            // копия настоящего индекса ради stat-кэша: с пустым git заново хешировал бы каждый файл
+            if index.exists() {
+                fs::copy(&index, &envs[0].1)?;
+            }
+            self.with_gpp_excludes(envs, |envs| self.run_git_command_with_env(&["add", "-A"], envs))?;
+            self.run_git_command_with_env(&["write-tree"], envs)
+        })
+    }
+
+    fn create_tree_from_paths(&self, base_tree: Option<&str>, paths: &[String]) -> Result<String, Box<dyn Error>> {
+        // отдельный временный индекс: основной индекс и остальные изменения в рабочей директории остаются как были
+        self.with_scratch_index(base_tree, |envs| {
+            let mut args = vec!["add", "-A", "--"];
+            args.extend(paths.iter().map(String::as_str));
+            self.with_gpp_excludes(envs, |envs| self.run_git_command_with_env(&args, envs))?;
+            self.run_git_command_with_env(&["write-tree"], envs)
+        })
+    }
+
+    fn changed_paths(&self, base_tree: Option<&str>) -> Result<Vec<String>, Box<dyn Error>> {
+        // во временном индексе собираем всю рабочую директорию и сравниваем с базой
+        self.with_scratch_index(base_tree, |envs| {
+            self.with_gpp_excludes(envs, |envs| self.run_git_command_with_env(&["add", "-A"], envs))?;
+            // -z: пути без кавычек и \-экранирования, которыми git заменяет не-ASCII символы
+            let output = self.run_git_command_bytes_with_env(
+                &["diff-index", "--cached", "--name-only", "--no-renames", "-z", base_tree.unwrap_or(EMPTY_TREE)],
+                envs,
+            )?;
+            let mut paths = nul_separated(&output)
+                .map(|raw| {
+                    String::from_utf8(raw.to_vec())
+                        .map_err(|_| format!("Path '{}' is not valid UTF-8", String::from_utf8_lossy(raw)))
+                })
+                .collect::<Result<Vec<String>, _>>()?;
+            paths.sort();
+            Ok(paths)
+        })
+    }
+
+    fn diff_stat(&self, tree_a: Option<&str>, tree_b: &str) -> Result<DiffStat, Box<dyn Error>> {
+        // деревья ноды могут лежать не в активном контексте: берём первый, где есть оба
+        let mut candidates: Vec<String> = self.active_context()?.into_iter().collect();
+        for context in self.list_contexts()? {
+            if !candidates.contains(&context) {
+                candidates.push(context);
+            }
+        }
+        let context = candidates
+            .iter()
+            .find(|c| self.context_has_object(c, tree_b) && tree_a.is_none_or(|a| self.context_has_object(c, a)))
+            .ok_or_else(|| format!("Tree {} is not present in any initialized context", tree_b))?;
+
+        let output = self.run_git_in_context_bytes(
+            context,
+            &["diff", "--numstat", "--no-renames", "-z", tree_a.unwrap_or(EMPTY_TREE), tree_b],
+        )?;
+        Ok(parse_numstat(&output))
+    }
+
+    fn restore_tree(&self, tree_id: &str) -> Result<(), Box<dyn Error>> {
+        self.run_git_command(&["read-tree", "-u", "--reset", tree_id])?;
+        Ok(())
+    }
+
     fn create_commit(
         &self,
         tree_oid: &str,
         parents: &[NodeId],
         message: &str,
-        _author: &Author // Пока игнорируем автора для простоты, берем из git config
+        _author: &Author, // Пока игнорируем автора для простоты, берем из git config
+        timestamp: DateTime<FixedOffset>,
     ) -> Result<NodeId, Box<dyn Error>> {
         let mut args = vec!["commit-tree", tree_oid, "-m", message];
         for p in parents {
             args.push("-p");
             args.push(&p.0);
         }
-        let commit_hash = self.run_git_command(&args)?;
+        // "@<unix> +0300" - внутренний формат git, без разбора локали; пояс только для показа
+        let date = format!("@{} {}", timestamp.timestamp(), timestamp.format("%z"));
+        let envs = [("GIT_AUTHOR_DATE", date.clone()), ("GIT_COMMITTER_DATE", date)];
+        let commit_hash = self.run_git_command_with_env(&args, &envs)?;
         self.run_git_command(&vec!["update-ref", "HEAD", &commit_hash])?;
         Ok(NodeId(commit_hash))
     }
 
+    fn replicate_commit(&self, commit_id: &NodeId, remotes: &[RemoteRef]) -> Result<(), Box<dyn Error>> {
+        for remote in remotes {
+            // ещё не созданные контексты пропускаем, они получат объект лениво при первом пуше
+            if !self.context_path(&remote.name).exists() {
+                continue;
+            }
+            self.ensure_object_in_context(&remote.name, &commit_id.0)?;
+            self.run_git_in_context(&remote.name, &["update-ref", "HEAD", &commit_id.0])?;
+        }
+        Ok(())
+    }
+
+    fn discard_commit(&self, commit_id: &NodeId, parents: &[NodeId], remotes: &[RemoteRef]) -> Result<(), Box<dyn Error>> {
+        let mut contexts: Vec<String> = self.active_context()?.into_iter().collect();
+        for remote in remotes {
+            if !contexts.contains(&remote.name) && self.context_path(&remote.name).exists() {
+                contexts.push(remote.name.clone());
+            }
+        }
+        for context in contexts {
+            // старое значение в update-ref: если HEAD уже ушёл с коммита, git откажет, и это правильно
+            let head = self.run_git_in_context(&context, &["rev-parse", "--verify", "-q", "HEAD"]).unwrap_or_default();
+            if head != commit_id.0 {
+                continue;
+            }
+            match parents.first() {
+                Some(parent) => self.run_git_in_context(&context, &["update-ref", "HEAD", &parent.0, &commit_id.0])?,
+                None => self.run_git_in_context(&context, &["update-ref", "-d", "HEAD", &commit_id.0])?,
+            };
+        }
+        Ok(())
+    }
+
+    fn commit_parents(&self, id: &NodeId) -> Result<Option<Vec<NodeId>>, Box<dyn Error>> {
+        let commit = format!("{}^{{commit}}", id.0);
+        let args = ["show", "-s", "--format=%P", commit.as_str()];
+        // нода может жить только в контекстах своих ремоутов, активный .git её не обязан знать
+        let mut found = self.run_git_command(&args).ok();
+        for context in self.list_contexts()? {
+            if found.is_some() {
+                break;
+            }
+            found = self.run_git_in_context(&context, &args).ok();
+        }
+        Ok(found.map(|parents| parents.split_whitespace().map(|p| NodeId(p.to_string())).collect()))
+    }
+
+    fn verify_signature(&self, id: &NodeId) -> Result<SignatureStatus, Box<dyn Error>> {
+        let commit = format!("{}^{{commit}}", id.0);
+        // %G? - то же, что решает verify-commit, но и для неподписанного коммита без ошибки
+        let args = ["show", "-s", "--format=%G?%n%GS", commit.as_str()];
+        let mut found = self.run_git_command(&args).ok();
+        for context in self.list_contexts()? {
+            if found.is_some() {
+                break;
+            }
+            found = self.run_git_in_context(&context, &args).ok();
+        }
+        let output = found.ok_or_else(|| format!("Commit {} is not present in any initialized context", id.short()))?;
+        let (code, signer) = output.split_once('\n').unwrap_or((output.as_str(), ""));
+        Ok(match code.trim() {
+            "G" | "U" => SignatureStatus::Good { signer: signer.trim().to_string() },
+            "N" => SignatureStatus::Unsigned,
+            "B" => SignatureStatus::Bad("bad signature".into()),
+            "X" => SignatureStatus::Bad("signature has expired".into()),
+            "Y" => SignatureStatus::Bad("signed by an expired key".into()),
+            "R" => SignatureStatus::Bad("signed by a revoked key".into()),
+            "E" => SignatureStatus::Bad("cannot be checked (missing key?)".into()),
+            other => SignatureStatus::Bad(format!("unknown status '{}'", other)),
+        })
+    }
+
+    fn remote_tips(&self, remote_name: &str) -> Result<Vec<NodeId>, Box<dyn Error>> {
+        if !self.context_path(remote_name).exists() {
+            return Ok(Vec::new());
+        }
+        let prefix = format!("refs/remotes/{}/", remote_name);
+        let output = self.run_git_in_context(remote_name, &["for-each-ref", "--format=%(objectname)", &prefix])?;
+        Ok(output.lines().map(|l| NodeId(l.trim().to_string())).collect())
+    }
+
     fn push_update_ref(
         &self,
         remote: &RemoteRef,
         local_tip_id: &NodeId,
-        remote_target_ref: &str
+        remote_target_refs: &[String],
+        skip_hooks: bool,
+        progress: Option<&dyn Fn(Progress)>,
     ) -> Result<(), Box<dyn Error>> {
-        //self.switch_context(&remote.name)?;
+        // про несуществующий file:// git говорит "does not appear to be a git repository" уже после подготовки
+        let is_file_url = remote.url.trim().starts_with("file://");
+        if let Some(path) = local_remote_path(&remote.url, &self.workdir).filter(|p| is_file_url && !p.exists()) {
+            return Err(format!("Local mirror {} for remote '{}' does not exist", path.display(), remote.name).into());
+        }
 
-        let refspec = format!("{}:{}", local_tip_id.0, remote_target_ref);
-        let args = vec!["push", &remote.url, &refspec];
-        self.run_git_command(&args)?;
+        // пушим из контекста самого ремоута, а не из активного .git
+        self.init_context(&remote.name, Some(&remote.url))?;
+        self.ensure_object_in_context(&remote.name, &local_tip_id.0)?;
+
+        let refspecs: Vec<String> = remote_target_refs.iter().map(|r| format!("{}:{}", local_tip_id.0, r)).collect();
+        let mut args = vec!["push"];
+        if progress.is_some() {
+            // без терминала git молчит о прогрессе, поэтому --progress просим явно
+            args.push("--progress");
+        }
+        if skip_hooks {
+            args.push("--no-verify");
+        }
+        args.push(&remote.url);
+        args.extend(refspecs.iter().map(String::as_str));
+
+        let report_line = progress.map(|report| move |line: &str| report(Progress::Transfer(line.to_string())));
+        let pushed = self.run_git_network(
+            Some(&self.context_path(&remote.name)),
+            &args,
+            report_line.as_ref().map(|f| f as &dyn Fn(&str)),
+        );
+        if let Err(e) = pushed {
+            // "! [rejected] ... (fetch first)" или "(non-fast-forward)": ремоут ушёл вперёд, стену вывода git не показываем
+            let text = e.to_string();
+            if text.contains("[rejected]") && (text.contains("fetch first") || text.contains("non-fast-forward")) {
+                return Err(Box::new(PushError::NonFastForward { remote: remote.name.clone() }));
+            }
+            if let Some(line) = transient_network_error(&text) {
+                return Err(Box::new(PushError::Network { remote: remote.name.clone(), message: line.to_string() }));
+            }
+            return Err(e);
+        }
+
+        // push по URL не обновляет tracking-ссылки, делаем это сами, чтобы следующий push был инкрементальным.
+        // Для тегов tracking-ссылок не бывает.
+        for target in remote_target_refs.iter().filter(|r| r.starts_with("refs/heads/")) {
+            let tracking_ref = target.replacen("refs/heads/", &format!("refs/remotes/{}/", remote.name), 1);
+            self.run_git_in_context(&remote.name, &["update-ref", &tracking_ref, &local_tip_id.0])?;
+        }
         Ok(())
     }
 
@@ -192,25 +801,460 @@ impl RepoBackend for GitRepo {
         }
     }
 
-    fn checkout_node(&self, node: &Node) -> Result<(), Box<dyn Error>> {
-        let target_context = if let Some(remote) = node.remotes.iter().next() {
-            &remote.name
+    fn checkout_context(&self, node: &Node) -> Result<String, Box<dyn Error>> {
+        let preferred = node.preferred_context().unwrap_or("origin").to_string();
+
+        // контексты создаются лениво (при push), поэтому нужного может не быть или в нём нет объекта ноды.
+        // switch_context тогда создал бы пустой репозиторий и read-tree упал бы невнятно - ищем контекст,
+        // где нода реально есть: сначала её ремоуты, потом любые инициализированные
+        let mut own: Vec<String> = node.remotes.iter().map(|r| r.name.clone()).filter(|n| *n != preferred).collect();
+        own.sort();
+        let mut candidates = vec![preferred.clone()];
+        candidates.extend(own);
+        for context in self.list_contexts()? {
+            if !candidates.contains(&context) {
+                candidates.push(context);
+            }
+        }
+
+        // одного коммита мало: без дерева read-tree упадёт уже после переключения .git
+        candidates
+            .iter()
+            .find(|c| {
+                self.context_path(c).exists()
+                    && self.context_has_object(c, &node.commit_id().0)
+                    && self.context_has_object(c, &node.payload.tree_id)
+            })
+            .cloned()
+            .ok_or_else(|| format!(
+                "Node {} is not materialized in any initialized context (checked: {})",
+                node.id.short(),
+                candidates.join(", ")
+            ).into())
+    }
+
+    fn checkout_node(&self, node: &Node, target_context: &str) -> Result<(), Box<dyn Error>> {
+        let preferred = node.preferred_context().unwrap_or("origin");
+        if target_context == preferred {
+            text::status(text::switching_context(&node.id, target_context));
         } else {
-            "origin"
+            text::status(text::context_lacks_node(preferred, &node.id, target_context));
+        }
+
+        // Рабочая директория меняется раньше .git и индекса: read-tree идёт во временный индекс целевого
+        // контекста, а в основе у него копия текущего - по ней git знает, какие файлы удалить.
+        // Упал read-tree - ничего не тронуто: лок он берёт и конфликты проверяет до записи файлов.
+        // Упало что-то после него - файлы откатываются к дереву прежнего индекса, .git и оба индекса
+        // остаются прежними. Так репозиторий всегда либо целиком до checkout, либо целиком после.
+        let previous = self.active_context()?;
+        let previous_tree = match &previous {
+            Some(name) => self
+                .run_git_in_context(name, &["write-tree"])
+                .map_err(|e| format!("Cannot checkout {}: the index of '{}' is not a tree: {}", node.id.short(), name, e))?,
+            None => EMPTY_TREE.to_string(),
         };
+        let staged = self.context_path(target_context).join("index.gpp-checkout");
+        let current_index = self.git_dir().join("index");
+        if current_index.exists() {
+            fs::copy(&current_index, &staged)?;
+        } else if staged.exists() {
+            fs::remove_file(&staged)?;
+        }
+        let git_dir = self.context_path(target_context).to_string_lossy().to_string();
+        let envs = [("GIT_INDEX_FILE", staged.to_string_lossy().to_string())];
+        let args = ["--git-dir", &git_dir, "read-tree", "-u", "--reset", &node.payload.tree_id];
+        if let Err(e) = self.run_git_command_with_env(&args, &envs) {
+            let _ = fs::remove_file(&staged);
+            return Err(format!("Checkout of {} failed, nothing was changed: {}", node.id.short(), e).into());
+        }
 
-        println!("DEBUG: Node {} belongs to '{}'. Switching...", node.id.0, target_context);
+        if let Err(e) = self.link_checked_out(previous.as_deref(), target_context, &staged) {
+            // staged описывает то, что теперь лежит в рабочей директории; без прежнего контекста откатываемся к пустому дереву
+            let restore_context = previous.as_deref().unwrap_or(target_context);
+            let restored = self.restore_worktree(restore_context, &previous_tree, &staged);
+            let _ = fs::remove_file(&staged);
+            return Err(match restored {
+                Ok(()) => format!("Checkout of {} failed, the previous files are restored: {}", node.id.short(), e),
+                Err(r) => format!("Checkout of {} failed: {}; restoring the previous files failed too: {}", node.id.short(), e, r),
+            }
+            .into());
+        }
+        Ok(())
+    }
 
-        self.switch_context(target_context)?;
+    fn switch_context(&self, remote_name: &str) -> Result<(), Box<dyn Error>> {
+        if let Some(pinned) = &self.context {
+            return Err(format!("Backend is pinned to context '{}', cannot switch to '{}'", pinned, remote_name).into());
+        }
+        let git_link = self.workdir.join(".git");
+        let target_dir_name = format!(".git_{}", remote_name);
+        let target_path = self.workdir.join(&target_dir_name);
 
-        let lock_path = self.get_index_lock_path();
-        if lock_path.exists() {
-            fs::remove_file(&lock_path).ok();
+        // старую ссылку могли сделать на другой ОС, новую делаем по местным правилам
+        self.remove_git_link()?;
+
+        if !target_path.exists() {
+            self.init_context(remote_name, None)?;
         }
 
-        let args = vec!["read-tree", "-u", "--reset", &node.payload.tree_id];
-        self.run_git_command(&args)?;
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(Path::new(&target_dir_name), &git_link)?;
+        }
+
+        #[cfg(windows)]
+        {
+            // для windows используем Junction Point через mklink /J.
+            // Мы вызываем cmd, так как в std нет нативной поддержки junction без сторонних крейтов.
+            // короче говоря сраная винда как всегда суёт костыли в колёса
+            let status = Command::new("cmd")
+                .args(["/C", "mklink", "/J", ".git", &target_dir_name])
+                .current_dir(&self.workdir)
+                .output()?
+                .status;
+
+            if !status.success() {
+                return Err(format!(
+                    "Failed to create junction for context '{}'. Ensure you are not blocking .git folder.",
+                    remote_name
+                ).into());
+            }
+        }
 
         Ok(())
     }
+
+    fn with_context(&self, name: &str) -> Result<Box<dyn RepoBackend + Send>, Box<dyn Error>> {
+        // как и switch_context, создаём контекст лениво
+        if !self.context_path(name).exists() {
+            self.init_context(name, None)?;
+        }
+        Ok(Box::new(GitRepo {
+            workdir: self.workdir.clone(),
+            network_timeout: self.network_timeout,
+            context: Some(name.to_string()),
+        }))
+    }
+
+    fn list_history(&self) -> Result<Vec<HistoryEntry>, Box<dyn Error>> {
+        if self.is_repo_empty()? {
+            return Ok(Vec::new());
+        }
+
+        let format = "--format=%H%x1f%P%x1f%T%x1f%an%x1f%ae%x1f%aI%x1f%B%x1e";
+        let output = self.run_git_command(&["log", "--branches", "--topo-order", "--reverse", format])?;
+
+        let mut history = Vec::new();
+        for record in output.split(RECORD_SEP).map(str::trim).filter(|r| !r.is_empty()) {
+            let fields: Vec<&str> = record.splitn(7, FIELD_SEP).collect();
+            let [id, parents, tree, name, email, date, message] = fields[..] else {
+                return Err(format!("Unexpected git log record: {:?}", record).into());
+            };
+            history.push(HistoryEntry {
+                id: NodeId(id.to_string()),
+                parents: parents.split_whitespace().map(|p| NodeId(p.to_string())).collect(),
+                tree_id: tree.to_string(),
+                message: message.trim_end().to_string(),
+                author: Author { name: name.to_string(), email: email.to_string() },
+                created_at: DateTime::parse_from_rfc3339(date)?,
+            });
+        }
+        Ok(history)
+    }
+
+    fn list_contexts(&self) -> Result<Vec<String>, Box<dyn Error>> {
+        let mut contexts = Vec::new();
+        for entry in fs::read_dir(&self.workdir)? {
+            let entry = entry?;
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            if let Some(name) = file_name.strip_prefix(".git_").filter(|_| entry.path().is_dir()) {
+                contexts.push(name.to_string());
+            }
+        }
+        contexts.sort();
+        Ok(contexts)
+    }
+
+    fn active_context(&self) -> Result<Option<String>, Box<dyn Error>> {
+        if let Some(pinned) = &self.context {
+            return Ok(Some(pinned.clone()));
+        }
+        let git_link = self.workdir.join(".git");
+        // read_link понимает и симлинки, и junction-ы на винде
+        let target = match fs::read_link(&git_link) {
+            Ok(t) => t,
+            Err(_) => return Ok(None),
+        };
+        // junction с windows хранит путь вида C:\proj\.git_origin: на linux он не разрешится,
+        // поэтому имя берём из последнего компонента с любым разделителем и ищем контекст у себя
+        let target = target.to_string_lossy();
+        let name = target
+            .rsplit(['/', '\\'])
+            .next()
+            .and_then(|n| n.strip_prefix(".git_"))
+            .filter(|name| self.context_path(name).is_dir())
+            .map(|name| name.to_string());
+        Ok(name)
+    }
+}
+
+/// Абсолютный путь по правилам хоть linux, хоть windows: junction пишет в цель C:\... или \\?\C:\...
+fn is_absolute_on_any_os(target: &Path) -> bool {
+    let text = target.to_string_lossy();
+    let bytes = text.as_bytes();
+    target.is_absolute()
+        || text.starts_with('/')
+        || text.starts_with('\\')
+        || (bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':')
+}
+
+/// Ждёт процесс до deadline. None - не успел (процесс ещё жив).
+fn wait_until(child: &mut Child, deadline: Instant) -> Result<Option<ExitStatus>, Box<dyn Error>> {
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        if Instant::now() >= deadline {
+            return Ok(None);
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    }
+}
+
+/// Записи вывода git с -z. Последний NUL закрывает запись, пустого хвоста не бывает.
+fn nul_separated(output: &[u8]) -> impl Iterator<Item = &[u8]> {
+    output.split(|b| *b == 0).filter(|record| !record.is_empty())
+}
+
+/// `git diff --numstat -z`: "<+>\t<->\t<путь>\0" на файл, у бинарных вместо чисел "-".
+/// Путь может быть в любой кодировке, поэтому разбираем байты, а не строки.
+fn parse_numstat(output: &[u8]) -> DiffStat {
+    let mut stat = DiffStat::default();
+    for record in nul_separated(output) {
+        let mut fields = record.splitn(3, |b| *b == b'\t');
+        let mut count = || fields.next().and_then(|n| std::str::from_utf8(n).ok()?.parse::<usize>().ok());
+        let added = count();
+        let removed = count();
+        stat.files += 1;
+        stat.insertions += added.unwrap_or(0);
+        stat.deletions += removed.unwrap_or(0);
+    }
+    stat
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pinned_handles_work_in_their_contexts_in_parallel() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = GitRepo::new(dir.path());
+        repo.switch_context("origin").unwrap();
+        fs::write(dir.path().join("a.txt"), "shared worktree").unwrap();
+
+        // каждый хендл пишет в индекс и объекты своего контекста, общий .git никто не трогает
+        let workers: Vec<_> = ["work", "mirror"]
+            .into_iter()
+            .map(|name| {
+                let handle = repo.with_context(name).unwrap();
+                std::thread::spawn(move || (handle.active_context().unwrap(), handle.create_tree().unwrap()))
+            })
+            .collect();
+        let results: Vec<_> = workers.into_iter().map(|w| w.join().unwrap()).collect();
+
+        assert_eq!(results[0].0.as_deref(), Some("work"));
+        assert_eq!(results[1].0.as_deref(), Some("mirror"));
+        assert_eq!(results[0].1, results[1].1);
+        assert!(repo.context_has_object("work", &results[0].1));
+        assert!(repo.context_has_object("mirror", &results[0].1));
+        assert!(!repo.context_has_object("origin", &results[0].1));
+        assert_eq!(repo.active_context().unwrap().as_deref(), Some("origin"));
+
+        assert!(repo.with_context("work").unwrap().switch_context("origin").is_err());
+    }
+
+    #[test]
+    fn gpp_ignore_applies_only_to_gpp_trees() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = GitRepo::new(dir.path());
+        repo.switch_context("origin").unwrap();
+        fs::create_dir_all(dir.path().join(".gitpp")).unwrap();
+        fs::write(dir.path().join(".gitpp/ignore"), "*.log\n!keep.log\n").unwrap();
+        fs::write(dir.path().join("a.txt"), "a").unwrap();
+        fs::write(dir.path().join("debug.log"), "noise").unwrap();
+        fs::write(dir.path().join("keep.log"), "kept").unwrap();
+
+        let tree = repo.create_tree().unwrap();
+        assert_eq!(repo.run_git_command(&["ls-tree", "--name-only", &tree]).unwrap(), "a.txt\nkeep.log");
+        assert_eq!(repo.changed_paths(None).unwrap(), ["a.txt", "keep.log"]);
+        // обычный git шаблонов gpp не видит, и временный файл не остаётся
+        assert!(repo.run_git_command(&["check-ignore", "-q", "debug.log"]).is_err());
+        assert!(!repo.git_dir().join("gpp-exclude").exists());
+    }
+
+    #[test]
+    fn isolated_tree_leaves_staged_index_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = GitRepo::new(dir.path());
+        repo.switch_context("origin").unwrap();
+        fs::write(dir.path().join("a.txt"), "staged").unwrap();
+        repo.run_git_command(&["add", "a.txt"]).unwrap();
+        fs::write(dir.path().join("a.txt"), "edited").unwrap();
+        fs::write(dir.path().join("b.txt"), "new").unwrap();
+        let staged = repo.run_git_command(&["write-tree"]).unwrap();
+
+        let tree = repo.create_tree_isolated().unwrap();
+        assert_ne!(tree, staged);
+        assert_eq!(repo.run_git_command(&["write-tree"]).unwrap(), staged);
+        // дерево то же, что собрал бы обычный create_tree
+        assert_eq!(repo.create_tree().unwrap(), tree);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn failed_checkout_keeps_previous_context_and_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = GitRepo::new(dir.path());
+        repo.switch_context("work").unwrap();
+        fs::write(dir.path().join("a.txt"), "new").unwrap();
+        let tree = repo.create_tree().unwrap();
+        repo.switch_context("origin").unwrap();
+        fs::write(dir.path().join("a.txt"), "old").unwrap();
+        fs::write(dir.path().join("gone.txt"), "only in origin").unwrap();
+        repo.create_tree().unwrap();
+
+        let node = Node {
+            id: NodeId("n".into()),
+            parents: vec![],
+            children: Default::default(),
+            author: Author { name: "a".into(), email: "a@b".into() },
+            co_authors: vec![],
+            message: "n".into(),
+            created_at: chrono::Utc::now(),
+            commit: None,
+            tz_offset: 0,
+            payload: gpp_core::NodePayload { tree_id: tree.clone() },
+            remotes: Default::default(),
+            tags: Default::default(),
+            metadata: Default::default(),
+        };
+
+        // чужой лок на временном индексе - read-tree падает, ничего не тронув
+        let lock = repo.context_path("work").join("index.gpp-checkout.lock");
+        fs::write(&lock, "").unwrap();
+        assert!(repo.checkout_node(&node, "work").is_err());
+        assert_eq!(repo.active_context().unwrap().as_deref(), Some("origin"));
+        assert_eq!(fs::read_to_string(dir.path().join("a.txt")).unwrap(), "old");
+        assert!(!repo.context_path("work").join("index.gpp-checkout").exists());
+
+        fs::remove_file(&lock).unwrap();
+        repo.checkout_node(&node, "work").unwrap();
+        assert_eq!(repo.active_context().unwrap().as_deref(), Some("work"));
+        assert_eq!(fs::read_to_string(dir.path().join("a.txt")).unwrap(), "new");
+        assert!(!dir.path().join("gone.txt").exists());
+        assert_eq!(repo.run_git_in_context("work", &["write-tree"]).unwrap(), tree);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn checkout_failing_after_read_tree_restores_previous_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = GitRepo::new(dir.path());
+        repo.switch_context("work").unwrap();
+        fs::write(dir.path().join("a.txt"), "new").unwrap();
+        fs::write(dir.path().join("added.txt"), "only in work").unwrap();
+        let tree = repo.create_tree().unwrap();
+        let work_index = fs::read(repo.context_path("work").join("index")).unwrap();
+        repo.switch_context("origin").unwrap();
+        fs::remove_file(dir.path().join("added.txt")).unwrap();
+        fs::write(dir.path().join("a.txt"), "old").unwrap();
+        fs::write(dir.path().join("gone.txt"), "only in origin").unwrap();
+        let origin_tree = repo.create_tree().unwrap();
+        let origin_index = fs::read(repo.context_path("origin").join("index")).unwrap();
+
+        let node = Node {
+            id: NodeId("n".into()),
+            parents: vec![],
+            children: Default::default(),
+            author: Author { name: "a".into(), email: "a@b".into() },
+            co_authors: vec![],
+            message: "n".into(),
+            created_at: chrono::Utc::now(),
+            commit: None,
+            tz_offset: 0,
+            payload: gpp_core::NodePayload { tree_id: tree },
+            remotes: Default::default(),
+            tags: Default::default(),
+            metadata: Default::default(),
+        };
+
+        // закреплённый хендл read-tree выполняет, а переключить .git не может
+        let pinned = repo.with_context("origin").unwrap();
+        let err = pinned.checkout_node(&node, "work").unwrap_err();
+        assert!(err.to_string().contains("pinned"), "{}", err);
+
+        assert_eq!(repo.active_context().unwrap().as_deref(), Some("origin"));
+        assert_eq!(fs::read_to_string(dir.path().join("a.txt")).unwrap(), "old");
+        assert_eq!(fs::read_to_string(dir.path().join("gone.txt")).unwrap(), "only in origin");
+        assert!(!dir.path().join("added.txt").exists());
+        assert_eq!(fs::read(repo.context_path("origin").join("index")).unwrap(), origin_index);
+        assert_eq!(fs::read(repo.context_path("work").join("index")).unwrap(), work_index);
+        assert_eq!(repo.run_git_in_context("origin", &["write-tree"]).unwrap(), origin_tree);
+        assert_eq!(repo.run_git_in_context("origin", &["diff-files", "--name-only"]).unwrap(), "");
+        assert!(!repo.context_path("work").join("index.gpp-checkout").exists());
+    }
+
+    #[test]
+    fn junction_made_on_windows_is_understood_and_replaced() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = GitRepo::new(dir.path());
+        repo.init_context("origin", None).unwrap();
+        // так junction с windows выглядит с сетевого диска: абсолютный путь, который здесь не разрешается
+        symlink(r"C:\proj\.git_origin", dir.path().join(".git")).unwrap();
+
+        assert_eq!(repo.git_link_kind().unwrap(), Some(GitLinkKind::Junction));
+        assert!(GitLinkKind::Junction.is_foreign());
+        assert_eq!(repo.active_context().unwrap().as_deref(), Some("origin"));
+
+        repo.switch_context("work").unwrap();
+        assert_eq!(repo.git_link_kind().unwrap(), Some(GitLinkKind::Symlink));
+        assert_eq!(fs::read_link(dir.path().join(".git")).unwrap(), Path::new(".git_work"));
+        assert_eq!(repo.active_context().unwrap().as_deref(), Some("work"));
+    }
+
+    #[test]
+    fn plain_git_directory_is_not_removed() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join(".git/objects")).unwrap();
+        let repo = GitRepo::new(dir.path());
+
+        assert_eq!(repo.git_link_kind().unwrap(), Some(GitLinkKind::Plain));
+        assert!(repo.switch_context("origin").is_err());
+        assert!(dir.path().join(".git/objects").is_dir());
+    }
+
+    #[test]
+    fn numstat_survives_non_utf8_paths() {
+        let mut output = b"3\t1\t\xd0\xbf\xd1\x83\xd1\x82\xd1\x8c.txt\0".to_vec();
+        output.extend_from_slice(b"-\t-\tlatin1-\xe9.bin\0");
+        output.extend_from_slice(b"0\t2\tplain.txt\0");
+
+        let stat = parse_numstat(&output);
+        assert_eq!((stat.files, stat.insertions, stat.deletions), (3, 3, 3));
+    }
+
+    #[test]
+    fn only_network_failures_count_as_transient() {
+        let dns = "fatal: unable to access 'https://example.com/x.git/': Could not resolve host: example.com";
+        assert_eq!(transient_network_error(dns), Some(dns));
+        let reset = "error: RPC failed; curl 56 Recv failure: Connection reset by peer\nfatal: the remote end hung up unexpectedly";
+        assert_eq!(transient_network_error(reset), Some("error: RPC failed; curl 56 Recv failure: Connection reset by peer"));
+
+        let auth = "git@example.com: Permission denied (publickey).\nfatal: Could not read from remote repository.";
+        assert_eq!(transient_network_error(auth), None);
+        let rejected = " ! [rejected]        abc -> main (fetch first)\nerror: failed to push some refs";
+        assert_eq!(transient_network_error(rejected), None);
+        assert_eq!(transient_network_error("fatal: 'x' does not appear to be a git repository"), None);
+    }
 }
@@ -0,0 +1,234 @@
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use gpp_core::ids::{CommitHashIds, ContentHashIds, IdStrategy};
+use gpp_core::text::Lang;
+use gpp_core::{DenyRule, RetryPolicy};
+use gpp_core::types::{validate_remote_url, RemoteRef, DEFAULT_ABBREV_LEN};
+use gpp_core::version_graph::{InheritPolicy, TreeSource};
+
+// Настройки репозитория в .gitpp/config, по строке `ключ = значение`.
+// Ключи фиксированы, чтобы опечатка не превращалась в молча игнорируемую настройку.
+
+const CONFIG_FILE: &str = "config";
+
+/// Известные ключи и их допустимые значения (None - любое значение)
+const KNOWN_KEYS: &[(&str, Option<&[&str]>)] = &[
+    ("remotes.inherit", Some(&["union", "intersection"])),
+    // из чего add собирает дерево: index - через индекс git, worktree - не трогая его
+    ("tree.source", Some(&["index", "worktree"])),
+    // id новых нод: commit - хеш git-коммита, content - хеш содержимого ноды, от git не зависит
+    ("graph.ids", Some(&["commit", "content"])),
+    // секунды на push/clone, 0 - без ограничения
+    ("net.timeout", None),
+    // сколько раз повторить push после временного сбоя сети, 0 - не повторять
+    ("net.retries", None),
+    // URL для push, если в ноде его нет: "git@host:team/{remote}.git"
+    ("push.url-template", None),
+    // язык вывода; переменная окружения GPP_LANG важнее
+    ("ui.lang", Some(&["en", "ru"])),
+    // сколько символов id показывать в логе, сообщениях и GUI
+    ("ui.abbrev-len", None),
+];
+
+pub struct Config {
+    path: PathBuf,
+    values: BTreeMap<String, String>,
+}
+
+impl Config {
+    pub fn load(gpp_dir: &Path) -> Result<Self> {
+        let path = gpp_dir.join(CONFIG_FILE);
+        let mut values = BTreeMap::new();
+        if path.exists() {
+            let content = fs::read_to_string(&path).context("Failed to read .gitpp/config")?;
+            for (n, line) in content.lines().enumerate() {
+                let line = line.trim();
+                if line.is_empty() || line.starts_with('#') {
+                    continue;
+                }
+                let (key, value) = line
+                    .split_once('=')
+                    .ok_or_else(|| anyhow::anyhow!("{}:{}: expected `key = value`", path.display(), n + 1))?;
+                values.insert(key.trim().to_string(), value.trim().to_string());
+            }
+        }
+        Ok(Self { path, values })
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(String::as_str)
+    }
+
+    pub fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        if let Some(name) = remote_url_key(key) {
+            RemoteRef::without_url(name).map_err(|e| anyhow::anyhow!(e))?;
+            if !value.is_empty() {
+                validate_remote_url(value).map_err(|e| anyhow::anyhow!(e))?;
+            }
+            self.values.insert(key.to_string(), value.to_string());
+            return Ok(());
+        }
+        if let Some(name) = remote_deny_key(key) {
+            RemoteRef::without_url(name).map_err(|e| anyhow::anyhow!(e))?;
+            parse_deny_rules(value)?;
+            self.values.insert(key.to_string(), value.to_string());
+            return Ok(());
+        }
+        let allowed = KNOWN_KEYS
+            .iter()
+            .find(|(k, _)| *k == key)
+            .map(|(_, allowed)| *allowed)
+            .ok_or_else(|| anyhow::anyhow!("Unknown config key '{}'", key))?;
+        if let Some(allowed) = allowed {
+            if !allowed.contains(&value) {
+                anyhow::bail!("Invalid value '{}' for {}: expected one of {}", value, key, allowed.join(", "));
+            }
+        }
+        if key == "net.timeout" {
+            parse_timeout(value)?;
+        }
+        if key == "net.retries" {
+            parse_retries(value)?;
+        }
+        if key == "ui.abbrev-len" {
+            parse_abbrev_len(value)?;
+        }
+        self.values.insert(key.to_string(), value.to_string());
+        Ok(())
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let content: String = self.values.iter().map(|(k, v)| format!("{} = {}\n", k, v)).collect();
+        fs::write(&self.path, content).context("Failed to write .gitpp/config")
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = (&String, &String)> {
+        self.values.iter()
+    }
+
+    pub fn inherit_policy(&self) -> Result<InheritPolicy> {
+        match self.get("remotes.inherit") {
+            None | Some("union") => Ok(InheritPolicy::Union),
+            Some("intersection") => Ok(InheritPolicy::Intersection),
+            Some(other) => anyhow::bail!("Invalid remotes.inherit '{}' in .gitpp/config", other),
+        }
+    }
+
+    /// tree.source: собирать дерево через индекс контекста (по умолчанию) или мимо него
+    pub fn tree_source(&self) -> Result<TreeSource> {
+        match self.get("tree.source") {
+            None | Some("index") => Ok(TreeSource::Index),
+            Some("worktree") => Ok(TreeSource::Worktree),
+            Some(other) => anyhow::bail!("Invalid tree.source '{}' in .gitpp/config", other),
+        }
+    }
+
+    /// graph.ids: id новой ноды - хеш её коммита (по умолчанию) или хеш её содержимого
+    pub fn id_strategy(&self) -> Result<Box<dyn IdStrategy>> {
+        match self.get("graph.ids") {
+            None | Some("commit") => Ok(Box::new(CommitHashIds)),
+            Some("content") => Ok(Box::new(ContentHashIds)),
+            Some(other) => anyhow::bail!("Invalid graph.ids '{}' in .gitpp/config", other),
+        }
+    }
+
+    pub fn push_url_template(&self) -> Option<String> {
+        self.get("push.url-template").map(str::to_string)
+    }
+
+    /// Ремоуты, объявленные через remote.<name>.url (в init или руками). URL может быть пустым:
+    /// ремоут объявлен, но адреса у него пока нет.
+    pub fn declared_remotes(&self) -> Result<Vec<RemoteRef>> {
+        self.values
+            .iter()
+            .filter_map(|(key, url)| remote_url_key(key).map(|name| (name, url)))
+            .map(|(name, url)| {
+                let remote = if url.is_empty() { RemoteRef::without_url(name) } else { RemoteRef::new(name, url.clone()) };
+                remote.map_err(|e| anyhow::anyhow!("Invalid remote.{}.url in .gitpp/config: {}", name, e))
+            })
+            .collect()
+    }
+
+    /// remote.<name>.deny: правила через запятую (tag:<имя>, meta:<ключ>[=<значение>]) - ноды,
+    /// которые на этот ремоут не уходят никогда, какие бы права у них ни были
+    pub fn push_deny(&self) -> Result<Vec<(String, Vec<DenyRule>)>> {
+        self.values
+            .iter()
+            .filter_map(|(key, value)| remote_deny_key(key).map(|name| (name, value)))
+            .map(|(name, value)| {
+                let rules = parse_deny_rules(value).with_context(|| format!("Invalid remote.{}.deny in .gitpp/config", name))?;
+                Ok((name.to_string(), rules))
+            })
+            .collect()
+    }
+
+    /// net.retries: повторы push после сетевой ошибки, пауза между ними растёт вдвое. Не задан - 2.
+    pub fn push_retry(&self) -> Result<RetryPolicy> {
+        let retries = self.get("net.retries").map_or(Ok(RetryPolicy::default().retries), parse_retries)?;
+        Ok(RetryPolicy { retries, ..RetryPolicy::default() })
+    }
+
+    /// ui.lang: язык сообщений. Не задан - английский.
+    pub fn lang(&self) -> Result<Lang> {
+        self.get("ui.lang")
+            .map_or(Ok(Lang::default()), |value| value.parse())
+            .map_err(|e| anyhow::anyhow!("Invalid ui.lang in .gitpp/config: {}", e))
+    }
+
+    /// ui.abbrev-len: длина короткого id. Не задан - 7, как у git.
+    pub fn abbrev_len(&self) -> Result<usize> {
+        self.get("ui.abbrev-len")
+            .map_or(Ok(DEFAULT_ABBREV_LEN), parse_abbrev_len)
+            .map_err(|e| anyhow::anyhow!("Invalid ui.abbrev-len in .gitpp/config: {}", e))
+    }
+
+    /// net.timeout: сколько ждать сетевые команды git. Не задан или 0 - без ограничения.
+    pub fn network_timeout(&self) -> Result<Option<Duration>> {
+        self.get("net.timeout").map_or(Ok(None), parse_timeout)
+    }
+}
+
+/// "remote.<name>.url" -> имя ремоута
+fn remote_url_key(key: &str) -> Option<&str> {
+    key.strip_prefix("remote.")?.strip_suffix(".url").filter(|name| !name.is_empty())
+}
+
+fn remote_deny_key(key: &str) -> Option<&str> {
+    key.strip_prefix("remote.")?.strip_suffix(".deny").filter(|name| !name.is_empty())
+}
+
+fn parse_deny_rules(value: &str) -> Result<Vec<DenyRule>> {
+    value
+        .split(',')
+        .filter(|rule| !rule.trim().is_empty())
+        .map(|rule| rule.parse().map_err(|e: String| anyhow::anyhow!(e)))
+        .collect()
+}
+
+fn parse_retries(value: &str) -> Result<u32> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Invalid retries '{}': expected a whole number", value))
+}
+
+/// Короче 4 символов префикс id уже не принимается как ревизия, длиннее 40 у sha-1 не бывает
+fn parse_abbrev_len(value: &str) -> Result<usize> {
+    match value.trim().parse() {
+        Ok(len @ 4..=40) => Ok(len),
+        _ => anyhow::bail!("Invalid abbrev length '{}': expected a number from 4 to 40", value),
+    }
+}
+
+/// Секунды таймаута: "30" -> 30s, "0" -> без ограничения
+pub fn parse_timeout(value: &str) -> Result<Option<Duration>> {
+    let secs: u64 = value
+        .trim()
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Invalid timeout '{}': expected whole seconds", value))?;
+    Ok((secs > 0).then(|| Duration::from_secs(secs)))
+}
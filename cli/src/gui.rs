@@ -1,8 +1,10 @@
 use eframe::egui::{self, Color32, Pos2, Rect, Stroke, Vec2, FontId};
 use std::collections::{HashMap, HashSet};
-use std::fs;
+use std::path::PathBuf;
 use gpp_core::types::{Node, NodeId};
 
+use crate::repository::Repository;
+
 // --- КОНСТАНТЫ ОТРИСОВКИ ---
 const NODE_RADIUS: f32 = 10.0;   // Радиус узла
 const Y_SPACING: f32 = 80.0;     // Вертикальный отступ между поколениями
@@ -12,7 +14,7 @@ const PADDING: f32 = 60.0;       // Отступ от краев окна
 const FONT_SIZE: f32 = 14.0;     // Размер шрифта
 const MAX_MSG_LEN: usize = 10;   // Максимальная длина сообщения перед обрезкой
 
-pub fn run_gui() -> Result<(), eframe::Error> {
+pub fn run_gui(repo_root: PathBuf) -> Result<(), eframe::Error> {
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default().with_inner_size([1200.0, 800.0]),
         ..Default::default()
@@ -21,7 +23,7 @@ pub fn run_gui() -> Result<(), eframe::Error> {
     eframe::run_native(
         "Git++ Visualizer",
         options,
-        Box::new(|_cc| Box::new(GppApp::new())),
+        Box::new(|_cc| Box::new(GppApp::new(repo_root))),
     )
 }
 
@@ -32,6 +34,7 @@ struct VisualNode {
     row: usize,       // Y - поколение
     x: f32,           // X - точная позиция
     color: Color32,
+    is_merge: bool,   // больше одного родителя - рисуется двойным контуром
 }
 
 // --- ПАЛИТРА И СМЕШИВАНИЕ (CMY) ---
@@ -107,6 +110,8 @@ impl Palette {
 }
 
 struct GppApp {
+    /// Папка репозитория (в ней лежит .gitpp)
+    repo_root: PathBuf,
     raw_nodes: HashMap<NodeId, Node>,
     visual_nodes: HashMap<NodeId, VisualNode>,
     connections: Vec<(NodeId, NodeId)>,
@@ -119,8 +124,9 @@ struct GppApp {
 }
 
 impl GppApp {
-    fn new() -> Self {
+    fn new(repo_root: PathBuf) -> Self {
         let mut app = Self {
+            repo_root,
             raw_nodes: HashMap::new(),
             visual_nodes: HashMap::new(),
             connections: Vec::new(),
@@ -140,15 +146,9 @@ impl GppApp {
     }
 
     fn load_graph(&mut self) -> anyhow::Result<()> {
-        let current_dir = std::env::current_dir()?;
-        let db_path = current_dir.join(".gitpp").join("graph.json");
-        
-        if !db_path.exists() {
-            return Err(anyhow::anyhow!("Repo not found at {:?}. Run 'gpp init' first.", db_path));
-        }
-
-        let content = fs::read_to_string(db_path)?;
-        self.raw_nodes = serde_json::from_str(&content)?;
+        // открывается заново на каждую загрузку, чтобы Retry видел свежий graph.json
+        let repo = Repository::open(&self.repo_root)?;
+        self.raw_nodes = repo.nodes()?;
 
         self.palette.assign_colors(&self.raw_nodes);
         
@@ -161,106 +161,144 @@ impl GppApp {
 
         if self.raw_nodes.is_empty() { return; }
 
-        let mut roots: Vec<NodeId> = self.raw_nodes.values()
-            .filter(|n| n.parents.is_empty())
-            .map(|n| n.id.clone())
-            .collect();
-        
-        roots.sort_by(|a, b| a.0.cmp(&b.0));
-
-        let mut visited = HashSet::new();
         let mut current_global_x = 0.0;
 
-        for root in roots {
-            let tree_width_used = self.layout_tree(
-                &root, 
-                0,           // row
-                current_global_x, // base_x
-                0,          // depth
-                &mut visited
-            );
-
-            current_global_x += tree_width_used + TREE_GAP;
-        }
-        
-        self.max_row = self.visual_nodes.values().map(|n| n.row).max().unwrap_or(0);
-        self.total_width = current_global_x;
-    }
-
-    fn layout_tree(
-        &mut self, 
-        node_id: &NodeId, 
-        row: usize, 
-        base_x: f32,      
-        depth: usize,     
-        visited: &mut HashSet<NodeId>,
-    ) -> f32 {
-        if visited.contains(node_id) { return 0.0; }
-        visited.insert(node_id.clone());
-
-        let node = match self.raw_nodes.get(node_id) {
-            Some(n) => n,
-            None => return 0.0,
-        };
+        // независимые графы (компоненты связности) рисуются рядом, как раньше отдельные деревья
+        for component in components(&self.raw_nodes) {
+            let mut component_width: f32 = 0.0;
 
-        // --- ЦВЕТ ---
-        let node_color = self.palette.get_mixed_color(&node.remotes);
+            for (id, (row, slot)) in layered_layout(&self.raw_nodes, &component) {
+                let node = &self.raw_nodes[&id];
+                let node_x_offset = slot as f32 * BRANCH_STEP;
 
-        // --- ПОЗИЦИЯ ---
-        let node_x_offset = depth as f32 * BRANCH_STEP;
-        let absolute_x = base_x + node_x_offset;
+                // --- ТЕКСТ (ОБРЕЗКА) ---
+                let full_msg = node.message.lines().next().unwrap_or("").to_string();
+                let display_msg = if full_msg.chars().count() > MAX_MSG_LEN {
+                    let truncated: String = full_msg.chars().take(MAX_MSG_LEN).collect();
+                    format!("{}...", truncated)
+                } else {
+                    full_msg
+                };
+                let text_width = estimate_text_width(&display_msg);
+                component_width = component_width.max(node_x_offset + (NODE_RADIUS * 2.0) + 10.0 + text_width);
 
-        // --- ТЕКСТ (ОБРЕЗКА) ---
-        let full_msg = node.message.lines().next().unwrap_or("").to_string();
-        let display_msg = if full_msg.chars().count() > MAX_MSG_LEN {
-            let truncated: String = full_msg.chars().take(MAX_MSG_LEN).collect();
-            format!("{}...", truncated)
-        } else {
-            full_msg
-        };
+                for child_id in &node.children {
+                    self.connections.push((id.clone(), child_id.clone()));
+                }
 
-        let text_width = estimate_text_width(&display_msg);
-        let node_width_usage = node_x_offset + (NODE_RADIUS * 2.0) + 10.0 + text_width;
+                let v_node = VisualNode {
+                    id: id.clone(),
+                    display_message: display_msg,
+                    author: node.author.name.clone(),
+                    row,
+                    x: current_global_x + node_x_offset,
+                    color: self.palette.get_mixed_color(&node.remotes),
+                    is_merge: node.parents.len() > 1,
+                };
+                self.visual_nodes.insert(id, v_node);
+            }
 
-        let v_node = VisualNode {
-            id: node_id.clone(),
-            display_message: display_msg,
-            author: node.author.name.clone(),
-            row,
-            x: absolute_x,
-            color: node_color,
-        };
-        self.visual_nodes.insert(node_id.clone(), v_node);
+            current_global_x += component_width + TREE_GAP;
+        }
 
-        // --- ДЕТИ ---
-        let mut children_vec: Vec<NodeId> = node.children.iter().cloned().collect();
-        children_vec.sort_by(|a, b| a.0.cmp(&b.0));
+        self.max_row = self.visual_nodes.values().map(|n| n.row).max().unwrap_or(0);
+        self.total_width = current_global_x;
+    }
+}
 
-        let mut max_width_in_subtree = node_width_usage;
+/// Компоненты связности графа (по родителям и детям), каждая - отсортированный список id
+fn components(nodes: &HashMap<NodeId, Node>) -> Vec<Vec<NodeId>> {
+    let mut ids: Vec<&NodeId> = nodes.keys().collect();
+    ids.sort();
+
+    let mut seen = HashSet::new();
+    let mut result = Vec::new();
+    for start in ids {
+        if seen.contains(start) { continue; }
+        let mut component = Vec::new();
+        let mut stack = vec![start.clone()];
+        while let Some(id) = stack.pop() {
+            if !seen.insert(id.clone()) { continue; }
+            if let Some(node) = nodes.get(&id) {
+                stack.extend(node.parents.iter().chain(node.children.iter()).filter(|n| nodes.contains_key(*n)).cloned());
+            }
+            component.push(id);
+        }
+        component.sort();
+        result.push(component);
+    }
+    result
+}
 
-        for (i, child_id) in children_vec.iter().enumerate() {
-            self.connections.push((node_id.clone(), child_id.clone()));
+/// Послойная укладка DAG: слой ноды - длина самого длинного пути от корня (так мерж всегда ниже
+/// обоих родителей), порядок внутри слоя - барицентрическая эвристика против пересечений рёбер.
+/// Возвращает id -> (слой, позиция в слое).
+fn layered_layout(nodes: &HashMap<NodeId, Node>, component: &[NodeId]) -> HashMap<NodeId, (usize, usize)> {
+    let in_component: HashSet<&NodeId> = component.iter().collect();
+    let parents_of = |id: &NodeId| -> Vec<NodeId> {
+        nodes[id].parents.iter().filter(|p| in_component.contains(p)).cloned().collect()
+    };
+    let children_of = |id: &NodeId| -> Vec<NodeId> {
+        let mut children: Vec<NodeId> = nodes[id].children.iter().filter(|c| in_component.contains(c)).cloned().collect();
+        children.sort();
+        children
+    };
 
-            // ВЕЕРНОЕ РАСПОЛОЖЕНИЕ:
-            // Каждый следующий ребенок уходит глубже вправо (depth + i), 
-            // чтобы не накладываться на предыдущего.
-            let next_depth = depth + i;
-            
-            let child_width = self.layout_tree(
-                child_id, 
-                row + 1, 
-                base_x, 
-                next_depth, 
-                visited
-            );
-            
-            if child_width > max_width_in_subtree {
-                max_width_in_subtree = child_width;
+    // --- СЛОИ (Кан: нода обрабатывается, когда готовы все её родители) ---
+    let mut pending: HashMap<NodeId, usize> = component.iter().map(|id| (id.clone(), parents_of(id).len())).collect();
+    let mut queue: Vec<NodeId> = component.iter().filter(|id| pending[*id] == 0).cloned().collect();
+    let mut layer_of: HashMap<NodeId, usize> = HashMap::new();
+    let mut order = Vec::new();
+    while let Some(id) = queue.pop() {
+        let layer = parents_of(&id).iter().filter_map(|p| layer_of.get(p)).map(|l| l + 1).max().unwrap_or(0);
+        layer_of.insert(id.clone(), layer);
+        for child in children_of(&id).into_iter().rev() {
+            if let Some(count) = pending.get_mut(&child) {
+                *count -= 1;
+                if *count == 0 { queue.push(child); }
             }
         }
+        order.push(id);
+    }
+
+    let depth = layer_of.values().max().map_or(0, |m| m + 1);
+    let mut layers: Vec<Vec<NodeId>> = vec![Vec::new(); depth];
+    for id in order {
+        layers[layer_of[&id]].push(id);
+    }
 
-        max_width_in_subtree
+    // --- ПОРЯДОК ВНУТРИ СЛОЁВ: несколько проходов вниз и вверх по среднему положению соседей ---
+    let position = |layers: &Vec<Vec<NodeId>>| -> HashMap<NodeId, usize> {
+        layers.iter().flat_map(|l| l.iter().enumerate().map(|(i, id)| (id.clone(), i))).collect()
+    };
+    let reorder = |layer: &mut Vec<NodeId>, pos: &HashMap<NodeId, usize>, neighbours: &dyn Fn(&NodeId) -> Vec<NodeId>| {
+        let mut keyed: Vec<(f32, usize, NodeId)> = layer
+            .drain(..)
+            .enumerate()
+            .map(|(i, id)| {
+                let slots: Vec<usize> = neighbours(&id).iter().filter_map(|n| pos.get(n).copied()).collect();
+                let key = if slots.is_empty() { i as f32 } else { slots.iter().sum::<usize>() as f32 / slots.len() as f32 };
+                (key, i, id)
+            })
+            .collect();
+        keyed.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+        layer.extend(keyed.into_iter().map(|(_, _, id)| id));
+    };
+    for _ in 0..4 {
+        for i in 1..layers.len() {
+            let pos = position(&layers);
+            reorder(&mut layers[i], &pos, &parents_of);
+        }
+        for i in (0..layers.len().saturating_sub(1)).rev() {
+            let pos = position(&layers);
+            reorder(&mut layers[i], &pos, &children_of);
+        }
     }
+
+    position(&layers).into_iter().map(|(id, slot)| {
+        let layer = layer_of[&id];
+        (id, (layer, slot))
+    }).collect()
 }
 
 fn estimate_text_width(msg: &str) -> f32 {
@@ -315,6 +353,16 @@ impl eframe::App for GppApp {
                     ui.label("=");
                     ui.label(egui::RichText::new("Blue").color(Color32::from_rgb(50, 50, 255)));
                 });
+
+                ui.add_space(20.0);
+                ui.heading("Nodes");
+                ui.separator();
+                ui.horizontal(|ui| {
+                    let (rect, _) = ui.allocate_exact_size(Vec2::splat(16.0), egui::Sense::hover());
+                    ui.painter().circle_stroke(rect.center(), 4.0, Stroke::new(1.5, Color32::WHITE));
+                    ui.painter().circle_stroke(rect.center(), 7.0, Stroke::new(1.5, Color32::WHITE));
+                    ui.label("Merge");
+                });
             });
 
         // --- ГРАФ ---
@@ -377,12 +425,15 @@ impl eframe::App for GppApp {
                     
                     painter.circle_filled(center, NODE_RADIUS, node.color);
                     painter.circle_stroke(center, NODE_RADIUS, Stroke::new(1.5, Color32::WHITE));
+                    if node.is_merge {
+                        painter.circle_stroke(center, NODE_RADIUS + 4.0, Stroke::new(1.5, Color32::WHITE));
+                    }
 
                     let text_pos = center + Vec2::new(NODE_RADIUS + 8.0, 0.0);
                     painter.text(
                         text_pos,
                         egui::Align2::LEFT_CENTER,
-                        format!("{} ({})", node.display_message, &node.id.0[..6]),
+                        format!("{} ({})", node.display_message, node.id.short()),
                         FontId::proportional(FONT_SIZE),
                         Color32::LIGHT_GRAY,
                     );
@@ -399,6 +450,10 @@ impl eframe::App for GppApp {
                                 if let Some(raw) = self.raw_nodes.get(&node.id) {
                                     let remotes: Vec<_> = raw.remotes.iter().map(|r| r.name.as_str()).collect();
                                     ui.colored_label(Color32::LIGHT_BLUE, format!("Remotes: {:?}", remotes));
+                                    if raw.parents.len() > 1 {
+                                        let parents: Vec<&str> = raw.parents.iter().map(|p| p.short()).collect();
+                                        ui.label(format!("Merge of: {}", parents.join(", ")));
+                                    }
                                     ui.separator();
                                     ui.label(format!("Message:\n{}", raw.message));
                                 }
@@ -409,4 +464,60 @@ impl eframe::App for GppApp {
             });
         });
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gpp_core::types::{Author, NodePayload};
+
+    fn graph(edges: &[(&str, &[&str])]) -> HashMap<NodeId, Node> {
+        let mut nodes: HashMap<NodeId, Node> = edges
+            .iter()
+            .map(|(id, parents)| {
+                let node = Node {
+                    id: NodeId(id.to_string()),
+                    parents: parents.iter().map(|p| NodeId(p.to_string())).collect(),
+                    children: HashSet::new(),
+                    author: Author { name: "a".into(), email: "a@b".into() },
+                    co_authors: vec![],
+                    message: id.to_string(),
+                    created_at: chrono::Utc::now(),
+                    commit: None,
+                    tz_offset: 0,
+                    payload: NodePayload { tree_id: "t".into() },
+                    remotes: HashSet::new(),
+                    tags: HashMap::new(),
+                    metadata: HashMap::new(),
+                };
+                (node.id.clone(), node)
+            })
+            .collect();
+        for (id, parents) in edges {
+            for p in parents.iter() {
+                nodes.get_mut(&NodeId(p.to_string())).unwrap().children.insert(NodeId(id.to_string()));
+            }
+        }
+        nodes
+    }
+
+    #[test]
+    fn merge_is_placed_below_its_deepest_parent() {
+        // правая ветка длиннее левой: мерж должен встать под неё, а не под первого родителя
+        let nodes = graph(&[("r", &[]), ("a", &["r"]), ("b1", &["r"]), ("b2", &["b1"]), ("m", &["a", "b2"])]);
+        let comps = components(&nodes);
+        assert_eq!(comps.len(), 1);
+
+        let layout = layered_layout(&nodes, &comps[0]);
+        let row = |id: &str| layout[&NodeId(id.into())].0;
+        assert_eq!(row("r"), 0);
+        assert_eq!(row("a"), 1);
+        assert_eq!(row("b2"), 2);
+        assert_eq!(row("m"), 3);
+    }
+
+    #[test]
+    fn separate_roots_form_separate_components() {
+        let nodes = graph(&[("r1", &[]), ("c1", &["r1"]), ("r2", &[])]);
+        assert_eq!(components(&nodes).len(), 2);
+    }
+}
@@ -0,0 +1,155 @@
+use colored::*;
+use gpp_core::backend::DiffStat;
+use gpp_core::LogEntry;
+use gpp_core::types::{Node, NodeId};
+
+// Отображение результата gpp log. Dispatcher отдаёт только данные, все форматы строятся здесь из них.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogFormat {
+    /// Блок на ноду, из сообщения только первая строка (заголовок)
+    Medium,
+    /// То же, но сообщение целиком, с описанием
+    Full,
+    Oneline,
+    Json,
+}
+
+/// В каком поясе показывать Date (--date)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DateMode {
+    /// В поясе автора, как git log по умолчанию
+    Author,
+    /// В поясе этой машины (--date=local)
+    Local,
+    Utc,
+}
+
+impl DateMode {
+    pub fn parse(value: &str) -> Self {
+        match value {
+            "local" => DateMode::Local,
+            "utc" => DateMode::Utc,
+            _ => DateMode::Author,
+        }
+    }
+
+    fn format(self, node: &Node) -> String {
+        const FORMAT: &str = "%Y-%m-%d %H:%M:%S %z";
+        match self {
+            DateMode::Author => node.authored_at().format(FORMAT).to_string(),
+            DateMode::Local => node.created_at.with_timezone(&chrono::Local).format(FORMAT).to_string(),
+            DateMode::Utc => node.created_at.format(FORMAT).to_string(),
+        }
+    }
+}
+
+const SEPARATOR: &str = "------------------------------";
+// ширина самой длинной метки ("Co-author: "), по ней выравниваются значения
+const LABEL_WIDTH: usize = 11;
+
+pub fn render(entries: &[LogEntry], format: LogFormat, dates: DateMode) -> anyhow::Result<String> {
+    if format == LogFormat::Json {
+        return Ok(serde_json::to_string_pretty(entries)?);
+    }
+
+    let mut lines = Vec::new();
+    for entry in entries {
+        match (&entry.graph, format) {
+            // граф всегда рисуется по строке на ноду
+            (Some(graph), _) => {
+                lines.extend(graph.connectors.iter().cloned());
+                lines.push(format!("{} {}", graph.prefix, oneline(entry)));
+            }
+            (None, LogFormat::Oneline) => lines.push(oneline(entry)),
+            (None, _) => {
+                full(&entry.node, format == LogFormat::Full, dates, &mut lines);
+                if let Some(stat) = &entry.stat {
+                    field(&mut lines, "Changes", stat_summary(stat));
+                }
+                lines.push(SEPARATOR.to_string());
+            }
+        }
+    }
+    Ok(lines.join("\n"))
+}
+
+fn summary(node: &Node) -> &str {
+    node.message.lines().next().unwrap_or_default()
+}
+
+fn oneline(entry: &LogEntry) -> String {
+    let line = format!("{} {}", entry.node.id.short().yellow(), summary(&entry.node));
+    match &entry.stat {
+        Some(stat) => format!("{} ({})", line, stat_summary(stat).dimmed()),
+        None => line,
+    }
+}
+
+/// Как последняя строка `git log --stat`: "2 files changed, 5 insertions(+), 1 deletion(-)"
+fn stat_summary(stat: &DiffStat) -> String {
+    let plural = |n: usize, one: &str, many: &str| format!("{} {}", n, if n == 1 { one } else { many });
+    let mut parts = vec![plural(stat.files, "file changed", "files changed")];
+    if stat.insertions > 0 {
+        parts.push(plural(stat.insertions, "insertion(+)", "insertions(+)"));
+    }
+    if stat.deletions > 0 {
+        parts.push(plural(stat.deletions, "deletion(-)", "deletions(-)"));
+    }
+    parts.join(", ")
+}
+
+fn field(lines: &mut Vec<String>, label: &str, value: impl std::fmt::Display) {
+    lines.push(format!("{:<width$}{}", format!("{}:", label), value, width = LABEL_WIDTH));
+}
+
+fn id_list(ids: impl IntoIterator<Item = NodeId>) -> String {
+    ids.into_iter().map(|id| id.0).collect::<Vec<_>>().join(", ")
+}
+
+fn full(node: &Node, with_body: bool, dates: DateMode, lines: &mut Vec<String>) {
+    field(lines, "Commit", node.id.0.yellow());
+    if node.parents.len() > 1 {
+        let short: Vec<&str> = node.parents.iter().map(|p| p.short()).collect();
+        field(lines, "Merge", short.join(" "));
+    }
+    field(lines, "Author", node.author.to_string().cyan());
+    for co_author in &node.co_authors {
+        field(lines, "Co-author", co_author.to_string().cyan());
+    }
+    field(lines, "Date", dates.format(node));
+
+    let mut message = node.message.lines();
+    field(lines, "Message", message.next().unwrap_or_default());
+    if with_body {
+        for line in message {
+            lines.push(format!("{:width$}{}", "", line, width = LABEL_WIDTH).trim_end().to_string());
+        }
+    }
+
+    let mut remotes: Vec<&str> = node.remotes.iter().map(|r| r.name.as_str()).collect();
+    remotes.sort();
+    field(lines, "Remotes", remotes.join(", "));
+
+    if !node.tags.is_empty() {
+        let mut tags: Vec<_> = node.tags.values().collect();
+        tags.sort_by(|a, b| a.name.cmp(&b.name));
+        let tags: Vec<String> = tags
+            .into_iter()
+            .map(|tag| match tag.meta.get("message").and_then(|m| m.lines().next()) {
+                Some(message) => format!("{} ({})", tag.name, message),
+                None => tag.name.clone(),
+            })
+            .collect();
+        field(lines, "Tags", tags.join(", "));
+    }
+
+    if !node.parents.is_empty() {
+        field(lines, "Parents", id_list(node.parents.iter().cloned()));
+    }
+    if !node.children.is_empty() {
+        let mut children: Vec<NodeId> = node.children.iter().cloned().collect();
+        children.sort();
+        field(lines, "Children", id_list(children));
+    }
+}
@@ -1,23 +1,40 @@
+mod config;
 mod gui;
-use clap::{Parser, Subcommand};
+mod log_view;
+mod repository;
+mod snapshot;
+use clap::{CommandFactory, Parser, Subcommand};
+use clap_complete::Shell;
 use anyhow::{Context, Result};
+use std::collections::HashSet;
 use std::fs;
-use std::io::Write;
+use std::path::{Path, PathBuf};
 use colored::*;
-use dialoguer::{Input};
+use dialoguer::MultiSelect;
+use indicatif::ProgressBar;
 
-use gpp_core::types::{Author, NodeId};
+use gpp_core::types::{normalize_remote_url, remote_name_key, validate_remote_url, Author, NodeId, RemoteRef};
 use gpp_core::version_graph::VersionGraph;
-use gpp_core::dispatcher::{CommandDispatcher, Command, CmdResult};
+use gpp_core::backend::{GraphOps, Progress, RepoBackend};
+use gpp_core::dispatcher::{Command, CmdResult};
+use gpp_core::{parse_date, LogFilter, TextMatch};
+use gpp_core::RevContext;
+use gpp_core::text;
 
 use backend_git::git_repo::GitRepo;
-use storage_file::json_storage::JsonStorage;
+use storage_file::json_storage::{JsonStorage, RepairOutcome};
 
-use tracing_subscriber;
+use repository::Repository;
+
+use tracing_subscriber::filter::LevelFilter;
 
 #[derive(Parser)]
 #[command(name = "gpp")]
 struct Cli {
+    #[arg(long, global = true, env = "GPP_DIR", help = "Repository directory (the one with .gitpp) instead of searching up from the current one")]
+    repo: Option<PathBuf>,
+    #[arg(short, long, global = true, help = "Only errors and the result itself: the new node id for add, Pushed lines for push")]
+    quiet: bool,
     #[command(subcommand)]
     command: Commands,
 }
@@ -25,18 +42,71 @@ struct Cli {
 #[derive(Subcommand)]
 enum Commands {
     Init {
-        #[arg(num_args = 0.., help = "Список контекстов (remotes)")]
+        #[arg(num_args = 0.., help = "Contexts (remotes) to create, NAME or NAME=URL")]
         remotes: Vec<String>,
+        #[arg(long, help = "Create the contexts of all remotes right away, not only the first one")]
+        eager: bool,
+    },
+    #[command(about = "Clone an existing git repository and build the graph from its history")]
+    Clone {
+        url: String,
+        #[arg(help = "Directory (defaults to the repository name from the URL)")]
+        dir: Option<String>,
+        #[arg(short, long, default_value = "origin", help = "Context name for the clone")]
+        remote: String,
+        #[arg(long, value_name = "SECONDS", help = "Abort the clone if it takes longer (0 means no limit)")]
+        timeout: Option<String>,
     },
     Add {
-        #[arg(short, long)]
-        message: Option<String>,
+        #[arg(short, long, help = "Message; several -m become paragraphs. Without -m $EDITOR is opened")]
+        message: Vec<String>,
         #[arg(short, long, num_args = 0..)]
         parents: Option<Vec<String>>,
+        #[arg(long, conflicts_with = "parents", help = "New root without parents, HEAD is ignored")]
+        root: bool,
         #[arg(short, long, num_args = 0..)]
         remotes: Option<Vec<String>>,
+        #[arg(long = "co-author", value_name = "NAME <EMAIL>", help = "Co-author (can be repeated)")]
+        co_authors: Vec<Author>,
+        #[arg(last = true, value_name = "PATHS", help = "Take only these paths (after --) into the node, the rest stays as in the parent")]
+        paths: Vec<String>,
+        #[arg(short, long, conflicts_with = "paths", help = "Pick the changed files interactively")]
+        interactive: bool,
+        #[arg(long, help = "Node and commit date (ISO 8601 or as in log --since), defaults to now")]
+        date: Option<String>,
+        #[arg(long, requires = "date", help = "Allow a --date in the future")]
+        allow_future: bool,
+        #[arg(long = "no-verify", help = "Do not run the commit hooks")]
+        no_verify: bool,
+    },
+    Log {
+        #[arg(long, help = "Only nodes whose author (name or email) contains the string")]
+        author: Option<String>,
+        #[arg(long, help = "Only nodes whose message contains the string")]
+        grep: Option<String>,
+        #[arg(long, help = "Treat --author and --grep as regular expressions")]
+        regex: bool,
+        #[arg(long, help = "Not before the date: 2024-05-01, 2.weeks, yesterday")]
+        since: Option<String>,
+        #[arg(long, help = "Not after the date")]
+        until: Option<String>,
+        #[arg(long, help = "Only nodes allowed on this remote (what it will see)")]
+        remote: Option<String>,
+        #[arg(long, help = "Draw the node graph as text art, one line per node")]
+        graph: bool,
+        #[arg(long, help = "How many files and lines the node changed against its first parent")]
+        stat: bool,
+        #[arg(long, help = "One line per node: short id and the first line of the message")]
+        oneline: bool,
+        #[arg(long, conflicts_with_all = ["oneline", "graph"], help = "The whole message, not only the first line")]
+        full: bool,
+        #[arg(long, conflicts_with_all = ["oneline", "graph"], help = "Output as JSON")]
+        json: bool,
+        #[arg(long, value_parser = ["default", "local", "utc"], default_value = "default", help = "Time zone for Date: the author's (default), this machine's or UTC")]
+        date: String,
+        #[arg(long, help = "All nodes of the graph, not only the history of HEAD")]
+        all: bool,
     },
-    Log,
     Chrm {
         #[arg(short, long)]
         remote: String,
@@ -46,6 +116,12 @@ enum Commands {
         node: Option<String>,
         #[arg(long, action)]
         remove: bool,
+        #[arg(long, help = "Add together with all ancestors, remove together with all descendants")]
+        recursive: bool,
+        #[arg(long, help = "Show which nodes would gain or lose the remote without changing anything")]
+        dry_run: bool,
+        #[arg(long, requires = "remove", conflicts_with = "recursive", help = "Remove the remote from this node only, even if its descendants keep it")]
+        force: bool,
     },
     Push {
         #[arg(short, long, default_value = "origin")]
@@ -54,36 +130,263 @@ enum Commands {
         url: Option<String>,
         #[arg(long)]
         node: Option<String>,
+        #[arg(long, conflicts_with = "node", help = "Push the node with this tag and create the tag on the remote")]
+        tag: Option<String>,
         #[arg(long)]
         dry_run: bool,
+        #[arg(long = "no-verify", help = "Do not run the pre-push hook (like git push --no-verify)")]
+        no_verify: bool,
     },
     Checkout {
-        #[arg(help = "ID ноды")]
-        node: String,
+        #[arg(required_unless_present = "orphan", help = "Revision: id or its prefix, tag, HEAD~N, HEAD^N or - (the previous HEAD)")]
+        node: Option<String>,
+        #[arg(long, conflicts_with = "node", help = "Clear HEAD: the next add creates a new root, files stay as they are")]
+        orphan: bool,
+        #[arg(long, conflicts_with = "orphan", help = "Remember the node as the tip of this remote's context (for push); the shared HEAD and files do not change")]
+        remote: Option<String>,
+    },
+    #[command(about = "Find nodes by tag and/or metadata")]
+    #[command(group(clap::ArgGroup::new("criteria").required(true).multiple(true).args(["tag", "meta"])))]
+    Find {
+        #[arg(long, help = "The node with this tag")]
+        tag: Option<String>,
+        #[arg(long, value_name = "KEY=VALUE", help = "Value in the node metadata (KEY without '=' means the key is present); can be repeated")]
+        meta: Vec<String>,
+    },
+    #[command(about = "Tag a node (HEAD by default) or list the tags")]
+    Tag {
+        #[arg(required_unless_present = "list")]
+        name: Option<String>,
+        #[arg(long, conflicts_with = "list")]
+        node: Option<String>,
+        #[arg(short, long, conflicts_with = "list", help = "Tag annotation (e.g. release notes)")]
+        message: Option<String>,
+        #[arg(short, long, conflicts_with = "name", help = "List all tags with their annotations")]
+        list: bool,
+        #[arg(long, requires = "list", value_parser = ["name", "date"], default_value = "name", help = "Order of --list")]
+        sort: String,
+    },
+    #[command(about = "Export the subgraph visible on a remote as JSON (nodes by id, as in graph.json)")]
+    Export {
+        #[arg(long)]
+        remote: String,
+    },
+    #[command(about = "Check node parents in the graph against git commits, or verify commit signatures")]
+    Verify {
+        #[arg(long, help = "Verify commit signatures (git verify-commit) instead of the edges")]
+        signatures: bool,
+        #[arg(long, requires = "signatures", help = "Only nodes that go to this remote")]
+        remote: Option<String>,
+    },
+    #[command(about = "State relative to a remote. Only --remote for now: how far HEAD is ahead and behind")]
+    Status {
+        #[arg(long, required = true, help = "Compare with this remote's tracking refs, like ahead/behind in git status")]
+        remote: String,
+    },
+    #[command(about = "Graph cleanup. Only a report for now: --dedup")]
+    Gc {
+        #[arg(long, required = true, help = "Show nodes with the same tree (candidates for redundant commits)")]
+        dedup: bool,
+    },
+    #[command(about = "Graph summary: nodes, roots, tips, depth, remotes, authors")]
+    Stats {
+        #[arg(long, help = "Output as JSON")]
+        json: bool,
+    },
+    #[command(about = "Common ancestor of two nodes (the base for merge/rebase)")]
+    MergeBase {
+        a: String,
+        b: String,
+        #[arg(long, help = "Show all candidates, not only the best one")]
+        all: bool,
     },
-    #[command(about = "Запуск графического интерфейса")]
+    #[command(about = "Switch the active context (.git -> .git_<name>) or list the contexts")]
+    Context {
+        #[arg(required_unless_present = "list")]
+        name: Option<String>,
+        #[arg(short, long, help = "List all contexts and mark the active one")]
+        list: bool,
+    },
+    #[command(about = "Stash uncommitted changes and reset the working tree to HEAD")]
+    Stash {
+        #[command(subcommand)]
+        action: Option<StashAction>,
+    },
+    #[command(about = "Show or change a repository setting (.gitpp/config)")]
+    Config {
+        #[arg(required_unless_present = "list")]
+        key: Option<String>,
+        value: Option<String>,
+        #[arg(short, long, help = "List all settings that are set")]
+        list: bool,
+    },
+    #[command(about = "Shell completion script: gpp completions bash > /etc/bash_completion.d/gpp")]
+    Completions {
+        shell: Shell,
+    },
+    #[command(about = "Start the graphical interface")]
     Gui,
+    #[command(about = "Restore a corrupted graph.json from the latest backup")]
+    Repair,
+    #[command(about = "Snapshot the graph and HEAD into .gitpp/snapshots (a safety net before risky operations)")]
+    Snapshot {
+        #[arg(long, help = "Snapshot name (defaults to the current time)")]
+        name: Option<String>,
+        #[arg(long, value_name = "NAME", conflicts_with_all = ["name", "list"])]
+        restore: Option<String>,
+        #[arg(long, conflicts_with = "name")]
+        list: bool,
+    },
+}
+
+/// Подсказки dialoguer без терминала либо падают с невнятной ошибкой, либо ждут stdin вечно
+fn require_tty(what: &str) -> Result<()> {
+    use std::io::IsTerminal;
+    if !std::io::stdin().is_terminal() || !std::io::stderr().is_terminal() {
+        anyhow::bail!("{} needs an interactive terminal", what);
+    }
+    Ok(())
+}
+
+/// Сообщение ноды через редактор, как у git commit без -m: $VISUAL, $EDITOR, иначе vi.
+/// Строки с '#' выкидываются, пустое сообщение отменяет add.
+fn edit_message(gpp_dir: &Path) -> Result<String> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .ok()
+        .filter(|e| !e.trim().is_empty());
+    if editor.is_none() {
+        // vi без терминала просто зависнет
+        require_tty("Commit message editor (set $EDITOR or pass -m)")?;
+    }
+    let editor = editor.unwrap_or_else(|| "vi".to_string());
+
+    let path = gpp_dir.join("ADD_EDITMSG");
+    fs::write(
+        &path,
+        "\n# Сообщение ноды: первая строка - заголовок, после пустой строки - описание.\n\
+         # Строки, начинающиеся с '#', не попадут в сообщение. Пустое сообщение отменяет add.\n",
+    )?;
+
+    // "code --wait" и подобное: первое слово - программа, остальное - её аргументы
+    let mut parts = editor.split_whitespace();
+    let program = parts.next().unwrap_or("vi");
+    let status = std::process::Command::new(program)
+        .args(parts)
+        .arg(&path)
+        .status()
+        .with_context(|| format!("Failed to run editor '{}'", editor))?;
+    if !status.success() {
+        anyhow::bail!("Editor '{}' exited with {}, add aborted", editor, status);
+    }
+
+    let raw = fs::read_to_string(&path)?;
+    let lines: Vec<&str> = raw.lines().filter(|l| !l.starts_with('#')).map(str::trim_end).collect();
+    let message = lines.join("\n").trim().to_string();
+    if message.is_empty() {
+        anyhow::bail!("Aborting add due to empty message");
+    }
+    Ok(message)
+}
+
+/// Ищет .gitpp в папке и выше (до корня ФС), как git ищет .git.
+/// Найденная папка - корень репозитория: рабочая директория для GitRepo и место graph.json.
+fn find_repo_root(start: &Path) -> Option<PathBuf> {
+    start.ancestors().find(|dir| dir.join(".gitpp").is_dir()).map(Path::to_path_buf)
+}
+
+/// "git@host:team/project.git" -> "project", как это делает git clone
+fn repo_name_from_url(url: &str) -> Option<String> {
+    let last = url.trim_end_matches(['/', '\\']).rsplit(['/', '\\', ':']).next()?;
+    let name = last.strip_suffix(".git").unwrap_or(last);
+    (!name.is_empty()).then(|| name.to_string())
+}
+
+#[derive(Subcommand)]
+enum StashAction {
+    #[command(about = "Restore the stashed changes")]
+    Pop,
+}
+
+/// Всё, что clone делает в папке root: .gitpp, клон в контекст remote, импорт истории и checkout HEAD.
+/// Возвращает число импортированных нод.
+fn clone_repo(root: &Path, remote: &str, upstream: RemoteRef, timeout: Option<std::time::Duration>) -> Result<usize> {
+    let clone_gpp_dir = root.join(".gitpp");
+    let clone_db_path = clone_gpp_dir.join("graph.json");
+    fs::create_dir_all(&clone_gpp_dir).context("Failed to create .gitpp")?;
+    fs::write(&clone_db_path, "{}").context("Failed to create graph.json")?;
+    JsonStorage::write_format(&clone_db_path).map_err(|e| anyhow::anyhow!(e))?;
+
+    let git = GitRepo::new(root).with_network_timeout(timeout);
+    git.clone_context(remote, &upstream.url).map_err(|e| anyhow::anyhow!("{}", e))?;
+    git.switch_context(remote).map_err(|e| anyhow::anyhow!("{}", e))?;
+
+    let history = git.list_history().map_err(|e| anyhow::anyhow!("{}", e))?;
+    let head = git.read_ref("HEAD").map_err(|e| anyhow::anyhow!("{}", e))?;
+
+    let storage = Box::new(JsonStorage::new(&clone_db_path).map_err(|e| anyhow::anyhow!(e))?);
+    let mut graph = VersionGraph::new(storage, Box::new(GitRepo::new(root)));
+    let imported = graph.import_history(history, upstream).map_err(|e| anyhow::anyhow!("{}", e))?;
+
+    if let Some(head) = head {
+        graph.checkout(&head, None).map_err(|e| anyhow::anyhow!("{}", e))?;
+        fs::write(clone_gpp_dir.join("HEAD"), &head.0)?;
+    }
+    Ok(imported)
+}
+
+/// Удаляет содержимое папки, саму папку оставляет
+fn clear_dir(dir: &Path) -> std::io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() && !path.is_symlink() {
+            fs::remove_dir_all(&path)?;
+        } else {
+            fs::remove_file(&path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Строка "Success: ..."; с --quiet не печатается
+fn print_success(msg: impl std::fmt::Display) {
+    text::status(format!("{} {}", text::success_label().green().bold(), msg));
 }
 
 fn main() -> Result<()> {
-    tracing_subscriber::fmt::init();
     let cli = Cli::parse();
+    let log_level = if cli.quiet { LevelFilter::ERROR } else { LevelFilter::INFO };
+    tracing_subscriber::fmt().with_max_level(log_level).init();
+    text::set_quiet(cli.quiet);
+    if let Commands::Completions { shell } = cli.command {
+        clap_complete::generate(shell, &mut Cli::command(), "gpp", &mut std::io::stdout());
+        return Ok(());
+    }
+
     let current_dir = std::env::current_dir()?;
-    let gpp_dir = current_dir.join(".gitpp");
+    let repo_root = match &cli.repo {
+        Some(path) => current_dir.join(path),
+        // init создаёт репозиторий ровно здесь, а не в найденном выше
+        None if matches!(cli.command, Commands::Init { .. }) => current_dir.clone(),
+        None => find_repo_root(&current_dir).unwrap_or_else(|| current_dir.clone()),
+    };
+    let gpp_dir = repo_root.join(".gitpp");
     let db_path = gpp_dir.join("graph.json");
-    let head_path = gpp_dir.join("HEAD");
 
-    if let Commands::Init { remotes } = cli.command {
+    // язык и длина id нужны до первого вывода: GPP_LANG, иначе ui.lang из конфига, иначе английский
+    let ui_config = config::Config::load(&gpp_dir)?;
+    let lang = match std::env::var("GPP_LANG") {
+        Ok(value) if !value.is_empty() => value.parse().map_err(|e| anyhow::anyhow!("Invalid GPP_LANG: {}", e))?,
+        _ => ui_config.lang()?,
+    };
+    text::set_lang(lang);
+    gpp_core::types::set_abbrev_len(ui_config.abbrev_len()?);
+
+    if let Commands::Init { remotes, eager } = cli.command {
         if gpp_dir.exists() {
-            println!("{}", "Репозиторий Git++ уже существует".yellow());
+            text::status(text::repo_exists().yellow());
             return Ok(());
         }
-        println!("{}", "Инициализация Git++...".green().bold());
-
-        fs::create_dir_all(&gpp_dir).context("Не удалось создать .gitpp")?;
-        fs::write(&db_path, "{}").context("Не удалось создать graph.json")?;
-        JsonStorage::new(&db_path).map_err(|e| anyhow::anyhow!(e))?;
-        let git = GitRepo::new(&current_dir);
 
         let targets: Vec<String> = if remotes.is_empty() {
             vec!["origin".to_string()]
@@ -91,13 +394,42 @@ fn main() -> Result<()> {
             remotes
         };
 
+        let mut seen = HashSet::new();
+        for target_spec in &targets {
+            let (name, url) = target_spec.split_once('=').map_or((target_spec.as_str(), None), |(n, u)| (n, Some(u)));
+            RemoteRef::without_url(name).map_err(|e| anyhow::anyhow!(e))?;
+            if let Some(url) = url {
+                validate_remote_url(url).map_err(|e| anyhow::anyhow!(e))?;
+            }
+            if !seen.insert(remote_name_key(name)) {
+                anyhow::bail!("Remote '{}' is listed twice (remote names are case-insensitive)", name);
+            }
+        }
+
+        text::status(text::init_started().green().bold());
+
+        fs::create_dir_all(&gpp_dir).context("Failed to create .gitpp")?;
+        fs::write(&db_path, "{}").context("Failed to create graph.json")?;
+        JsonStorage::write_format(&db_path).map_err(|e| anyhow::anyhow!(e))?;
+        JsonStorage::new(&db_path).map_err(|e| anyhow::anyhow!(e))?;
+        let git = GitRepo::new(&repo_root);
+        let mut config = config::Config::load(&gpp_dir)?;
+
         for (i, target_spec) in targets.iter().enumerate() {
             let (name, url) = match target_spec.split_once('=') {
-                Some((n, u)) => (n, Some(u)),
+                Some((n, u)) => (n, Some(normalize_remote_url(u, &current_dir))),
                 None => (target_spec.as_str(), None),
             };
+            let url = url.as_deref();
+            config.set(&format!("remote.{}.url", name), url.unwrap_or_default())?;
+
+            // первый контекст нужен сразу (на него смотрит .git), остальные создаст первый push или переключение
+            if i > 0 && !eager {
+                text::status(text::remote_declared(name));
+                continue;
+            }
 
-            println!("Настройка контекста '{}'...", name.cyan());
+            text::status(text::context_setup(name));
 
             git.init_context(name, url)
                 .map_err(|e| anyhow::anyhow!("Failed to init context {}: {}", name, e))?;
@@ -107,124 +439,430 @@ fn main() -> Result<()> {
                     .map_err(|e| anyhow::anyhow!("Failed to switch to {}: {}", name, e))?;
             }
         }
+        config.save()?;
 
-        let exclude_path = current_dir.join(".git").join("info").join("exclude");
-        if let Some(parent) = exclude_path.parent() {
-            fs::create_dir_all(parent)?;
+        print_success(text::init_done());
+        return Ok(());
+    }
+
+    if let Commands::Clone { url, dir, remote, timeout } = &cli.command {
+        // git clone идёт уже из новой папки, относительный путь к зеркалу надо развернуть здесь
+        let url = &normalize_remote_url(url, &current_dir);
+        let dir_name = match dir {
+            Some(d) => d.clone(),
+            None => repo_name_from_url(url)
+                .ok_or_else(|| anyhow::anyhow!("Cannot guess directory name from '{}', pass it explicitly", url))?,
+        };
+        let root = current_dir.join(&dir_name);
+        if root.exists() && fs::read_dir(&root)?.next().is_some() {
+            anyhow::bail!("Destination '{}' already exists and is not empty", dir_name);
         }
-        let mut file = fs::OpenOptions::new().write(true).append(true).create(true).open(&exclude_path)?;
-        writeln!(file, ".gitpp")?;
-        writeln!(file, ".git_*")?;
 
-        println!("{} Готово!", "SUCCESS:".green().bold());
+        validate_remote_url(url).map_err(|e| anyhow::anyhow!(e))?;
+        let upstream = RemoteRef::new(remote.clone(), url.clone()).map_err(|e| anyhow::anyhow!(e))?;
+
+        let timeout = timeout.as_deref().map(config::parse_timeout).transpose()?.flatten();
+
+        text::status(text::clone_started(url, &dir_name));
+        let existed = root.exists();
+        let imported = match clone_repo(&root, remote, upstream, timeout) {
+            Ok(imported) => imported,
+            Err(e) => {
+                // полусклонированный контекст clone_context убирает сам, а .gitpp и файлы - здесь,
+                // иначе повтор упрётся в "already exists and is not empty"
+                let _ = if existed { clear_dir(&root) } else { fs::remove_dir_all(&root) };
+                return Err(e);
+            }
+        };
+
+        print_success(text::cloned(&dir_name, imported));
         return Ok(());
     }
 
     if !gpp_dir.exists() {
-        anyhow::bail!("{} Запустите gpp init", "Репозиторий не найден.".red().bold());
+        anyhow::bail!(
+            "{} not a gpp repository (or any of the parent directories): .gitpp\nRun `gpp init` to create one.",
+            "fatal:".red().bold()
+        );
     }
 
     if let Commands::Gui = cli.command {
-        println!("Запуск графического интерфейса...");
-        gui::run_gui().map_err(|e| anyhow::anyhow!("GUI Error: {}", e))?;
+        text::status(text::gui_starting());
+        gui::run_gui(repo_root.clone()).map_err(|e| anyhow::anyhow!("GUI Error: {}", e))?;
         return Ok(());
     }
 
-    let storage = Box::new(JsonStorage::new(&db_path).map_err(|e| anyhow::anyhow!(e))?);
-    let backend_main = Box::new(GitRepo::new(&current_dir));
-    let backend_aux = Box::new(GitRepo::new(&current_dir));
-
-    let graph = VersionGraph::new(storage, backend_main);
-    let mut dispatcher = CommandDispatcher::new(graph, backend_aux);
+    if let Commands::Repair = cli.command {
+        match JsonStorage::repair(&db_path).map_err(|e| anyhow::anyhow!(e))? {
+            RepairOutcome::Healthy => text::status(text::graph_healthy()),
+            RepairOutcome::Restored { backup, corrupt_copy } => {
+                print_success(text::graph_restored(&backup));
+                text::status(text::corrupt_copy_kept(&corrupt_copy));
+            }
+        }
+        return Ok(());
+    }
 
-    let get_head = || -> Result<Option<NodeId>> {
-        if head_path.exists() {
-            let h = fs::read_to_string(&head_path)?;
-            let pid = h.trim().to_string();
-            if pid.is_empty() { Ok(None) } else { Ok(Some(NodeId(pid))) }
+    if let Commands::Snapshot { name, restore, list } = &cli.command {
+        if *list {
+            let names = snapshot::list(&gpp_dir)?;
+            if names.is_empty() {
+                text::status(text::no_snapshots());
+            }
+            for n in names {
+                println!("{}", n);
+            }
+        } else if let Some(target) = restore {
+            let backup = snapshot::restore(&gpp_dir, target)?;
+            print_success(text::snapshot_restored(target, &backup));
         } else {
-            Ok(None)
+            let created = snapshot::create(&gpp_dir, name.as_deref())?;
+            print_success(text::snapshot_created(&created));
         }
+        return Ok(());
+    }
+
+    if let Commands::Config { key, value, list } = &cli.command {
+        let mut config = config::Config::load(&gpp_dir)?;
+        match (key, value) {
+            (Some(key), Some(value)) if !*list => {
+                let value = if key.ends_with(".url") { normalize_remote_url(value, &current_dir) } else { value.clone() };
+                config.set(key, &value)?;
+                config.save()?;
+            }
+            (Some(key), None) if !*list => match config.get(key) {
+                Some(v) => println!("{}", v),
+                None => std::process::exit(1),
+            },
+            _ => {
+                for (k, v) in config.entries() {
+                    println!("{} = {}", k, v);
+                }
+            }
+        }
+        return Ok(());
+    }
+    let mut repo = Repository::open(&repo_root)?;
+
+    // HEAD на несуществующую ноду: предупреждаем сразу, а не невнятной ошибкой посреди команды.
+    // checkout сам и есть способ это починить, ему не мешаем.
+    let dangling_head = repo.dangling_head()?;
+    if let Some(head) = dangling_head.as_ref().filter(|_| !cli.quiet && !matches!(cli.command, Commands::Checkout { .. })) {
+        eprintln!("{} {}", text::warning_label().yellow().bold(), text::dangling_head(head));
+    }
+
+    if let Commands::Stash { action } = &cli.command {
+        // один слот: id дерева со спрятанными изменениями
+        let stash_path = gpp_dir.join("stash");
+        match action {
+            None => {
+                if stash_path.exists() {
+                    anyhow::bail!("Stash is already occupied, run `gpp stash pop` first");
+                }
+                let head = repo.head()?.ok_or_else(|| anyhow::anyhow!("No HEAD to reset to, nothing to stash against"))?;
+                let tree_id = repo.graph().stash_worktree(&head).map_err(|e| anyhow::anyhow!("{}", e))?;
+                fs::write(&stash_path, &tree_id)?;
+                print_success(text::stash_saved(&head));
+            }
+            Some(StashAction::Pop) => {
+                if !stash_path.exists() {
+                    anyhow::bail!("No stash to pop");
+                }
+                let tree_id = fs::read_to_string(&stash_path)?.trim().to_string();
+                repo.graph().unstash_worktree(&tree_id, repo.head()?.as_ref()).map_err(|e| anyhow::anyhow!("{}", e))?;
+                fs::remove_file(&stash_path)?;
+                print_success(text::stash_restored());
+            }
+        }
+        return Ok(());
+    }
+
+    if let Commands::Checkout { orphan: true, .. } = &cli.command {
+        repo.clear_head()?;
+        text::status(text::head_cleared());
+        return Ok(());
+    }
+
+    // спиннер для долгих push-ей; без терминала indicatif сам ничего не рисует
+    let spinner = if cli.quiet { ProgressBar::hidden() } else { ProgressBar::new_spinner() };
+    {
+        let spinner = spinner.clone();
+        repo.dispatcher_mut().on_progress(move |event| match event {
+            Progress::Planned { nodes } => {
+                spinner.enable_steady_tick(std::time::Duration::from_millis(100));
+                spinner.set_message(format!("Pushing {} node(s)...", nodes));
+            }
+            Progress::Transfer(line) => spinner.set_message(line),
+            // в CI терминала нет и спиннер молчит, а повторы должны быть видны в логе
+            Progress::Retry { .. } if text::quiet() => {}
+            Progress::Retry { attempt, retries, delay, error } => spinner.suspend(|| {
+                eprintln!("{} {}", text::warning_label().yellow().bold(), error);
+                eprintln!("{}", text::push_retrying(attempt, retries, delay.as_secs()));
+            }),
+        });
+    }
+
+    // все аргументы-ноды принимают ревизии: префикс id, тег, HEAD~2, "-"
+    let rev_ctx = RevContext { head: repo.head()?, previous_head: repo.previous_head()? };
+    let resolve = |rev: &str| -> Result<NodeId> {
+        repo.graph().resolve_rev(rev, &rev_ctx).map_err(|e| anyhow::anyhow!("{}", e))
     };
 
+    if let Commands::Checkout { node: Some(rev), remote: Some(remote), .. } = &cli.command {
+        let node = repo.graph().get_node(&resolve(rev)?).map_err(|e| anyhow::anyhow!("{}", e))?;
+        let remote = node
+            .remotes
+            .iter()
+            .find(|r| remote_name_key(&r.name) == remote_name_key(remote))
+            .map(|r| r.name.clone())
+            .ok_or_else(|| anyhow::anyhow!("Node {} is not permitted on remote '{}'", node.id.short(), remote))?;
+        repo.set_remote_head(&remote, &node.id)?;
+        text::status(text::remote_head_set(&remote, &node.id));
+        return Ok(());
+    }
+
     // --- MAPPING CLI -> COMMAND DTO ---
     let cmd_dto = match &cli.command {
         Commands::Init { .. } => unreachable!(),
+        Commands::Clone { .. } => unreachable!(),
+        Commands::Completions { .. } => unreachable!(),
+        Commands::Config { .. } => unreachable!(),
         Commands::Gui => unreachable!(),
+        Commands::Repair => unreachable!(),
+        Commands::Snapshot { .. } => unreachable!(),
+        Commands::Stash { .. } => unreachable!(),
 
-        Commands::Add { message, parents, remotes } => {
-            let msg = match message {
-                Some(m) => m.clone(),
-                None => {
-                    Input::new()
-                        .with_prompt("Введите сообщение коммита")
-                        .interact_text()?
+        Commands::Add { message, parents, root, remotes, co_authors, paths, interactive, date, allow_future, no_verify } => {
+            let date = match date {
+                Some(input) => {
+                    let now = chrono::Utc::now();
+                    let date = parse_date(input, now).map_err(|e| anyhow::anyhow!(e))?;
+                    if date > now && !allow_future {
+                        anyhow::bail!("--date {} is in the future, pass --allow-future if that is intended", input);
+                    }
+                    Some(date)
                 }
+                None => None,
             };
 
-            let resolved_parents = if let Some(p_list) = parents {
-                p_list.iter().map(|s| NodeId(s.clone())).collect()
+            let resolved_parents = if *root {
+                Vec::new()
+            } else if let Some(p_list) = parents {
+                p_list.iter().map(|s| resolve(s)).collect::<Result<Vec<_>>>()?
             } else {
-                get_head()?.map(|h| vec![h]).unwrap_or_default()
+                repo.head()?.map(|h| vec![h]).unwrap_or_default()
+            };
+
+            let paths = if *interactive {
+                require_tty("gpp add -i")?;
+                let changed = repo.graph()
+                    .changed_paths(resolved_parents.first())
+                    .map_err(|e| anyhow::anyhow!("{}", e))?;
+                if changed.is_empty() {
+                    anyhow::bail!("Nothing changed since the parent node");
+                }
+                let picked = MultiSelect::new()
+                    .with_prompt(text::files_prompt())
+                    .items(&changed)
+                    .interact()?;
+                if picked.is_empty() {
+                    anyhow::bail!("No files selected, nothing to add");
+                }
+                picked.into_iter().map(|i| changed[i].clone()).collect()
+            } else {
+                paths.clone()
+            };
+
+            let msg = if message.is_empty() {
+                edit_message(&gpp_dir)?
+            } else {
+                // как git commit -m a -m b: каждый -m - отдельный абзац
+                message.join("\n\n")
             };
 
             Command::Add {
                 message: msg,
                 author: Author { name: "User".into(), email: "user@example.com".into() },
+                co_authors: co_authors.clone(),
                 parents: resolved_parents,
                 target_remotes: remotes.clone(),
+                paths,
+                date,
+                skip_hooks: *no_verify,
             }
         },
 
-        Commands::Log => Command::Log,
+        Commands::Log { author, grep, regex, since, until, remote, graph, stat, all, .. } => {
+            let pattern = |p: &Option<String>| -> Result<Option<TextMatch>> {
+                p.as_deref()
+                    .map(|p| TextMatch::new(p, *regex).with_context(|| format!("Invalid pattern '{}'", p)))
+                    .transpose()
+            };
+            let now = chrono::Utc::now();
+            let date = |d: &Option<String>| -> Result<Option<chrono::DateTime<chrono::Utc>>> {
+                d.as_deref().map(|d| parse_date(d, now).map_err(|e| anyhow::anyhow!(e))).transpose()
+            };
+            Command::Log {
+                filter: LogFilter {
+                    author: pattern(author)?,
+                    grep: pattern(grep)?,
+                    since: date(since)?,
+                    until: date(until)?,
+                    remote: remote.clone(),
+                },
+                graph: *graph,
+                stat: *stat,
+                // без HEAD (сразу после init, --orphan, висячий HEAD) показывать от него нечего - весь лес
+                from: rev_ctx.head.clone().filter(|_| !*all && dangling_head.is_none()),
+            }
+        }
 
-        Commands::Chrm { remote, url, node, remove } => {
-            let target = if let Some(id) = node { Some(NodeId(id.clone())) } else { get_head()? };
+        Commands::Chrm { remote, url, node, remove, recursive, dry_run, force } => {
+            let target = if let Some(rev) = node { Some(resolve(rev)?) } else { repo.head()? };
             Command::ChangeRemote {
                 remote: remote.clone(),
-                url: url.clone(),
+                url: url.as_deref().map(|u| normalize_remote_url(u, &current_dir)),
                 node: target,
-                remove: *remove
+                remove: *remove,
+                recursive: *recursive,
+                dry_run: *dry_run,
+                force: *force,
             }
         },
 
-        Commands::Push { remote, url, node, dry_run } => {
-            let target = if let Some(id) = node { Some(NodeId(id.clone())) } else { get_head()? };
-            let u = url.clone().unwrap_or_else(|| format!("git@github.com:{}.git", remote));
+        Commands::Push { remote, url, node, tag, dry_run, no_verify } => {
+            // с --tag нода берётся из тега, HEAD не нужен
+            let target = match (node, tag) {
+                (Some(rev), _) => Some(resolve(rev)?),
+                (None, Some(_)) => None,
+                // своя вершина ремоута, если её задали через checkout --remote, иначе общий HEAD
+                (None, None) => match repo.remote_head(remote)? {
+                    Some(tip) => Some(tip),
+                    None => repo.head()?,
+                },
+            };
+
             Command::Push {
                 remote_name: remote.clone(),
-                remote_url: u,
+                remote_url: url.as_deref().map(|u| normalize_remote_url(u, &current_dir)),
                 node: target,
-                dry_run: *dry_run
+                tag: tag.clone(),
+                dry_run: *dry_run,
+                skip_hooks: *no_verify,
             }
         },
 
-        Commands::Checkout { node } => {
-            Command::Checkout { node: NodeId(node.clone()) }
+        Commands::Tag { list: true, sort, .. } => Command::ListTags { by_date: sort == "date" },
+
+        Commands::Tag { name, node, message, .. } => {
+            let target = match node {
+                Some(rev) => resolve(rev)?,
+                None => repo.head()?.ok_or_else(|| anyhow::anyhow!("No HEAD to tag, pass --node"))?,
+            };
+            let name = name.clone().ok_or_else(|| anyhow::anyhow!("Tag name is required"))?;
+            Command::Tag { name, node: target, message: message.clone() }
+        },
+
+        Commands::Checkout { node, .. } => {
+            let node = node.as_deref().ok_or_else(|| anyhow::anyhow!("Specify a node to check out"))?;
+            // с висячим HEAD сверять рабочую директорию не с чем, как после --orphan
+            let head = rev_ctx.head.clone().filter(|_| dangling_head.is_none());
+            Command::Checkout { node: resolve(node)?, head }
+        }
+
+        Commands::MergeBase { a, b, all } => {
+            Command::MergeBase { a: resolve(a)?, b: resolve(b)?, all: *all }
+        }
+
+        Commands::Export { remote } => Command::Export { remote: remote.clone() },
+
+        Commands::Find { tag, meta } => Command::Find {
+            tag: tag.clone(),
+            meta: meta
+                .iter()
+                .map(|m| match m.split_once('=') {
+                    Some((k, v)) => (k.to_string(), Some(v.to_string())),
+                    None => (m.clone(), None),
+                })
+                .collect(),
+        },
+
+        Commands::Verify { signatures: false, .. } => Command::Verify,
+        Commands::Verify { remote, .. } => Command::VerifySignatures { remote: remote.clone() },
+
+        Commands::Gc { .. } => Command::FindDuplicateTrees,
+
+        Commands::Status { remote } => {
+            // та же вершина, что ушла бы при push без --node
+            let node = match repo.remote_head(remote)? {
+                Some(tip) => tip,
+                None => repo.head()?.ok_or_else(|| anyhow::anyhow!("No HEAD to compare with remote '{}'", remote))?,
+            };
+            Command::AheadBehind { remote: remote.clone(), node }
+        }
+
+        Commands::Stats { json } => Command::Stats { json: *json },
+
+        Commands::Context { name, list } => {
+            match name {
+                Some(n) if !*list => Command::SwitchContext { name: n.clone() },
+                _ => Command::ListContexts,
+            }
         }
     };
 
-    match dispatcher.dispatch(cmd_dto) {
+    let outcome = repo.dispatch(cmd_dto);
+    spinner.finish_and_clear();
+
+    match outcome {
         Ok(result) => {
             match result {
+                // без префикса и цвета: эту строку разбирают скрипты
+                CmdResult::NodeCreated(id) if cli.quiet => println!("{}", id.0),
+                CmdResult::NodeCreated(id) => println!("{}", text::node_created(&id)),
+                CmdResult::Pushed { remote, outcome } => {
+                    print_success(text::push_completed());
+                    // последними и без цвета: по ним деплой берёт запушенный хеш
+                    for target in &outcome.refs {
+                        println!("{}", text::pushed_ref(&remote, target, &outcome.tip));
+                    }
+                },
                 CmdResult::Success(msg) => {
-                    println!("{} {}", "SUCCESS:".green().bold(), msg);
-
-                    if let Commands::Add { .. } = &cli.command {
-                        if let Some(id) = msg.strip_prefix("Node created: ") {
-                            fs::write(&head_path, id.trim())?;
+                    print_success(msg);
+                },
+                CmdResult::Output(text) => {
+                    println!("{}", text);
+                    // репозиторий могли создать на другой ОС (WSL, сетевой диск), показываем, что за ссылка .git
+                    if let Commands::Context { name, list } = &cli.command {
+                        if *list || name.is_none() {
+                            let kind = GitRepo::new(&repo_root).git_link_kind().map_err(|e| anyhow::anyhow!("{}", e))?;
+                            if let Some(kind) = kind {
+                                text::status(text::context_link_kind(kind.name(), kind.is_foreign()));
+                            }
                         }
                     }
-                    if let Commands::Checkout { node } = &cli.command {
-                        fs::write(&head_path, node)?;
+                },
+                CmdResult::Log(entries) => {
+                    let format = match &cli.command {
+                        Commands::Log { json: true, .. } => log_view::LogFormat::Json,
+                        Commands::Log { oneline: true, .. } => log_view::LogFormat::Oneline,
+                        Commands::Log { full: true, .. } => log_view::LogFormat::Full,
+                        _ => log_view::LogFormat::Medium,
+                    };
+                    let dates = match &cli.command {
+                        Commands::Log { date, .. } => log_view::DateMode::parse(date),
+                        _ => log_view::DateMode::Author,
+                    };
+                    let text = log_view::render(&entries, format, dates)?;
+                    if !text.is_empty() {
+                        println!("{}", text);
                     }
                 },
-                CmdResult::Output(text) => println!("{}", text),
                 CmdResult::None => {},
             }
         },
         Err(e) => {
-            eprintln!("{} {}", "ERROR:".red().bold(), e);
+            eprintln!("{} {}", text::error_label().red().bold(), e);
             std::process::exit(1);
         },
     }
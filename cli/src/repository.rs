@@ -0,0 +1,220 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use backend_git::git_repo::GitRepo;
+use gpp_core::backend::GraphOps;
+use gpp_core::dispatcher::{build_dispatcher, CmdResult, Command, CommandDispatcher};
+use gpp_core::storage::StorageError;
+use gpp_core::types::{remote_name_key, Node, NodeId};
+use gpp_core::version_graph::VersionGraph;
+use storage_file::json_storage::JsonStorage;
+
+use crate::config::Config;
+
+// Открытый репозиторий: graph.json, git-бэкенд, dispatcher и HEAD в одном месте.
+// Через него работают и CLI, и GUI, чтобы не собирать всё это руками в каждом месте.
+
+pub struct Repository {
+    root: PathBuf,
+    dispatcher: CommandDispatcher,
+}
+
+impl Repository {
+    /// root - папка, в которой лежит .gitpp (выше она не ищется)
+    pub fn open(root: impl AsRef<Path>) -> Result<Self> {
+        let root = root.as_ref().to_path_buf();
+        let gpp_dir = root.join(".gitpp");
+        if !gpp_dir.is_dir() {
+            anyhow::bail!("{} is not a gpp repository: no .gitpp. Run `gpp init` first.", root.display());
+        }
+        let config = Config::load(&gpp_dir)?;
+
+        let storage = Box::new(JsonStorage::new(gpp_dir.join("graph.json")).map_err(|e| match e {
+            StorageError::Corrupt { .. } => anyhow::anyhow!("{}\nRun `gpp repair` to restore the last good version.", e),
+            other => anyhow::anyhow!(other),
+        })?);
+        let mut dispatcher = build_dispatcher(GitRepo::new(&root).with_network_timeout(config.network_timeout()?), storage);
+        dispatcher.set_inherit_policy(config.inherit_policy()?);
+        dispatcher.set_tree_source(config.tree_source()?);
+        dispatcher.set_id_strategy(config.id_strategy()?);
+        dispatcher.set_push_url_template(config.push_url_template());
+        dispatcher.set_push_retry(config.push_retry()?);
+        for remote in config.declared_remotes()? {
+            dispatcher.declare_remote(remote);
+        }
+        for (remote, rules) in config.push_deny()? {
+            dispatcher.deny_on_push(&remote, rules);
+        }
+
+        Ok(Self { root, dispatcher })
+    }
+
+    pub fn gpp_dir(&self) -> PathBuf {
+        self.root.join(".gitpp")
+    }
+
+    pub fn graph(&self) -> &VersionGraph {
+        self.dispatcher.graph()
+    }
+
+    /// Для настройки (прогресс, плагины). Команды лучше слать через dispatch: он ещё и двигает HEAD.
+    pub fn dispatcher_mut(&mut self) -> &mut CommandDispatcher {
+        &mut self.dispatcher
+    }
+
+    /// Исполняет команду; после add HEAD встаёт на новую ноду, после checkout - на выбранную
+    pub fn dispatch(&mut self, cmd: Command) -> Result<CmdResult> {
+        let checkout_target = match &cmd {
+            Command::Checkout { node, .. } => Some(node.clone()),
+            _ => None,
+        };
+
+        let result = self.dispatcher.dispatch(cmd).map_err(|e| anyhow::anyhow!("{}", e))?;
+
+        if let Some(node) = checkout_target {
+            if let Some(old) = self.head()?.filter(|old| *old != node) {
+                write_node_file(&self.gpp_dir().join("HEAD.prev"), &old)?;
+            }
+            self.set_head(&node)?;
+        } else if let CmdResult::NodeCreated(id) = &result {
+            self.set_head(id)?;
+        }
+        Ok(result)
+    }
+
+    pub fn head(&self) -> Result<Option<NodeId>> {
+        read_node_file(&self.gpp_dir().join("HEAD"))
+    }
+
+    /// HEAD, который указывает на ноду, которой нет в графе: graph.json восстановили из бэкапа
+    /// или HEAD правили руками. Иначе следующая команда падает на загрузке ноды с невнятной ошибкой.
+    pub fn dangling_head(&self) -> Result<Option<NodeId>> {
+        let Some(head) = self.head()? else { return Ok(None) };
+        match self.graph().get_node(&head) {
+            Ok(_) => Ok(None),
+            Err(e) if matches!(e.downcast_ref::<StorageError>(), Some(StorageError::NodeNotFound(_))) => Ok(Some(head)),
+            Err(e) => Err(anyhow::anyhow!("{}", e)),
+        }
+    }
+
+    /// Куда указывал HEAD до последнего checkout, для `gpp checkout -`
+    pub fn previous_head(&self) -> Result<Option<NodeId>> {
+        read_node_file(&self.gpp_dir().join("HEAD.prev"))
+    }
+
+    /// Только переставляет HEAD, рабочую директорию не трогает
+    pub fn set_head(&self, node: &NodeId) -> Result<()> {
+        write_node_file(&self.gpp_dir().join("HEAD"), node)
+    }
+
+    /// Своя вершина контекста ремоута (.gitpp/HEAD_<remote>). None - не задана, действует общий HEAD.
+    pub fn remote_head(&self, remote: &str) -> Result<Option<NodeId>> {
+        read_node_file(&self.remote_head_path(remote))
+    }
+
+    pub fn set_remote_head(&self, remote: &str, node: &NodeId) -> Result<()> {
+        write_node_file(&self.remote_head_path(remote), node)
+    }
+
+    // имена ремоутов без учёта регистра, поэтому и файл один на Origin/origin
+    fn remote_head_path(&self, remote: &str) -> PathBuf {
+        self.gpp_dir().join(format!("HEAD_{}", remote_name_key(remote)))
+    }
+
+    /// Снимает HEAD, чтобы следующий add создал новый корень. Рабочая директория не меняется,
+    /// прежний HEAD запоминается для `gpp checkout -`.
+    pub fn clear_head(&self) -> Result<()> {
+        if let Some(old) = self.head()? {
+            write_node_file(&self.gpp_dir().join("HEAD.prev"), &old)?;
+        }
+        fs::write(self.gpp_dir().join("HEAD"), "")?;
+        Ok(())
+    }
+
+    /// Весь граф по id, как он лежит в graph.json
+    pub fn nodes(&self) -> Result<HashMap<NodeId, Node>> {
+        self.graph().load_all_nodes().map_err(|e| anyhow::anyhow!("{}", e))
+    }
+}
+
+/// Файл с одним id ноды (HEAD, HEAD.prev). Нет файла или он пустой - None.
+pub fn read_node_file(path: &Path) -> Result<Option<NodeId>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let id = fs::read_to_string(path)?.trim().to_string();
+    Ok(if id.is_empty() { None } else { Some(NodeId(id)) })
+}
+
+fn write_node_file(path: &Path, node: &NodeId) -> Result<()> {
+    fs::write(path, &node.0)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn head_round_trips_through_the_facade() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let db_path = dir.path().join(".gitpp").join("graph.json");
+        fs::create_dir_all(db_path.parent().unwrap()).unwrap();
+        fs::write(&db_path, "{}").unwrap();
+        JsonStorage::write_format(&db_path).unwrap();
+
+        let repo = Repository::open(dir.path()).unwrap();
+        assert_eq!(repo.head().unwrap(), None);
+        assert!(repo.nodes().unwrap().is_empty());
+
+        repo.set_head(&NodeId("abc".into())).unwrap();
+        assert_eq!(repo.head().unwrap(), Some(NodeId("abc".into())));
+        assert_eq!(repo.previous_head().unwrap(), None);
+    }
+
+    #[test]
+    fn cleared_head_is_remembered_as_previous() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let db_path = dir.path().join(".gitpp").join("graph.json");
+        fs::create_dir_all(db_path.parent().unwrap()).unwrap();
+        fs::write(&db_path, "{}").unwrap();
+        JsonStorage::write_format(&db_path).unwrap();
+
+        let repo = Repository::open(dir.path()).unwrap();
+        repo.set_head(&NodeId("abc".into())).unwrap();
+        repo.clear_head().unwrap();
+        assert_eq!(repo.head().unwrap(), None);
+        assert_eq!(repo.previous_head().unwrap(), Some(NodeId("abc".into())));
+
+        // повторная очистка не затирает запомненный HEAD
+        repo.clear_head().unwrap();
+        assert_eq!(repo.previous_head().unwrap(), Some(NodeId("abc".into())));
+    }
+
+    #[test]
+    fn remote_heads_are_separate_from_the_global_one() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let db_path = dir.path().join(".gitpp").join("graph.json");
+        fs::create_dir_all(db_path.parent().unwrap()).unwrap();
+        fs::write(&db_path, "{}").unwrap();
+        JsonStorage::write_format(&db_path).unwrap();
+
+        let repo = Repository::open(dir.path()).unwrap();
+        repo.set_head(&NodeId("main".into())).unwrap();
+        assert_eq!(repo.remote_head("mirror").unwrap(), None);
+
+        repo.set_remote_head("Mirror", &NodeId("tip".into())).unwrap();
+        assert_eq!(repo.remote_head("mirror").unwrap(), Some(NodeId("tip".into())));
+        assert_eq!(repo.head().unwrap(), Some(NodeId("main".into())));
+        assert!(dir.path().join(".gitpp/HEAD_mirror").exists());
+    }
+
+    #[test]
+    fn open_refuses_a_directory_without_gitpp() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let err = Repository::open(dir.path()).err().unwrap();
+        assert!(err.to_string().contains("not a gpp repository"), "{}", err);
+    }
+}
@@ -0,0 +1,87 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Снапшоты метаданных .gitpp (graph.json, HEAD) - страховка перед опасными операциями,
+// не зависящая от reflog самого git.
+
+const SNAPSHOTS_DIR: &str = "snapshots";
+
+fn snapshots_root(gpp_dir: &Path) -> PathBuf {
+    gpp_dir.join(SNAPSHOTS_DIR)
+}
+
+/// Файлы состояния, которые попадают в снапшот: сам граф, его версия формата и все HEAD-ы
+fn state_files(gpp_dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(gpp_dir)? {
+        let path = entry?.path();
+        let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+        if path.is_file() && (name == "graph.json" || name == "format" || name.starts_with("HEAD")) {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}
+
+fn validate_name(name: &str) -> Result<()> {
+    if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']) {
+        anyhow::bail!("Invalid snapshot name '{}'", name);
+    }
+    Ok(())
+}
+
+pub fn create(gpp_dir: &Path, name: Option<&str>) -> Result<String> {
+    let name = match name {
+        Some(n) => n.to_string(),
+        None => chrono::Local::now().format("%Y%m%d-%H%M%S").to_string(),
+    };
+    validate_name(&name)?;
+
+    let target = snapshots_root(gpp_dir).join(&name);
+    if target.exists() {
+        anyhow::bail!("Snapshot '{}' already exists", name);
+    }
+    fs::create_dir_all(&target).context("Failed to create the snapshot directory")?;
+
+    for file in state_files(gpp_dir)? {
+        fs::copy(&file, target.join(file.file_name().unwrap_or_default()))?;
+    }
+    Ok(name)
+}
+
+pub fn list(gpp_dir: &Path) -> Result<Vec<String>> {
+    let root = snapshots_root(gpp_dir);
+    if !root.exists() {
+        return Ok(Vec::new());
+    }
+    let mut names: Vec<String> = fs::read_dir(root)?
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().is_dir())
+        .map(|e| e.file_name().to_string_lossy().to_string())
+        .collect();
+    names.sort();
+    Ok(names)
+}
+
+/// Откатывает .gitpp к снапшоту. Текущее состояние перед этим само сохраняется как pre-restore-*,
+/// так что restore тоже можно откатить.
+pub fn restore(gpp_dir: &Path, name: &str) -> Result<String> {
+    validate_name(name)?;
+    let source = snapshots_root(gpp_dir).join(name);
+    if !source.is_dir() {
+        anyhow::bail!("Snapshot '{}' not found", name);
+    }
+
+    let backup_name = create(gpp_dir, Some(&format!("pre-restore-{}", chrono::Local::now().format("%Y%m%d-%H%M%S%.3f"))))?;
+
+    // HEAD-ы, которых не было в снапшоте, должны исчезнуть
+    for file in state_files(gpp_dir)? {
+        fs::remove_file(file)?;
+    }
+    for entry in fs::read_dir(&source)? {
+        let path = entry?.path();
+        fs::copy(&path, gpp_dir.join(path.file_name().unwrap_or_default()))?;
+    }
+    Ok(backup_name)
+}
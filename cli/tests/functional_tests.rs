@@ -5,6 +5,8 @@ use std::path::Path;
 use tempfile::TempDir;
 use std::process::Command as SysCommand;
 
+use gpp_core::text;
+
 struct TestEnv {
     /// Временная директория, удалится сама при выходе из скоупа
     root: TempDir,
@@ -24,10 +26,19 @@ impl TestEnv {
 
     /// Запускает gpp с аргументами внутри тестовой папки
     fn gpp(&self) -> Command {
+        self.gpp_in(".")
+    }
+
+    /// То же, но в подпапке тестовой директории (например, после clone)
+    fn gpp_in(&self, dir: &str) -> Command {
         let mut cmd = Command::cargo_bin("gpp")
             .or_else(|_| Command::cargo_bin("gpp_cli"))
             .expect("Binary gpp/gpp_cli not found");
-        cmd.current_dir(self.path());
+        cmd.current_dir(self.path().join(dir));
+        // без -m gpp add открывает редактор: тесты, которым он нужен, задают EDITOR сами
+        cmd.env_remove("VISUAL").env_remove("EDITOR");
+        // сообщения сверяются с английскими текстами из gpp_core::text
+        cmd.env("GPP_LANG", "en");
         cmd
     }
 
@@ -60,10 +71,12 @@ impl TestEnv {
     /// Хелпер: вытаскивает ID ноды из stdout команды add
     fn parse_node_id(&self, stdout: &[u8]) -> String {
         let s = String::from_utf8_lossy(stdout);
-        s.lines()
+        let line = s.lines()
             .find(|l| l.contains("Node created"))
-            .expect("Output does not contain 'Node created'")
-            .replace("Node created: ", "")
+            .expect("Output does not contain 'Node created'");
+        line.split("Node created: ")
+            .nth(1)
+            .unwrap_or("")
             .trim()
             .to_string()
     }
@@ -71,7 +84,7 @@ impl TestEnv {
 #[test]
 fn test_init_default() {
     let env = TestEnv::new();
-    env.gpp().arg("init").assert().success().stdout(predicate::str::contains("Готово!"));
+    env.gpp().arg("init").assert().success().stdout(predicate::str::contains(text::init_done()));
 
     env.assert_exists(".gitpp");
     env.assert_exists(".gitpp/graph.json");
@@ -83,7 +96,7 @@ fn test_init_default() {
 fn test_init_multiple_remotes() {
     let env = TestEnv::new();
     env.gpp()
-        .args(&["init", "personal", "work=git@example.com:corp/repo.git"])
+        .args(&["init", "--eager", "personal", "work=git@example.com:corp/repo.git"])
         .assert()
         .success();
 
@@ -96,6 +109,65 @@ fn test_init_multiple_remotes() {
     assert!(config_content.contains("git@example.com:corp/repo.git"));
 }
 
+#[test]
+fn test_init_declares_extra_remotes_without_contexts() {
+    let env = TestEnv::new();
+    let mirror_dir = TempDir::new().unwrap();
+    SysCommand::new("git").args(&["init", "--bare", "--quiet"]).current_dir(&mirror_dir).output().unwrap();
+    let mirror = mirror_dir.path().to_str().unwrap().to_string();
+
+    env.gpp().args(&["init", "origin", &format!("mirror={}", mirror)]).assert().success();
+    env.assert_exists(".git_origin");
+    env.assert_missing(".git_mirror");
+    env.gpp()
+        .args(&["config", "--list"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!("remote.mirror.url = {}", mirror)));
+    env.gpp()
+        .args(&["context", "--list"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("  mirror (not initialized)"));
+
+    // URL берётся из объявления и в chrm, и в push; контекст появляется только на push
+    env.write_file("a.txt", "A");
+    let out = env.gpp().args(&["add", "-m", "first"]).output().unwrap();
+    let node = env.parse_node_id(&out.stdout);
+    env.gpp().args(&["chrm", "--node", &node, "--remote", "mirror"]).assert().success();
+    env.gpp().args(&["push", "--node", &node, "--remote", "mirror"]).assert().success();
+    env.assert_exists(".git_mirror");
+    let pushed = SysCommand::new("git").args(&["rev-parse", "main"]).current_dir(&mirror_dir).output().unwrap();
+    assert_eq!(String::from_utf8_lossy(&pushed.stdout).trim(), node);
+}
+
+#[test]
+fn test_remote_names_are_case_insensitive() {
+    let env = TestEnv::new();
+    env.gpp()
+        .args(&["init", "origin", "Origin"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("case-insensitive"));
+    env.assert_missing(".gitpp");
+
+    env.gpp().args(&["init", "origin", "Work"]).assert().success();
+    env.write_file("f.txt", "1");
+    let out = env.gpp().args(&["add", "-m", "root", "-r", "origin", "Work"]).output().unwrap();
+    let id = env.parse_node_id(&out.stdout);
+
+    env.gpp()
+        .args(&["chrm", "--node", &id, "--remote", "Origin", "--url", "https://example.com/x.git"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("differs only by case"));
+    env.gpp()
+        .args(&["context", "WORK"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Switched to context 'Work'"));
+}
+
 #[test]
 fn test_basic_workflow_add_log() {
     let env = TestEnv::new();
@@ -117,6 +189,35 @@ fn test_basic_workflow_add_log() {
         .stdout(predicate::str::contains("User <user@example.com>"));
 }
 
+#[test]
+fn test_log_follows_head_unless_all() {
+    let env = TestEnv::new();
+    env.gpp().arg("init").assert().success();
+    env.write_file("a.txt", "1");
+    let out = env.gpp().args(&["add", "-m", "base"]).output().unwrap();
+    let base = env.parse_node_id(&out.stdout);
+    env.write_file("a.txt", "2");
+    env.gpp().args(&["add", "-m", "left"]).assert().success();
+    env.gpp().args(&["checkout", &base]).assert().success();
+    env.write_file("a.txt", "3");
+    env.gpp().args(&["add", "-m", "right"]).assert().success();
+
+    // соседняя линия (left) в истории HEAD не видна, как в git log
+    env.gpp()
+        .args(&["log", "--oneline"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("base"))
+        .stdout(predicate::str::contains("right"))
+        .stdout(predicate::str::contains("left").not());
+    env.gpp()
+        .args(&["log", "--oneline", "--all"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("left"))
+        .stdout(predicate::str::contains("right"));
+}
+
 #[test]
 fn test_multicontext_switching() {
     let env = TestEnv::new();
@@ -225,6 +326,199 @@ fn test_checkout_restores_files() {
     assert_eq!(head_content, id1);
 }
 
+#[test]
+fn test_add_only_listed_paths() {
+    let env = TestEnv::new();
+    env.gpp().arg("init").assert().success();
+
+    env.write_file("a.txt", "a1");
+    env.write_file("b.txt", "b1");
+    let out1 = env.gpp().args(&["add", "-m", "v1"]).output().unwrap();
+    env.parse_node_id(&out1.stdout);
+
+    env.write_file("a.txt", "a2");
+    env.write_file("b.txt", "b2");
+    env.write_file("dir/c.txt", "c");
+    let out2 = env.gpp().args(&["add", "-m", "only a", "--", "a.txt", "dir"]).output().unwrap();
+    let id2 = env.parse_node_id(&out2.stdout);
+
+    let show = |path: &str| {
+        let out = env.git().args(&["show", &format!("{}:{}", id2, path)]).output().unwrap();
+        assert!(out.status.success(), "{} should be in the tree", path);
+        String::from_utf8_lossy(&out.stdout).to_string()
+    };
+    assert_eq!(show("a.txt"), "a2");
+    assert_eq!(show("dir/c.txt"), "c");
+    // неперечисленный файл - как в родителе, а в рабочей директории правка осталась
+    assert_eq!(show("b.txt"), "b1");
+    assert_eq!(fs::read_to_string(env.path().join("b.txt")).unwrap(), "b2");
+
+    env.gpp().args(&["add", "-m", "bad", "--", "missing.txt"]).assert().failure();
+}
+
+#[test]
+fn test_worktree_tree_source_keeps_git_index() {
+    let env = TestEnv::new();
+    env.gpp().arg("init").assert().success();
+    env.gpp().args(&["config", "tree.source", "staged"]).assert().failure();
+    env.gpp().args(&["config", "tree.source", "worktree"]).assert().success();
+
+    // пользователь застейджил через обычный git только a.txt
+    env.write_file("a.txt", "a");
+    env.write_file("b.txt", "b");
+    assert!(env.git().args(&["add", "a.txt"]).status().unwrap().success());
+    let staged = || {
+        let out = env.git().args(&["ls-files", "--cached"]).output().unwrap();
+        String::from_utf8_lossy(&out.stdout).to_string()
+    };
+    assert_eq!(staged(), "a.txt\n");
+
+    let out = env.gpp().args(&["add", "-m", "all"]).output().unwrap();
+    let id = env.parse_node_id(&out.stdout);
+    let tree = env.git().args(&["ls-tree", "--name-only", &id]).output().unwrap();
+    assert_eq!(String::from_utf8_lossy(&tree.stdout), "a.txt\nb.txt\n");
+    assert_eq!(staged(), "a.txt\n");
+}
+
+#[test]
+fn test_quiet_prints_only_results() {
+    let env = TestEnv::new();
+    env.gpp().args(&["init", "-q"]).assert().success().stdout("");
+
+    env.write_file("a.txt", "a");
+    let out = env.gpp().args(&["-q", "add", "-m", "first"]).output().unwrap();
+    assert!(out.status.success());
+    let id = String::from_utf8(out.stdout).unwrap();
+    assert_eq!(id.trim().len(), 40, "{:?}", id);
+
+    env.gpp().args(&["chrm", "--remote", "work", "--url", "http://fake", "-q"]).assert().success().stdout("");
+    env.gpp().args(&["tag", "v1", "--quiet"]).assert().success().stdout("");
+    // результат команды остаётся
+    env.gpp().args(&["log", "--oneline", "-q"]).assert().success().stdout(predicate::str::contains("first"));
+    env.gpp().args(&["-q", "checkout", "missing"]).assert().failure().stderr(predicate::str::contains("missing"));
+}
+
+#[test]
+fn test_interactive_add_requires_terminal() {
+    let env = TestEnv::new();
+    env.gpp().arg("init").assert().success();
+    env.write_file("a.txt", "a");
+
+    // под тестом stdin не терминал: ошибка, а не зависание на выборе файлов
+    env.gpp()
+        .args(&["add", "-i", "-m", "picked"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("needs an interactive terminal"));
+    env.gpp().args(&["add", "-i", "--", "a.txt"]).assert().failure();
+    env.gpp()
+        .args(&["add"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("pass -m"));
+}
+
+#[test]
+fn test_checkout_by_revision() {
+    let env = TestEnv::new();
+    env.gpp().arg("init").assert().success();
+
+    let mut ids = Vec::new();
+    for v in ["Version 1", "Version 2", "Version 3"] {
+        env.write_file("data.txt", v);
+        let out = env.gpp().args(&["add", "-m", v]).output().unwrap();
+        ids.push(env.parse_node_id(&out.stdout));
+    }
+    let data = || fs::read_to_string(env.path().join("data.txt")).unwrap();
+
+    env.gpp().args(&["checkout", "HEAD~2"]).assert().success();
+    assert_eq!(data(), "Version 1");
+    assert_eq!(fs::read_to_string(env.path().join(".gitpp/HEAD")).unwrap(), ids[0]);
+
+    env.gpp().args(&["checkout", "-"]).assert().success();
+    assert_eq!(data(), "Version 3");
+
+    env.gpp().args(&["checkout", &ids[1][..8]]).assert().success();
+    assert_eq!(data(), "Version 2");
+
+    env.gpp().args(&["tag", "stable"]).assert().success();
+    env.gpp().args(&["checkout", "HEAD~1"]).assert().success();
+    env.gpp().args(&["checkout", "stable"]).assert().success();
+    assert_eq!(data(), "Version 2");
+
+    env.gpp()
+        .args(&["checkout", "HEAD~5"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("goes past the root"));
+}
+
+#[test]
+fn test_checkout_orphan_starts_a_new_root() {
+    let env = TestEnv::new();
+    env.gpp().arg("init").assert().success();
+    env.write_file("a.txt", "main history");
+    let out = env.gpp().args(&["add", "-m", "first"]).output().unwrap();
+    let first = env.parse_node_id(&out.stdout);
+
+    env.gpp().args(&["checkout", "--orphan"]).assert().success().stdout(predicate::str::contains("HEAD cleared"));
+    assert_eq!(fs::read_to_string(env.path().join(".gitpp/HEAD")).unwrap(), "");
+    // файлы на месте, как после git checkout --orphan
+    assert_eq!(fs::read_to_string(env.path().join("a.txt")).unwrap(), "main history");
+
+    env.write_file("b.txt", "docs history");
+    let out = env.gpp().args(&["add", "-m", "docs root"]).output().unwrap();
+    let root = env.parse_node_id(&out.stdout);
+    let parents = env.git().args(&["log", "-1", "--format=%P", &root]).output().unwrap();
+    assert_eq!(String::from_utf8_lossy(&parents.stdout).trim(), "");
+
+    env.gpp().args(&["checkout", "--orphan"]).assert().success();
+    env.gpp().args(&["checkout", "-"]).assert().success();
+    assert_eq!(fs::read_to_string(env.path().join(".gitpp/HEAD")).unwrap(), root);
+
+    env.gpp().args(&["checkout", &first, "--orphan"]).assert().failure();
+    env.gpp().arg("checkout").assert().failure();
+}
+
+#[test]
+fn test_dangling_head_warns_and_checkout_resets_it() {
+    let env = TestEnv::new();
+    env.gpp().arg("init").assert().success();
+    env.write_file("a.txt", "one");
+    let out = env.gpp().args(&["add", "-m", "first"]).output().unwrap();
+    let first = env.parse_node_id(&out.stdout);
+
+    // например, graph.json откатили из бэкапа, а HEAD остался на более новой ноде
+    fs::write(env.path().join(".gitpp/HEAD"), "0123456789abcdef0123456789abcdef01234567").unwrap();
+    env.gpp()
+        .arg("log")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("HEAD points to 0123456 "))
+        .stderr(predicate::str::contains("gpp checkout --orphan"));
+
+    env.gpp().args(&["checkout", &first]).assert().success().stderr(predicate::str::contains("HEAD points").not());
+    env.gpp().arg("log").assert().success().stderr(predicate::str::contains("HEAD points").not());
+}
+
+#[test]
+fn test_add_root_ignores_head() {
+    let env = TestEnv::new();
+    env.gpp().arg("init").assert().success();
+    env.write_file("a.txt", "one");
+    env.gpp().args(&["add", "-m", "first"]).assert().success();
+
+    env.write_file("a.txt", "two");
+    let out = env.gpp().args(&["add", "-m", "second root", "--root"]).output().unwrap();
+    let root = env.parse_node_id(&out.stdout);
+    let parents = env.git().args(&["log", "-1", "--format=%P", &root]).output().unwrap();
+    assert_eq!(String::from_utf8_lossy(&parents.stdout).trim(), "");
+    // HEAD переезжает на новый корень, дальше история растёт от него
+    assert_eq!(fs::read_to_string(env.path().join(".gitpp/HEAD")).unwrap(), root);
+
+    env.gpp().args(&["add", "-m", "x", "--root", "-p", &root]).assert().failure();
+}
+
 #[test]
 fn test_multicontext_switching_check_log() {
     let env = TestEnv::new();
@@ -244,6 +538,69 @@ fn test_multicontext_switching_check_log() {
     assert!(log_str.contains("First commit") || log_str.contains("c1"));
 }
 
+#[test]
+fn test_checkout_falls_back_to_context_with_the_object() {
+    let env = TestEnv::new();
+    env.gpp().arg("init").assert().success();
+
+    env.write_file("file1.txt", "v1");
+    let out = env.gpp().args(&["add", "-m", "c1"]).output().unwrap();
+    let node_id = env.parse_node_id(&out.stdout);
+    env.write_file("file1.txt", "v2");
+    env.gpp().args(&["add", "-m", "c2"]).assert().success();
+
+    // нода теперь принадлежит только work, а .git_work пустой: объекта там нет
+    env.gpp()
+        .args(&["chrm", "--remote", "work", "--url", "http://fake", "--node", &node_id])
+        .assert()
+        .success();
+    env.gpp().args(&["chrm", "--remote", "origin", "--remove", "--force", "--node", &node_id]).assert().success();
+    env.gpp().args(&["context", "work"]).assert().success();
+
+    env.gpp()
+        .args(&["checkout", &node_id])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Context 'work' does not contain node"))
+        .stdout(predicate::str::contains("switching to 'origin' instead"));
+    assert_eq!(fs::read_to_string(env.path().join("file1.txt")).unwrap(), "v1");
+}
+
+#[test]
+fn test_checkout_refuses_before_touching_git_link_or_files() {
+    let env = TestEnv::new();
+    env.gpp().arg("init").assert().success();
+    env.write_file("file1.txt", "v1");
+    let out = env.gpp().args(&["add", "-m", "c1"]).output().unwrap();
+    let first = env.parse_node_id(&out.stdout);
+    env.write_file("file1.txt", "v2");
+    env.gpp().args(&["add", "-m", "c2"]).assert().success();
+    let link = || fs::read_link(env.path().join(".git")).unwrap();
+    let before = link();
+
+    // правки поверх HEAD: checkout отказывается, файл остаётся как был
+    env.write_file("file1.txt", "local edit");
+    env.gpp()
+        .args(&["checkout", &first])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("uncommitted changes"));
+    assert_eq!(fs::read_to_string(env.path().join("file1.txt")).unwrap(), "local edit");
+    env.write_file("file1.txt", "v2");
+
+    // объект коммита пропал из единственного контекста
+    let object = env.path().join(".git_origin/objects").join(&first[..2]).join(&first[2..]);
+    fs::remove_file(object).unwrap();
+    env.gpp()
+        .args(&["checkout", &first])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("is not materialized"));
+    assert_eq!(link(), before);
+    assert_eq!(fs::read_to_string(env.path().join("file1.txt")).unwrap(), "v2");
+    assert_ne!(fs::read_to_string(env.path().join(".gitpp/HEAD")).unwrap(), first);
+}
+
 #[test]
 fn test_push_to_local_bare_repository_with_lazy_init() {
     let env = TestEnv::new();
@@ -285,7 +642,7 @@ fn test_push_to_local_bare_repository_with_lazy_init() {
         ])
         .assert()
         .success()
-        .stdout(predicate::str::contains("Успешно обновлена ссылка"));
+        .stdout(predicate::str::contains("Updated ref refs/heads/main"));
 
     env.assert_exists(".git_local_server");
 
@@ -351,6 +708,35 @@ fn test_chrm_add_and_remove_permissions() {
     assert!(!json_content_after.contains("git@github.com:user/repo.git"), "Remote URL should be removed");
 }
 
+#[test]
+fn test_malformed_remote_urls_are_rejected() {
+    let env = TestEnv::new();
+    env.gpp().arg("init").assert().success();
+    env.write_file("a.txt", "A");
+    let out = env.gpp().args(&["add", "-m", "first"]).output().unwrap();
+    let node = env.parse_node_id(&out.stdout);
+
+    env.gpp()
+        .args(&["chrm", "--node", &node, "--remote", "work", "--url", "htps://host/repo"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Unsupported URL scheme 'htps'"));
+    env.gpp()
+        .args(&["chrm", "--node", &node, "--remote", "work", "--url", "./no/such/repo"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("does not exist"));
+    let graph = fs::read_to_string(env.path().join(".gitpp/graph.json")).unwrap();
+    assert!(!graph.contains("\"work\""), "rejected remote leaked into graph.json");
+
+    // хост по имени ремоута больше не угадывается
+    env.gpp()
+        .args(&["push", "--node", &node, "--remote", "origin"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("pass --url"));
+}
+
 #[test]
 fn test_chrm_fails_without_node_id_argument() {
     let env = TestEnv::new();
@@ -362,6 +748,273 @@ fn test_chrm_fails_without_node_id_argument() {
         .failure();
 }
 
+#[test]
+fn test_push_defaults_to_remote_head() {
+    let env = TestEnv::new();
+    env.gpp().arg("init").assert().success();
+    env.write_file("f.txt", "stable");
+    let out = env.gpp().args(&["add", "-m", "stable"]).output().unwrap();
+    let stable = env.parse_node_id(&out.stdout);
+    env.write_file("f.txt", "wip");
+    let out = env.gpp().args(&["add", "-m", "wip"]).output().unwrap();
+    let wip = env.parse_node_id(&out.stdout);
+
+    let mirror = tempfile::TempDir::new().unwrap();
+    SysCommand::new("git").args(&["init", "--bare", "--quiet"]).current_dir(&mirror).output().unwrap();
+    let url = mirror.path().to_str().unwrap();
+
+    env.gpp()
+        .args(&["checkout", "--remote", "ORIGIN", &stable[..8]])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("HEAD for remote 'origin'"));
+    // общий HEAD и рабочая директория остались на wip
+    assert_eq!(fs::read_to_string(env.path().join(".gitpp/HEAD")).unwrap(), wip);
+    assert_eq!(fs::read_to_string(env.path().join("f.txt")).unwrap(), "wip");
+
+    env.gpp().args(&["push", "--remote", "origin", "--url", url]).assert().success();
+    let pushed = SysCommand::new("git").args(&["rev-parse", "main"]).current_dir(&mirror).output().unwrap();
+    assert_eq!(String::from_utf8_lossy(&pushed.stdout).trim(), stable);
+
+    env.gpp()
+        .args(&["checkout", "--remote", "secret", &stable])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("is not permitted on remote 'secret'"));
+}
+
+#[test]
+fn test_relative_mirror_path_is_stored_absolute() {
+    let env = TestEnv::new();
+    fs::create_dir_all(env.path().join("project/src")).unwrap();
+    SysCommand::new("git").args(&["init", "--bare", "--quiet", "mirror.git"]).current_dir(env.path()).output().unwrap();
+    let mirror = env.path().join("mirror.git").canonicalize().unwrap();
+
+    env.gpp_in("project").arg("init").assert().success();
+    env.write_file("project/src/a.txt", "A");
+    let out = env.gpp_in("project/src").args(&["add", "-m", "first"]).output().unwrap();
+    let node = env.parse_node_id(&out.stdout);
+
+    // путь относительно папки пользователя, а git потом запускается из корня репозитория
+    env.gpp_in("project/src")
+        .args(&["chrm", "--node", &node, "--remote", "origin", "--url", "../../mirror.git"])
+        .assert()
+        .success();
+    let graph = fs::read_to_string(env.path().join("project/.gitpp/graph.json")).unwrap();
+    assert!(graph.contains(&format!("\"url\": \"{}\"", mirror.display())), "{}", graph);
+
+    env.gpp_in("project/src").args(&["push", "--node", &node, "--url", "../../mirror.git"]).assert().success();
+    let pushed = SysCommand::new("git").args(&["rev-parse", "main"]).current_dir(&mirror).output().unwrap();
+    assert_eq!(String::from_utf8_lossy(&pushed.stdout).trim(), node);
+
+    // нужен новый узел, иначе push решит, что отправлять нечего
+    env.write_file("project/src/b.txt", "B");
+    let out = env.gpp_in("project").args(&["add", "-m", "second"]).output().unwrap();
+    let second = env.parse_node_id(&out.stdout);
+    env.gpp_in("project")
+        .args(&["chrm", "--node", &second, "--remote", "origin", "--url", "file:///nonexistent/mirror.git"])
+        .assert()
+        .success();
+    env.gpp_in("project")
+        .args(&["push", "--node", &second, "--url", "file:///nonexistent/mirror.git"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Local mirror /nonexistent/mirror.git for remote 'origin' does not exist"));
+}
+
+#[test]
+fn test_status_reports_ahead_of_remote() {
+    let env = TestEnv::new();
+    env.gpp().arg("init").assert().success();
+    let mirror = TempDir::new().unwrap();
+    SysCommand::new("git").args(&["init", "--bare", "--quiet"]).current_dir(&mirror).output().unwrap();
+    let url = mirror.path().to_str().unwrap();
+
+    env.write_file("a.txt", "1");
+    let out = env.gpp().args(&["add", "-m", "first"]).output().unwrap();
+    let first = env.parse_node_id(&out.stdout);
+    env.gpp().args(&["chrm", "--remote", "origin", "--url", url]).assert().success();
+
+    env.gpp()
+        .args(&["status", "--remote", "origin"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!("{} is ahead of 'origin' by 1 node(s).", &first[..7])));
+    env.gpp().args(&["push", "--url", url]).assert().success();
+    env.gpp()
+        .args(&["status", "--remote", "Origin"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("is up to date with 'origin'."));
+
+    env.write_file("a.txt", "2");
+    env.gpp().args(&["add", "-m", "second"]).assert().success();
+    env.write_file("a.txt", "3");
+    env.gpp().args(&["add", "-m", "third"]).assert().success();
+    env.gpp()
+        .args(&["status", "--remote", "origin"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("is ahead of 'origin' by 2 node(s)."));
+    env.gpp().arg("status").assert().failure();
+}
+
+#[test]
+fn test_content_ids_keep_git_working() {
+    let env = TestEnv::new();
+    env.gpp().arg("init").assert().success();
+    env.gpp().args(&["config", "graph.ids", "content"]).assert().success();
+    let mirror = TempDir::new().unwrap();
+    SysCommand::new("git").args(&["init", "--bare", "--quiet"]).current_dir(&mirror).output().unwrap();
+    let url = mirror.path().to_str().unwrap();
+
+    env.write_file("a.txt", "1");
+    let out = env.gpp().args(&["add", "-m", "first"]).output().unwrap();
+    let first = env.parse_node_id(&out.stdout);
+    env.write_file("a.txt", "2");
+    let out = env.gpp().args(&["add", "-m", "second"]).output().unwrap();
+    let second = env.parse_node_id(&out.stdout);
+
+    // id ноды - не коммит, коммит лежит рядом в графе, и его родитель - коммит первой ноды
+    let graph: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(env.path().join(".gitpp/graph.json")).unwrap()).unwrap();
+    let first_commit = graph["nodes"][&first]["commit"].as_str().unwrap().to_string();
+    let second_commit = graph["nodes"][&second]["commit"].as_str().unwrap().to_string();
+    assert_ne!(first, first_commit);
+    assert!(!env.git().args(&["cat-file", "-e", &second]).status().unwrap().success());
+    let parent = env.git().args(&["rev-parse", &format!("{}^", second_commit)]).output().unwrap();
+    assert_eq!(String::from_utf8_lossy(&parent.stdout).trim(), first_commit);
+    env.gpp().arg("verify").assert().success();
+
+    env.gpp().args(&["chrm", "--remote", "origin", "--url", url]).assert().success();
+    env.gpp().args(&["push", "--url", url]).assert().success()
+        .stdout(predicate::str::contains(format!("Pushed: origin refs/heads/main {}", second_commit)));
+    let main = SysCommand::new("git").args(&["rev-parse", "main"]).current_dir(&mirror).output().unwrap();
+    assert_eq!(String::from_utf8_lossy(&main.stdout).trim(), second_commit);
+    env.gpp()
+        .args(&["status", "--remote", "origin"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("is up to date with 'origin'."));
+
+    env.gpp().args(&["checkout", &first]).assert().success();
+    assert_eq!(fs::read_to_string(env.path().join("a.txt")).unwrap(), "1");
+}
+
+#[test]
+fn test_push_refuses_nodes_denied_for_the_remote() {
+    let env = TestEnv::new();
+    env.gpp().arg("init").assert().success();
+    let mirror = TempDir::new().unwrap();
+    SysCommand::new("git").args(&["init", "--bare", "--quiet"]).current_dir(&mirror).output().unwrap();
+    let url = mirror.path().to_str().unwrap();
+
+    env.write_file("a.txt", "1");
+    env.gpp().args(&["add", "-m", "public"]).assert().success();
+    env.write_file("a.txt", "2");
+    let out = env.gpp().args(&["add", "-m", "secret"]).output().unwrap();
+    let secret = env.parse_node_id(&out.stdout);
+    env.gpp().args(&["chrm", "--remote", "origin", "--url", url, "--recursive"]).assert().success();
+
+    let json_path = env.path().join(".gitpp/graph.json");
+    let mut graph: serde_json::Value = serde_json::from_str(&fs::read_to_string(&json_path).unwrap()).unwrap();
+    graph["nodes"][&secret]["metadata"]["classification"] = serde_json::json!("secret");
+    fs::write(&json_path, serde_json::to_string_pretty(&graph).unwrap()).unwrap();
+
+    env.gpp().args(&["config", "remote.origin.deny", "label:x"]).assert().failure();
+    env.gpp().args(&["config", "remote.origin.deny", "tag:internal, meta:classification=secret"]).assert().success();
+    env.gpp()
+        .args(&["push", "--url", url])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(format!(
+            "node {} matches 'meta:classification=secret', which must never reach remote 'origin'",
+            &secret[..7]
+        )));
+    let main = SysCommand::new("git").args(&["rev-parse", "--verify", "-q", "main"]).current_dir(&mirror).output().unwrap();
+    assert!(!main.status.success());
+
+    // правило действует только на свой ремоут и только на ноды с этими данными
+    env.gpp().args(&["push", "--url", url, "--node", "HEAD~1"]).assert().success();
+}
+
+#[test]
+fn test_push_dry_run_lists_new_nodes_and_ref_move() {
+    let env = TestEnv::new();
+    env.gpp().arg("init").assert().success();
+    let mirror = TempDir::new().unwrap();
+    SysCommand::new("git").args(&["init", "--bare", "--quiet"]).current_dir(&mirror).output().unwrap();
+    let url = mirror.path().to_str().unwrap();
+
+    env.write_file("f.txt", "1");
+    let out = env.gpp().args(&["add", "-m", "first\n\nbody"]).output().unwrap();
+    let first = env.parse_node_id(&out.stdout);
+    env.gpp()
+        .args(&["push", "--url", url, "--dry-run"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!("    {} first\n", &first[..7])))
+        .stdout(predicate::str::contains(format!("refs/heads/main  (none) -> {}", &first[..7])))
+        .stdout(predicate::str::contains("Push completed").not());
+    // план - результат команды, а не статус: --quiet его не прячет
+    env.gpp()
+        .args(&["push", "--url", url, "--dry-run", "--quiet"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!("    {} first\n", &first[..7])));
+    env.gpp().args(&["push", "--url", url]).assert().success();
+
+    env.write_file("f.txt", "2");
+    let out = env.gpp().args(&["add", "-m", "second"]).output().unwrap();
+    let second = env.parse_node_id(&out.stdout);
+    env.write_file("f.txt", "3");
+    let out = env.gpp().args(&["add", "-m", "third"]).output().unwrap();
+    let third = env.parse_node_id(&out.stdout);
+
+    // уже отправленная first в список не попадает, зато видно, откуда сдвинется main
+    env.gpp()
+        .args(&["push", "--url", url, "--dry-run"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("New nodes: 2"))
+        .stdout(predicate::str::contains(format!("    {} third\n    {} second\n", &third[..7], &second[..7])))
+        .stdout(predicate::str::contains(format!("    {} first", &first[..7])).not())
+        .stdout(predicate::str::contains(format!("refs/heads/main  {} -> {}", &first[..7], &third[..7])));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_push_no_verify_skips_pre_push_hook() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let env = TestEnv::new();
+    env.gpp().arg("init").assert().success();
+    env.write_file("a.txt", "A");
+    env.gpp().args(&["add", "-m", "first", "--no-verify"]).assert().success();
+
+    let mirror = TempDir::new().unwrap();
+    SysCommand::new("git").args(&["init", "--bare", "--quiet"]).current_dir(&mirror).output().unwrap();
+    let url = mirror.path().to_str().unwrap();
+
+    // сломанный хук в контексте ремоута блокирует любой push
+    let hook = env.path().join(".git_origin/hooks/pre-push");
+    fs::create_dir_all(hook.parent().unwrap()).unwrap();
+    fs::write(&hook, "#!/bin/sh\necho 'pre-push hook says no' >&2\nexit 1\n").unwrap();
+    fs::set_permissions(&hook, fs::Permissions::from_mode(0o755)).unwrap();
+
+    env.gpp()
+        .args(&["push", "--remote", "origin", "--url", url])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("pre-push hook says no"));
+    env.gpp()
+        .args(&["push", "--remote", "origin", "--url", url, "--no-verify"])
+        .assert()
+        .success();
+    let pushed = SysCommand::new("git").args(&["rev-parse", "--verify", "main"]).current_dir(&mirror).output().unwrap();
+    assert!(pushed.status.success());
+}
+
 #[test]
 fn test_push_to_local_bare_repository() {
     let env = TestEnv::new();
@@ -409,7 +1062,7 @@ fn test_push_to_local_bare_repository() {
         ])
         .assert()
         .success()
-        .stdout(predicate::str::contains("Успешно обновлена ссылка"));
+        .stdout(predicate::str::contains("Updated ref refs/heads/main"));
 
     let verify_cmd = SysCommand::new("git")
         .arg("--git-dir")
@@ -421,4 +1074,1311 @@ fn test_push_to_local_bare_repository() {
     let log_output = String::from_utf8(verify_cmd.stdout).unwrap();
 
     assert!(log_output.contains("feature_x"), "Remote repo should contain the pushed commit");
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_push_to_second_remote_without_switching_context() {
+    let env = TestEnv::new();
+    env.gpp().args(&["init", "--eager", "repo1", "repo2"]).assert().success();
+
+    let remote_dir = TempDir::new().unwrap();
+    let remote_path = remote_dir.path().to_str().unwrap().to_string();
+    let setup_status = SysCommand::new("git")
+        .args(&["init", "--bare"])
+        .current_dir(&remote_dir)
+        .output()
+        .expect("Failed to init bare repo");
+    assert!(setup_status.status.success());
+
+    env.write_file("shared.txt", "both remotes");
+    let out = env.gpp()
+        .args(&["add", "-m", "shared_node", "--remotes", "repo1", "repo2"])
+        .output()
+        .unwrap();
+    let node_id = env.parse_node_id(&out.stdout);
+
+    // Активный контекст - repo1, но объект коммита должен оказаться и в .git_repo2
+    let in_second = env.git()
+        .args(&["--git-dir", ".git_repo2", "cat-file", "-e", &node_id])
+        .status()
+        .unwrap();
+    assert!(in_second.success(), "Commit object must be replicated into .git_repo2");
+
+    env.gpp()
+        .args(&["chrm", "--node", &node_id, "--remote", "repo2", "--url", &remote_path])
+        .assert()
+        .success();
+
+    env.gpp()
+        .args(&["push", "--node", &node_id, "--remote", "repo2", "--url", &remote_path])
+        .assert()
+        .success();
+
+    let verify_cmd = SysCommand::new("git")
+        .arg("--git-dir")
+        .arg(remote_dir.path())
+        .args(&["log", "--oneline", "main"])
+        .output()
+        .expect("Failed to read remote log");
+    assert!(String::from_utf8_lossy(&verify_cmd.stdout).contains("shared_node"));
+}
+
+#[test]
+fn test_annotated_tags_are_listed() {
+    let env = TestEnv::new();
+    env.gpp().arg("init").assert().success();
+
+    env.gpp().args(&["tag", "--list"]).assert().success().stdout(predicate::str::contains("No tags."));
+
+    env.write_file("f.txt", "1");
+    let out = env.gpp().args(&["add", "-m", "first"]).output().unwrap();
+    let first = env.parse_node_id(&out.stdout);
+    env.write_file("f.txt", "2");
+    env.gpp().args(&["add", "-m", "second"]).assert().success();
+
+    env.gpp().args(&["tag", "zeta", "--node", &first]).assert().success();
+    // created_at хранится с точностью до секунды, иначе порядок по дате не отличить от порядка по имени
+    std::thread::sleep(std::time::Duration::from_millis(1100));
+    env.gpp().args(&["tag", "v1.0", "-m", "First release\nwith notes"]).assert().success();
+
+    let list = |args: &[&str]| {
+        let out = env.gpp().args(args).output().unwrap();
+        assert!(out.status.success());
+        String::from_utf8_lossy(&out.stdout).lines().map(str::to_string).collect::<Vec<_>>()
+    };
+    let by_name = list(&["tag", "--list"]);
+    assert_eq!(by_name.len(), 2);
+    assert!(by_name[0].starts_with("v1.0") && by_name[0].ends_with("First release"));
+    assert!(by_name[1].starts_with("zeta") && by_name[1].contains(&first[..7]));
+
+    let by_date = list(&["tag", "--list", "--sort", "date"]);
+    assert!(by_date[0].starts_with("zeta"));
+    assert!(by_date[1].starts_with("v1.0"));
+
+    env.gpp()
+        .args(&["log"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("v1.0 (First release)"));
+    env.gpp().args(&["tag", "--list", "extra"]).assert().failure();
+}
+
+#[test]
+fn test_push_by_tag_creates_remote_tag() {
+    let env = TestEnv::new();
+    env.gpp().arg("init").assert().success();
+
+    let remote_dir = TempDir::new().unwrap();
+    let remote_path = remote_dir.path().to_str().unwrap().to_string();
+    let setup_status = SysCommand::new("git")
+        .args(&["init", "--bare"])
+        .current_dir(&remote_dir)
+        .output()
+        .expect("Failed to init bare repo");
+    assert!(setup_status.status.success());
+
+    env.write_file("f.txt", "release");
+    let out = env.gpp().args(&["add", "-m", "release_node"]).output().unwrap();
+    let node_id = env.parse_node_id(&out.stdout);
+    env.write_file("f.txt", "later");
+    env.gpp().args(&["add", "-m", "later_node"]).assert().success();
+
+    env.gpp().args(&["tag", "v1.0", "--node", &node_id]).assert().success();
+    env.gpp().args(&["tag", "v1.0"]).assert().failure();
+    env.gpp()
+        .args(&["chrm", "--node", &node_id, "--remote", "origin", "--url", &remote_path, "--recursive"])
+        .assert()
+        .success();
+
+    env.gpp()
+        .args(&["push", "--tag", "v1.0", "--url", &remote_path, "--dry-run"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("refs/heads/main"))
+        .stdout(predicate::str::contains("refs/tags/v1.0"));
+
+    // последние строки одинаковы на любом языке: по ним CI узнаёт, что и куда ушло
+    let out = env.gpp().args(&["push", "--tag", "v1.0", "--url", &remote_path]).env("GPP_LANG", "ru").output().unwrap();
+    assert!(out.status.success());
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    let tail: Vec<&str> = stdout.lines().rev().take(2).collect();
+    assert_eq!(
+        tail,
+        vec![
+            format!("Pushed: origin refs/tags/v1.0 {}", node_id),
+            format!("Pushed: origin refs/heads/main {}", node_id),
+        ]
+    );
+
+    let verify = SysCommand::new("git")
+        .arg("--git-dir")
+        .arg(remote_dir.path())
+        .args(&["rev-parse", "refs/tags/v1.0", "refs/heads/main"])
+        .output()
+        .unwrap();
+    let refs = String::from_utf8_lossy(&verify.stdout);
+    assert_eq!(refs.lines().collect::<Vec<_>>(), vec![node_id.as_str(), node_id.as_str()]);
+
+    env.gpp().args(&["push", "--tag", "missing", "--url", &remote_path]).assert().failure();
+}
+
+#[test]
+fn test_repository_format_is_checked() {
+    let env = TestEnv::new();
+    env.gpp().arg("init").assert().success();
+    let format_path = env.path().join(".gitpp/format");
+    assert_eq!(fs::read_to_string(&format_path).unwrap().trim(), "2");
+
+    // старый репозиторий без файла format читается через миграции, но на диске обновляется только с первой записью
+    fs::remove_file(&format_path).unwrap();
+    let graph_before = fs::read_to_string(env.path().join(".gitpp/graph.json")).unwrap();
+    env.gpp().arg("log").assert().success();
+    env.gpp().arg("stats").assert().success();
+    assert!(!format_path.exists());
+    assert_eq!(fs::read_to_string(env.path().join(".gitpp/graph.json")).unwrap(), graph_before);
+    env.write_file("a.txt", "A");
+    env.gpp().args(&["add", "-m", "first"]).assert().success();
+    assert_eq!(fs::read_to_string(&format_path).unwrap().trim(), "2");
+
+    fs::write(&format_path, "99\n").unwrap();
+    env.gpp()
+        .arg("log")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Repository format 99 is newer than this gpp supports"));
+}
+
+#[test]
+fn test_context_switch_command() {
+    let env = TestEnv::new();
+    env.gpp().args(&["init", "personal", "work"]).assert().success();
+
+    env.gpp()
+        .args(&["context", "work"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Switched to context 'work'"));
+
+    let link = fs::read_link(env.path().join(".git")).expect(".git must be a link");
+    assert_eq!(link.to_string_lossy(), ".git_work");
+}
+
+#[test]
+fn test_context_list_marks_active() {
+    let env = TestEnv::new();
+    env.gpp().args(&["init", "personal", "work"]).assert().success();
+
+    env.gpp()
+        .args(&["context", "--list"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("* personal"))
+        .stdout(predicate::str::contains("  work"))
+        .stdout(predicate::str::contains("(.git is a symlink)"));
+}
+
+#[test]
+fn test_add_clears_stale_index_lock() {
+    let env = TestEnv::new();
+    env.gpp().arg("init").assert().success();
+    env.write_file("a.txt", "A");
+
+    // Свежий лок может принадлежать живому процессу - его трогать нельзя
+    let lock_path = env.path().join(".git/index.lock");
+    fs::write(&lock_path, "").unwrap();
+    env.gpp()
+        .args(&["add", "-m", "blocked"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("index.lock"));
+
+    // Протухший лок (остался после Ctrl-C) удаляется автоматически
+    let old = std::time::SystemTime::now() - std::time::Duration::from_secs(600);
+    fs::File::options().write(true).open(&lock_path).unwrap().set_modified(old).unwrap();
+    env.gpp()
+        .args(&["add", "-m", "after_crash"])
+        .assert()
+        .success();
+    env.assert_missing(".git/index.lock");
+}
+
+#[test]
+fn test_second_push_is_incremental() {
+    let env = TestEnv::new();
+    env.gpp().arg("init").assert().success();
+
+    let remote_dir = TempDir::new().unwrap();
+    let remote_path = remote_dir.path().to_str().unwrap().to_string();
+    SysCommand::new("git")
+        .args(&["init", "--bare"])
+        .current_dir(&remote_dir)
+        .output()
+        .expect("Failed to init bare repo");
+
+    env.write_file("a.txt", "A");
+    let out = env.gpp().args(&["add", "-m", "first"]).output().unwrap();
+    let node_id = env.parse_node_id(&out.stdout);
+    env.gpp()
+        .args(&["chrm", "--node", &node_id, "--remote", "mirror", "--url", &remote_path])
+        .assert()
+        .success();
+
+    let push = |expected: &str| {
+        env.gpp()
+            .args(&["push", "--node", &node_id, "--remote", "mirror", "--url", &remote_path])
+            .assert()
+            .success()
+            .stdout(predicate::str::contains(expected.to_string()));
+    };
+    push("Push completed successfully");
+    push("Nothing to push");
+}
+
+#[test]
+fn test_push_takes_url_from_node_or_template() {
+    let env = TestEnv::new();
+    env.gpp().arg("init").assert().success();
+
+    let remote_dir = TempDir::new().unwrap();
+    let remote_path = remote_dir.path().to_str().unwrap().to_string();
+    SysCommand::new("git")
+        .args(&["init", "--bare"])
+        .current_dir(&remote_dir)
+        .output()
+        .expect("Failed to init bare repo");
+
+    env.write_file("a.txt", "A");
+    let out = env.gpp().args(&["add", "-m", "first"]).output().unwrap();
+    let node = env.parse_node_id(&out.stdout);
+    env.gpp()
+        .args(&["chrm", "--node", &node, "--remote", "mirror", "--url", &remote_path])
+        .assert()
+        .success();
+
+    // URL берётся из ноды, куда его записал chrm
+    env.gpp()
+        .args(&["push", "--node", &node, "--remote", "mirror"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Push completed"));
+
+    // у origin из init URL нет: без шаблона - ошибка с подсказкой, с шаблоном - URL из него
+    env.gpp()
+        .args(&["push", "--node", &node, "--remote", "origin", "--dry-run"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("push.url-template"));
+    env.gpp().args(&["config", "push.url-template", "git@git.example.com:team/{remote}.git"]).assert().success();
+    env.gpp()
+        .args(&["push", "--node", &node, "--remote", "origin", "--dry-run"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("git@git.example.com:team/origin.git"));
+}
+
+#[test]
+fn test_push_to_hung_remote_times_out() {
+    let env = TestEnv::new();
+    env.gpp().arg("init").assert().success();
+    env.write_file("a.txt", "A");
+    let out = env.gpp().args(&["add", "-m", "first"]).output().unwrap();
+    let node = env.parse_node_id(&out.stdout);
+
+    // ремоут, который принимает соединение и молчит
+    let dead = "ext::sleep 5";
+    env.gpp()
+        .args(&["chrm", "--node", &node, "--remote", "dead", "--url", dead])
+        .assert()
+        .success();
+    env.gpp().args(&["config", "net.timeout", "soon"]).assert().failure();
+    env.gpp().args(&["config", "net.timeout", "1"]).assert().success();
+
+    let started = std::time::Instant::now();
+    env.gpp()
+        .args(&["push", "--node", &node, "--remote", "dead", "--url", dead])
+        .env("GIT_CONFIG_COUNT", "1")
+        .env("GIT_CONFIG_KEY_0", "protocol.ext.allow")
+        .env("GIT_CONFIG_VALUE_0", "always")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("timed out after 1s"));
+    assert!(started.elapsed() < std::time::Duration::from_secs(4), "push was not cut off by net.timeout");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_push_retries_transient_network_errors() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let env = TestEnv::new();
+    env.gpp().arg("init").assert().success();
+    env.write_file("a.txt", "A");
+    let out = env.gpp().args(&["add", "-m", "first"]).output().unwrap();
+    let node = env.parse_node_id(&out.stdout);
+
+    // ремоут, до которого "не доходит DNS": скрипт считает попытки и падает как curl
+    let helper_dir = TempDir::new().unwrap();
+    let attempts = helper_dir.path().join("attempts");
+    let script = helper_dir.path().join("flaky.sh");
+    fs::write(
+        &script,
+        format!(
+            "#!/bin/sh\necho x >> '{}'\necho 'fatal: unable to access: Could not resolve host: flaky.example' >&2\nexit 128\n",
+            attempts.display()
+        ),
+    )
+    .unwrap();
+    fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+    let flaky = format!("ext::{}", script.display());
+    env.gpp()
+        .args(&["chrm", "--node", &node, "--remote", "flaky", "--url", &flaky])
+        .assert()
+        .success();
+    env.gpp().args(&["config", "net.retries", "many"]).assert().failure();
+    env.gpp().args(&["config", "net.retries", "1"]).assert().success();
+
+    env.gpp()
+        .args(&["push", "--node", &node, "--remote", "flaky"])
+        .env("GIT_CONFIG_COUNT", "1")
+        .env("GIT_CONFIG_KEY_0", "protocol.ext.allow")
+        .env("GIT_CONFIG_VALUE_0", "always")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Retrying push (1/1)"))
+        .stderr(predicate::str::contains("Could not resolve host"));
+    assert_eq!(fs::read_to_string(&attempts).unwrap().lines().count(), 2);
+}
+
+#[test]
+fn test_push_rejected_when_remote_moved_ahead() {
+    let env = TestEnv::new();
+    env.gpp().arg("init").assert().success();
+
+    let remote_dir = TempDir::new().unwrap();
+    let remote_path = remote_dir.path().to_str().unwrap().to_string();
+    SysCommand::new("git")
+        .args(&["init", "--bare"])
+        .current_dir(&remote_dir)
+        .output()
+        .expect("Failed to init bare repo");
+
+    env.write_file("a.txt", "A");
+    let out = env.gpp().args(&["add", "-m", "first"]).output().unwrap();
+    let first = env.parse_node_id(&out.stdout);
+    env.gpp()
+        .args(&["chrm", "--node", &first, "--remote", "mirror", "--url", &remote_path])
+        .assert()
+        .success();
+    env.gpp()
+        .args(&["push", "--node", &first, "--remote", "mirror", "--url", &remote_path])
+        .assert()
+        .success();
+
+    // кто-то другой пушит в ту же ветку
+    let other = TempDir::new().unwrap();
+    let git = |args: &[&str]| {
+        let out = SysCommand::new("git")
+            .args(&["-c", "user.name=Other", "-c", "user.email=other@example.com"])
+            .args(args)
+            .current_dir(other.path())
+            .output()
+            .unwrap();
+        assert!(out.status.success(), "git {:?}: {}", args, String::from_utf8_lossy(&out.stderr));
+    };
+    git(&["clone", "-b", "main", &remote_path, "."]);
+    fs::write(other.path().join("b.txt"), "theirs").unwrap();
+    git(&["add", "-A"]);
+    git(&["commit", "-m", "theirs"]);
+    git(&["push", "origin", "HEAD:main"]);
+
+    env.write_file("a.txt", "B");
+    let out = env.gpp().args(&["add", "-m", "second"]).output().unwrap();
+    let second = env.parse_node_id(&out.stdout);
+    env.gpp()
+        .args(&["push", "--node", &second, "--remote", "mirror", "--url", &remote_path])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("non-fast-forward"))
+        .stderr(predicate::str::contains("hint:"))
+        .stderr(predicate::str::contains("[rejected]").not());
+}
+
+#[test]
+fn test_push_remembers_remote_tip_without_context() {
+    let env = TestEnv::new();
+    env.gpp().arg("init").assert().success();
+
+    let remote_dir = TempDir::new().unwrap();
+    let remote_path = remote_dir.path().to_str().unwrap().to_string();
+    SysCommand::new("git")
+        .args(&["init", "--bare"])
+        .current_dir(&remote_dir)
+        .output()
+        .expect("Failed to init bare repo");
+
+    env.write_file("a.txt", "A");
+    let out = env.gpp().args(&["add", "-m", "first"]).output().unwrap();
+    let first = env.parse_node_id(&out.stdout);
+    env.gpp()
+        .args(&["chrm", "--node", &first, "--remote", "mirror", "--url", &remote_path])
+        .assert()
+        .success();
+    env.gpp()
+        .args(&["push", "--node", &first, "--remote", "mirror", "--url", &remote_path])
+        .assert()
+        .success();
+
+    let graph: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(env.path().join(".gitpp/graph.json")).unwrap()).unwrap();
+    assert_eq!(graph["remote_tips"]["mirror"], first.as_str(), "pushed tip is not recorded in graph.json");
+    // в метаданные ноды служебная метка не попадает: её не видят find --meta и export
+    assert_eq!(graph["nodes"][&first]["metadata"], serde_json::json!({}));
+
+    // контекст со всеми tracking-ссылками потерян, но граф помнит, что уже лежит на ремоуте
+    fs::remove_dir_all(env.path().join(".git_mirror")).unwrap();
+    env.gpp()
+        .args(&["push", "--node", &first, "--remote", "mirror", "--url", &remote_path])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Nothing to push"));
+}
+
+#[test]
+fn test_repair_restores_corrupt_graph() {
+    let env = TestEnv::new();
+    env.gpp().arg("init").assert().success();
+    env.write_file("a.txt", "A");
+    env.gpp().args(&["add", "-m", "kept_node"]).assert().success();
+    env.write_file("a.txt", "B");
+    env.gpp().args(&["add", "-m", "second"]).assert().success();
+
+    env.write_file(".gitpp/graph.json", "{\"broken\": ");
+
+    env.gpp()
+        .arg("log")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("graph.json"))
+        .stderr(predicate::str::contains("gpp repair"));
+
+    env.gpp()
+        .arg("repair")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Restored graph"));
+
+    env.gpp()
+        .arg("log")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("kept_node"));
+}
+
+#[test]
+fn test_snapshot_create_list_restore() {
+    let env = TestEnv::new();
+    env.gpp().arg("init").assert().success();
+    env.write_file("a.txt", "A");
+    env.gpp().args(&["add", "-m", "before_snapshot"]).assert().success();
+
+    env.gpp().args(&["snapshot", "--name", "safe"]).assert().success();
+    env.assert_exists(".gitpp/snapshots/safe/graph.json");
+    env.assert_exists(".gitpp/snapshots/safe/HEAD");
+
+    env.write_file("a.txt", "B");
+    env.gpp().args(&["add", "-m", "after_snapshot"]).assert().success();
+
+    env.gpp()
+        .args(&["snapshot", "--list"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("safe"));
+
+    env.gpp().args(&["snapshot", "--restore", "safe"]).assert().success();
+
+    let graph = fs::read_to_string(env.path().join(".gitpp/graph.json")).unwrap();
+    assert!(graph.contains("before_snapshot"));
+    assert!(!graph.contains("after_snapshot"));
+}
+
+#[test]
+fn test_clone_imports_existing_history() {
+    let env = TestEnv::new();
+    let upstream = env.path().join("upstream");
+    fs::create_dir_all(&upstream).unwrap();
+
+    let git = |args: &[&str]| {
+        let status = SysCommand::new("git").args(args).current_dir(&upstream).status().unwrap();
+        assert!(status.success(), "git {:?} failed", args);
+    };
+    git(&["init", "--quiet", "-b", "main"]);
+    fs::write(upstream.join("a.txt"), "first").unwrap();
+    git(&["add", "-A"]);
+    git(&["commit", "--quiet", "-m", "upstream_first"]);
+    fs::write(upstream.join("a.txt"), "second").unwrap();
+    git(&["commit", "--quiet", "-am", "upstream_second"]);
+
+    // таймаут, который не помещается в Instant, - это просто "без ограничения"
+    env.gpp()
+        .args(&["clone", upstream.to_str().unwrap(), "cloned", "--timeout", "18446744073709551615"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("2 node(s) imported"));
+
+    env.assert_exists("cloned/.gitpp/HEAD");
+    env.assert_exists("cloned/.git_origin");
+    let content = fs::read_to_string(env.path().join("cloned/a.txt")).unwrap();
+    assert_eq!(content, "second");
+
+    let graph = fs::read_to_string(env.path().join("cloned/.gitpp/graph.json")).unwrap();
+    assert!(graph.contains("upstream_first"));
+    assert!(graph.contains("upstream_second"));
+
+    // граф рабочий: поверх импортированной истории можно коммитить
+    env.write_file("cloned/a.txt", "third");
+    env.gpp_in("cloned").args(&["add", "-m", "local_third"]).assert().success();
+}
+
+#[test]
+fn test_failed_clone_can_be_retried() {
+    let env = TestEnv::new();
+    let upstream = env.path().join("upstream");
+
+    env.gpp().args(&["clone", upstream.to_str().unwrap(), "cloned"]).assert().failure();
+    assert!(!env.path().join("cloned").exists());
+
+    // папка была и до clone: остаётся, но пустой
+    fs::create_dir_all(env.path().join("empty")).unwrap();
+    env.gpp().args(&["clone", upstream.to_str().unwrap(), "empty"]).assert().failure();
+    assert_eq!(fs::read_dir(env.path().join("empty")).unwrap().count(), 0);
+
+    fs::create_dir_all(&upstream).unwrap();
+    let git = |args: &[&str]| {
+        let status = SysCommand::new("git").args(args).current_dir(&upstream).status().unwrap();
+        assert!(status.success(), "git {:?} failed", args);
+    };
+    git(&["init", "--quiet", "-b", "main"]);
+    fs::write(upstream.join("a.txt"), "first").unwrap();
+    git(&["add", "-A"]);
+    git(&["commit", "--quiet", "-m", "upstream_first"]);
+
+    env.gpp().args(&["clone", upstream.to_str().unwrap(), "cloned"]).assert().success();
+    env.gpp().args(&["clone", upstream.to_str().unwrap(), "empty"]).assert().success();
+    env.assert_exists("empty/.gitpp/HEAD");
+}
+
+#[test]
+fn test_merge_base_of_diverged_nodes() {
+    let env = TestEnv::new();
+    env.gpp().arg("init").assert().success();
+
+    env.write_file("f.txt", "base");
+    let out = env.gpp().args(&["add", "-m", "base"]).output().unwrap();
+    let base = env.parse_node_id(&out.stdout);
+
+    env.write_file("f.txt", "left");
+    let out = env.gpp().args(&["add", "-m", "left"]).output().unwrap();
+    let left = env.parse_node_id(&out.stdout);
+
+    env.write_file("f.txt", "right");
+    let out = env.gpp().args(&["add", "-m", "right", "-p", &base]).output().unwrap();
+    let right = env.parse_node_id(&out.stdout);
+
+    env.gpp()
+        .args(&["merge-base", &left, &right])
+        .assert()
+        .success()
+        .stdout(predicate::str::diff(format!("{}\n", base)));
+}
+
+#[test]
+fn test_log_remote_filter() {
+    let env = TestEnv::new();
+    env.gpp().args(&["init", "public", "secret"]).assert().success();
+
+    env.write_file("f.txt", "1");
+    env.gpp().args(&["add", "-m", "shared base", "-r", "public", "secret"]).assert().success();
+    env.write_file("f.txt", "2");
+    env.gpp().args(&["add", "-m", "internal only", "-r", "secret"]).assert().success();
+
+    env.gpp()
+        .args(&["log", "--remote", "public", "--oneline"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("shared base"))
+        .stdout(predicate::str::contains("internal only").not());
+    env.gpp()
+        .args(&["log", "--remote", "secret", "--oneline"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("shared base"))
+        .stdout(predicate::str::contains("internal only"));
+}
+
+#[test]
+fn test_find_by_tag_and_metadata() {
+    let env = TestEnv::new();
+    env.gpp().arg("init").assert().success();
+    env.write_file("a.txt", "1");
+    env.gpp().args(&["add", "-m", "first"]).assert().success();
+    env.write_file("a.txt", "2");
+    let out = env.gpp().args(&["add", "-m", "release"]).output().unwrap();
+    let release = env.parse_node_id(&out.stdout);
+    env.gpp().args(&["tag", "v1.0"]).assert().success();
+
+    env.gpp()
+        .args(&["find", "--tag", "v1.0"])
+        .assert()
+        .success()
+        .stdout(predicate::str::diff(format!("{} release\n", &release[..7])));
+    env.gpp()
+        .args(&["find", "--tag", "v1.0", "--meta", "ci-build=123"])
+        .assert()
+        .success()
+        .stdout("No matching nodes.\n");
+    env.gpp().arg("find").assert().failure();
+}
+
+#[test]
+fn test_verify_compares_graph_edges_with_git() {
+    let env = TestEnv::new();
+    env.gpp().arg("init").assert().success();
+    env.write_file("a.txt", "1");
+    env.gpp().args(&["add", "-m", "first"]).assert().success();
+    env.write_file("a.txt", "2");
+    let out = env.gpp().args(&["add", "-m", "second"]).output().unwrap();
+    let second = env.parse_node_id(&out.stdout);
+
+    env.gpp()
+        .arg("verify")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Graph edges match git for 2 node(s)."));
+
+    // ребро пропало только из graph.json, git по-прежнему помнит родителя
+    let json_path = env.path().join(".gitpp/graph.json");
+    let mut graph: serde_json::Value = serde_json::from_str(&fs::read_to_string(&json_path).unwrap()).unwrap();
+    graph["nodes"][&second]["parents"] = serde_json::json!([]);
+    fs::write(&json_path, serde_json::to_string_pretty(&graph).unwrap()).unwrap();
+
+    env.gpp()
+        .arg("verify")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Graph disagrees with git on 1 of"))
+        .stderr(predicate::str::contains(&second[..7]));
+}
+
+#[test]
+fn test_verify_reports_duplicate_remote_names() {
+    let env = TestEnv::new();
+    env.gpp().arg("init").assert().success();
+    env.write_file("a.txt", "1");
+    let out = env.gpp().args(&["add", "-m", "first"]).output().unwrap();
+    let id = env.parse_node_id(&out.stdout);
+
+    // chrm с новым URL заменяет прежний, а не добавляет второй origin
+    env.gpp().args(&["chrm", "--remote", "origin", "--url", "https://a.example/repo.git"]).assert().success();
+    env.gpp().args(&["chrm", "--remote", "origin", "--url", "https://b.example/repo.git"]).assert().success();
+    let json_path = env.path().join(".gitpp/graph.json");
+    let mut graph: serde_json::Value = serde_json::from_str(&fs::read_to_string(&json_path).unwrap()).unwrap();
+    assert_eq!(graph["nodes"][&id]["remotes"].as_array().unwrap().len(), 1);
+    assert_eq!(graph["nodes"][&id]["remotes"][0]["url"], "https://b.example/repo.git");
+
+    // старый graph.json мог держать оба
+    let mut second = graph["nodes"][&id]["remotes"][0].clone();
+    second["url"] = serde_json::json!("https://a.example/repo.git");
+    graph["nodes"][&id]["remotes"].as_array_mut().unwrap().push(second);
+    fs::write(&json_path, serde_json::to_string_pretty(&graph).unwrap()).unwrap();
+    env.gpp()
+        .arg("verify")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("'origin' with urls [https://a.example/repo.git, https://b.example/repo.git]"));
+
+    env.gpp().args(&["chrm", "--remote", "origin", "--url", "https://b.example/repo.git"]).assert().success();
+    env.gpp().arg("verify").assert().success();
+}
+
+#[test]
+fn test_verify_signatures_fails_on_unsigned_nodes() {
+    let env = TestEnv::new();
+    env.gpp().arg("init").assert().success();
+    env.write_file("a.txt", "1");
+    let out = env.gpp().args(&["add", "-m", "first"]).output().unwrap();
+    let first = env.parse_node_id(&out.stdout);
+
+    env.gpp()
+        .args(&["verify", "--signatures"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("1 of 1 node(s) lack a valid signature"))
+        .stderr(predicate::str::contains(&format!("{} first  unsigned", &first[..7])));
+
+    // на work ничего не уходит - и проверять нечего
+    env.gpp()
+        .args(&["verify", "--signatures", "--remote", "work"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("All 0 node(s) have valid signatures."));
+    env.gpp().args(&["verify", "--remote", "work"]).assert().failure();
+}
+
+#[test]
+fn test_gc_dedup_reports_nodes_with_identical_trees() {
+    let env = TestEnv::new();
+    env.gpp().arg("init").assert().success();
+    env.write_file("f.txt", "v1");
+    env.gpp().args(&["add", "-m", "first"]).assert().success();
+    env.gpp().args(&["gc", "--dedup"]).assert().success().stdout(predicate::str::contains("No nodes share a tree."));
+
+    env.write_file("f.txt", "v2");
+    env.gpp().args(&["add", "-m", "change"]).assert().success();
+    env.write_file("f.txt", "v1");
+    env.gpp().args(&["add", "-m", "revert"]).assert().success();
+
+    let out = env.gpp().args(&["gc", "--dedup"]).output().unwrap();
+    assert!(out.status.success());
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.contains("is shared by 2 nodes:"), "{}", stdout);
+    assert!(stdout.contains(" first") && stdout.contains(" revert"), "{}", stdout);
+    assert!(!stdout.contains(" change"), "{}", stdout);
+
+    env.gpp().arg("gc").assert().failure();
+}
+
+#[test]
+fn test_export_remote_subgraph() {
+    let env = TestEnv::new();
+    env.gpp().args(&["init", "public", "secret"]).assert().success();
+
+    env.write_file("f.txt", "1");
+    let out = env.gpp().args(&["add", "-m", "shared base", "-r", "public", "secret"]).output().unwrap();
+    let base = env.parse_node_id(&out.stdout);
+    env.write_file("f.txt", "2");
+    let out = env.gpp().args(&["add", "-m", "internal only", "-r", "secret"]).output().unwrap();
+    let hidden = env.parse_node_id(&out.stdout);
+
+    let out = env.gpp().args(&["export", "--remote", "public"]).output().unwrap();
+    assert!(out.status.success());
+    let exported: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+    let nodes = exported.as_object().unwrap();
+    assert_eq!(nodes.len(), 1);
+    // ребро к скрытому ребёнку выброшено, а не оставлено висеть
+    assert_eq!(nodes[&base]["children"], serde_json::json!([]));
+    assert!(!String::from_utf8_lossy(&out.stdout).contains(&hidden));
+}
+
+#[test]
+fn test_log_grep_and_author_filters() {
+    let env = TestEnv::new();
+    env.gpp().arg("init").assert().success();
+
+    env.write_file("f.txt", "1");
+    env.gpp().args(&["add", "-m", "feat: parser"]).assert().success();
+    env.write_file("f.txt", "2");
+    env.gpp().args(&["add", "-m", "fix: parser crash"]).assert().success();
+    env.write_file("f.txt", "3");
+    env.gpp().args(&["add", "-m", "docs"]).assert().success();
+
+    // отфильтрованный корень не мешает дойти до потомков
+    env.gpp()
+        .args(&["log", "--grep", "FIX"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("fix: parser crash"))
+        .stdout(predicate::str::contains("feat: parser").not())
+        .stdout(predicate::str::contains("docs").not());
+
+    env.gpp()
+        .args(&["log", "--grep", "^(feat|docs)", "--regex"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("feat: parser"))
+        .stdout(predicate::str::contains("docs"))
+        .stdout(predicate::str::contains("fix:").not());
+
+    env.gpp()
+        .args(&["log", "--author", "nobody"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("parser").not());
+
+    env.gpp().args(&["log", "--grep", "(", "--regex"]).assert().failure();
+}
+
+#[test]
+fn test_log_date_range() {
+    let env = TestEnv::new();
+    env.gpp().arg("init").assert().success();
+    env.write_file("f.txt", "1");
+    env.gpp().args(&["add", "-m", "recent_node"]).assert().success();
+
+    env.gpp()
+        .args(&["log", "--since", "1.day"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("recent_node"));
+
+    env.gpp()
+        .args(&["log", "--until", "2000-01-01"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("recent_node").not());
+
+    env.gpp()
+        .args(&["log", "--since", "someday"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid date 'someday'"));
+}
+
+#[test]
+fn test_log_graph_shows_fork_and_merge() {
+    let env = TestEnv::new();
+    env.gpp().arg("init").assert().success();
+
+    env.write_file("f.txt", "base");
+    let out = env.gpp().args(&["add", "-m", "base"]).output().unwrap();
+    let base = env.parse_node_id(&out.stdout);
+
+    env.write_file("f.txt", "left");
+    let out = env.gpp().args(&["add", "-m", "left"]).output().unwrap();
+    let left = env.parse_node_id(&out.stdout);
+
+    env.write_file("f.txt", "right");
+    let out = env.gpp().args(&["add", "-m", "right", "-p", &base]).output().unwrap();
+    let right = env.parse_node_id(&out.stdout);
+
+    env.write_file("f.txt", "merged");
+    env.gpp().args(&["add", "-m", "merge", "-p", &left, &right]).assert().success();
+
+    let out = env.gpp().args(&["log", "--graph"]).output().unwrap();
+    assert!(out.status.success());
+    let text = String::from_utf8_lossy(&out.stdout);
+    let lines: Vec<&str> = text.lines().collect();
+
+    assert!(lines[0].starts_with("* ") && lines[0].ends_with(" merge"), "{}", text);
+    assert_eq!(lines[1], "|\\");
+    assert!(lines.contains(&"|/"), "{}", text);
+    assert!(lines.last().unwrap().starts_with(&format!("* {} base", &base[..7])), "{}", text);
+
+    env.gpp()
+        .arg("log")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!("Merge:     {} {}", &left[..7], &right[..7])));
+}
+
+#[test]
+fn test_multi_paragraph_messages() {
+    let env = TestEnv::new();
+    env.gpp().arg("init").assert().success();
+    env.write_file("f.txt", "1");
+    env.gpp().args(&["add", "-m", "Subject", "-m", "Body paragraph"]).assert().success();
+
+    env.gpp()
+        .arg("log")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Message:   Subject"))
+        .stdout(predicate::str::contains("Body paragraph").not());
+    env.gpp()
+        .args(&["log", "--full"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Message:   Subject\n\n           Body paragraph"));
+    env.gpp().args(&["log", "--full", "--oneline"]).assert().failure();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_add_without_message_opens_editor() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let env = TestEnv::new();
+    env.gpp().arg("init").assert().success();
+
+    let editor_dir = TempDir::new().unwrap();
+    let editor = |name: &str, body: &str| {
+        let path = editor_dir.path().join(name);
+        fs::write(&path, format!("#!/bin/sh\nprintf '{}' >> \"$1\"\n", body)).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    };
+
+    env.write_file("f.txt", "1");
+    let good = editor("good.sh", "Edited subject\\n\\nEdited body\\n# not part of it\\n");
+    env.gpp().arg("add").env("EDITOR", &good).assert().success();
+    let out = env.gpp().args(&["log", "--json"]).output().unwrap();
+    let entries: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+    assert_eq!(entries[0]["node"]["message"], "Edited subject\n\nEdited body");
+
+    // только комментарии из шаблона - отмена, нода не создаётся
+    env.write_file("f.txt", "2");
+    let empty = editor("empty.sh", "");
+    env.gpp()
+        .arg("add")
+        .env("EDITOR", &empty)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("empty message"));
+    let out = env.gpp().args(&["log", "--oneline"]).output().unwrap();
+    assert_eq!(String::from_utf8_lossy(&out.stdout).lines().count(), 1);
+}
+
+#[test]
+fn test_log_oneline_and_json_formats() {
+    let env = TestEnv::new();
+    env.gpp().arg("init").assert().success();
+    env.write_file("f.txt", "1");
+    let out = env.gpp().args(&["add", "-m", "first line\nsecond line"]).output().unwrap();
+    let id = env.parse_node_id(&out.stdout);
+
+    env.gpp()
+        .args(&["log", "--oneline"])
+        .assert()
+        .success()
+        .stdout(predicate::str::diff(format!("{} first line\n", &id[..7])));
+
+    let out = env.gpp().args(&["log", "--json"]).output().unwrap();
+    assert!(out.status.success());
+    let entries: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+    assert_eq!(entries[0]["node"]["id"], id.as_str());
+    assert_eq!(entries[0]["node"]["message"], "first line\nsecond line");
+
+    env.gpp().args(&["log", "--json", "--oneline"]).assert().failure();
+}
+
+#[test]
+fn test_log_stat() {
+    let env = TestEnv::new();
+    env.gpp().arg("init").assert().success();
+    env.write_file("f.txt", "a\nb\nc\n");
+    env.gpp().args(&["add", "-m", "root"]).assert().success();
+    env.write_file("f.txt", "a\nB\nc\n");
+    env.write_file("g.txt", "x\n");
+    env.gpp().args(&["add", "-m", "edit"]).assert().success();
+
+    // корень считается от пустого дерева
+    env.gpp()
+        .args(&["log", "--stat"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("1 file changed, 3 insertions(+)\n"))
+        .stdout(predicate::str::contains("2 files changed, 2 insertions(+), 1 deletion(-)"));
+
+    let out = env.gpp().args(&["log", "--stat", "--json"]).output().unwrap();
+    let entries: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+    let edit = entries.as_array().unwrap().iter().find(|e| e["node"]["message"] == "edit").unwrap();
+    assert_eq!(edit["stat"], serde_json::json!({"files": 2, "insertions": 2, "deletions": 1}));
+
+    let out = env.gpp().args(&["log", "--json"]).output().unwrap();
+    assert!(!String::from_utf8_lossy(&out.stdout).contains("\"stat\""));
+}
+
+#[test]
+fn test_stats_human_and_json() {
+    let env = TestEnv::new();
+    env.gpp().arg("init").assert().success();
+    env.write_file("f.txt", "1");
+    env.gpp().args(&["add", "-m", "one"]).assert().success();
+    env.write_file("f.txt", "2");
+    env.gpp().args(&["add", "-m", "two"]).assert().success();
+
+    env.gpp()
+        .arg("stats")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Nodes:            2"))
+        .stdout(predicate::str::contains("Max depth:        2"));
+
+    let out = env.gpp().args(&["stats", "--json"]).output().unwrap();
+    assert!(out.status.success());
+    let stats: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+    assert_eq!(stats["nodes"], 2);
+    assert_eq!(stats["roots"], 1);
+    assert_eq!(stats["tips"], 1);
+    assert_eq!(stats["nodes_per_remote"]["origin"], 2);
+}
+
+#[test]
+fn test_node_timestamp_matches_git_commit() {
+    let env = TestEnv::new();
+    env.gpp().arg("init").assert().success();
+    env.write_file("f.txt", "1");
+    let out = env.gpp().args(&["add", "-m", "stamped"]).output().unwrap();
+    let id = env.parse_node_id(&out.stdout);
+
+    let git_out = env.git().args(&["show", "-s", "--format=%at %ct", &id]).output().unwrap();
+    let git_times = String::from_utf8_lossy(&git_out.stdout).trim().to_string();
+    let (author_ts, committer_ts) = git_times.split_once(' ').unwrap();
+    assert_eq!(author_ts, committer_ts);
+
+    let graph: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(env.path().join(".gitpp/graph.json")).unwrap()).unwrap();
+    let created_at = graph["nodes"][&id]["created_at"].as_str().unwrap();
+    let node_ts = chrono::DateTime::parse_from_rfc3339(created_at).unwrap().timestamp();
+    assert_eq!(node_ts.to_string(), author_ts);
+}
+
+#[test]
+fn test_add_with_explicit_date() {
+    let env = TestEnv::new();
+    env.gpp().arg("init").assert().success();
+    env.write_file("f.txt", "1");
+    let out = env.gpp().args(&["add", "-m", "imported", "--date", "2020-01-02T03:04:05Z"]).output().unwrap();
+    let id = env.parse_node_id(&out.stdout);
+
+    let git_out = env.git().args(&["show", "-s", "--format=%at %ct", &id]).output().unwrap();
+    assert_eq!(String::from_utf8_lossy(&git_out.stdout).trim(), "1577934245 1577934245");
+    let graph: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(env.path().join(".gitpp/graph.json")).unwrap()).unwrap();
+    let created_at = graph["nodes"][&id]["created_at"].as_str().unwrap();
+    assert_eq!(chrono::DateTime::parse_from_rfc3339(created_at).unwrap().timestamp(), 1577934245);
+
+    env.write_file("f.txt", "2");
+    env.gpp()
+        .args(&["add", "-m", "later", "--date", "2999-01-01"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--allow-future"));
+    env.gpp().args(&["add", "-m", "later", "--date", "2999-01-01", "--allow-future"]).assert().success();
+    env.gpp().args(&["add", "-m", "bad", "--date", "someday"]).assert().failure();
+}
+
+#[test]
+fn test_node_keeps_author_timezone() {
+    let env = TestEnv::new();
+    env.gpp().arg("init").assert().success();
+    env.write_file("f.txt", "1");
+    // POSIX-запись пояса: <+03>-3 - это UTC+3
+    let out = env.gpp().env("TZ", "<+03>-3").args(&["add", "-m", "moscow", "--date", "2020-01-02T03:04:05Z"]).output().unwrap();
+    let id = env.parse_node_id(&out.stdout);
+
+    let graph: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(env.path().join(".gitpp/graph.json")).unwrap()).unwrap();
+    assert_eq!(graph["nodes"][&id]["tz_offset"], 3 * 3600);
+    let git_out = env.git().args(&["show", "-s", "--format=%ai", &id]).output().unwrap();
+    assert_eq!(String::from_utf8_lossy(&git_out.stdout).trim(), "2020-01-02 06:04:05 +0300");
+
+    // по умолчанию - пояс автора, где бы ни смотрели лог
+    env.gpp().env("TZ", "UTC").arg("log").assert().success()
+        .stdout(predicate::str::contains("2020-01-02 06:04:05 +0300"));
+    env.gpp().args(&["log", "--date", "utc"]).assert().success()
+        .stdout(predicate::str::contains("2020-01-02 03:04:05 +0000"));
+    env.gpp().env("TZ", "<-05>5").args(&["log", "--date", "local"]).assert().success()
+        .stdout(predicate::str::contains("2020-01-01 22:04:05 -0500"));
+}
+
+#[test]
+fn test_stash_and_pop() {
+    let env = TestEnv::new();
+    env.gpp().arg("init").assert().success();
+    env.write_file("f.txt", "committed");
+    env.gpp().args(&["add", "-m", "base"]).assert().success();
+
+    env.write_file("f.txt", "work in progress");
+    env.write_file("new.txt", "untracked");
+    env.gpp().arg("stash").assert().success();
+
+    assert_eq!(fs::read_to_string(env.path().join("f.txt")).unwrap(), "committed");
+    env.assert_missing("new.txt");
+    env.assert_exists(".gitpp/stash");
+
+    // второй слот не поддерживается
+    env.write_file("f.txt", "another change");
+    env.gpp().arg("stash").assert().failure();
+    env.write_file("f.txt", "committed");
+
+    env.gpp().args(&["stash", "pop"]).assert().success();
+    assert_eq!(fs::read_to_string(env.path().join("f.txt")).unwrap(), "work in progress");
+    env.assert_exists("new.txt");
+    env.assert_missing(".gitpp/stash");
+}
+
+#[test]
+fn test_commands_work_from_subdirectory() {
+    let env = TestEnv::new();
+    env.gpp().arg("init").assert().success();
+    env.write_file("src/nested/lib.rs", "fn main() {}");
+
+    env.gpp_in("src/nested").args(&["add", "-m", "from_subdir"]).assert().success();
+    env.assert_exists(".gitpp/HEAD");
+    env.assert_missing("src/nested/.gitpp");
+
+    env.gpp_in("src")
+        .arg("log")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("from_subdir"));
+}
+
+#[test]
+fn test_repo_flag_and_env_override_location() {
+    let env = TestEnv::new();
+    fs::create_dir_all(env.path().join("project")).unwrap();
+    fs::create_dir_all(env.path().join("elsewhere")).unwrap();
+
+    env.gpp_in("elsewhere").args(&["--repo", "../project", "init"]).assert().success();
+    env.assert_exists("project/.gitpp/graph.json");
+    env.assert_missing("elsewhere/.gitpp");
+
+    env.write_file("project/a.txt", "A");
+    env.gpp_in("elsewhere").args(&["add", "-m", "via_flag", "--repo", "../project"]).assert().success();
+
+    env.gpp_in("elsewhere")
+        .env("GPP_DIR", env.path().join("project"))
+        .arg("log")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("via_flag"));
+}
+
+#[test]
+fn test_outside_repository_reports_git_style_error() {
+    let env = TestEnv::new();
+    fs::create_dir_all(env.path().join("plain/dir")).unwrap();
+
+    env.gpp_in("plain/dir")
+        .arg("log")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("not a gpp repository (or any of the parent directories)"));
+}
+
+#[test]
+fn test_completions_generation() {
+    let env = TestEnv::new();
+    // репозиторий не нужен
+    env.gpp()
+        .args(&["completions", "bash"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("_gpp()"))
+        .stdout(predicate::str::contains("checkout"));
+
+    env.gpp().args(&["completions", "fish"]).assert().success().stdout(predicate::str::contains("complete -c gpp"));
+    env.gpp().args(&["completions", "tcsh-ish"]).assert().failure();
+}
+
+#[test]
+fn test_config_inherit_intersection() {
+    let env = TestEnv::new();
+    env.gpp().args(&["init", "origin", "secret"]).assert().success();
+
+    env.write_file("a.txt", "public");
+    let out = env.gpp().args(&["add", "-m", "public", "--remotes", "origin"]).output().unwrap();
+    let public = env.parse_node_id(&out.stdout);
+    env.write_file("a.txt", "secret");
+    let out = env.gpp().args(&["add", "-m", "secret", "-p", "--remotes", "origin", "secret"]).output().unwrap();
+    let secret = env.parse_node_id(&out.stdout);
+
+    env.gpp().args(&["config", "remotes.inherit", "sometimes"]).assert().failure();
+    env.gpp().args(&["config", "remotes.inherit", "intersection"]).assert().success();
+    env.gpp()
+        .args(&["config", "remotes.inherit"])
+        .assert()
+        .success()
+        .stdout("intersection\n");
+
+    env.write_file("a.txt", "merged");
+    env.gpp().args(&["add", "-m", "merge", "-p", &public, &secret]).assert().success();
+    env.gpp()
+        .args(&["add", "-m", "leak", "-p", &public, &secret, "--remotes", "secret"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Validation Error"));
+}
+
+#[test]
+fn test_output_language() {
+    let env = TestEnv::new();
+    env.gpp().arg("init").env("GPP_LANG", "ru").assert().success().stdout(predicate::str::contains("Готово!"));
+    env.gpp().arg("log").env("GPP_LANG", "de").assert().failure().stderr(predicate::str::contains("Invalid GPP_LANG"));
+
+    env.gpp().args(&["config", "ui.lang", "fr"]).assert().failure();
+    env.gpp().args(&["config", "ui.lang", "ru"]).assert().success();
+    env.gpp().arg("log").env_remove("GPP_LANG").assert().success().stdout(predicate::str::contains("История пуста."));
+    // переменная окружения важнее конфига
+    env.gpp().arg("log").assert().success().stdout(predicate::str::contains("History is empty."));
+
+    // строка для скриптов одна на все языки
+    env.write_file("f.txt", "x");
+    env.gpp()
+        .args(&["add", "-m", "first"])
+        .env_remove("GPP_LANG")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Node created: "));
+
+    // ошибки диспетчера тоже переводятся
+    env.gpp()
+        .args(&["push", "--tag", "missing"])
+        .env("GPP_LANG", "ru")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Тег 'missing' не найден"));
+}
+
+#[test]
+fn test_abbrev_len_config() {
+    let env = TestEnv::new();
+    env.gpp().arg("init").assert().success();
+    env.write_file("f.txt", "x");
+    let out = env.gpp().args(&["add", "-m", "first"]).output().unwrap();
+    let id = env.parse_node_id(&out.stdout);
+
+    env.gpp().args(&["log", "--oneline"]).assert().success().stdout(predicate::str::contains(format!("{} first", &id[..7])));
+
+    env.gpp().args(&["config", "ui.abbrev-len", "3"]).assert().failure();
+    env.gpp().args(&["config", "ui.abbrev-len", "12"]).assert().success();
+    env.gpp().args(&["log", "--oneline"]).assert().success().stdout(predicate::str::contains(format!("{} first", &id[..12])));
+    // и в сообщениях ядра
+    env.gpp().args(&["tag", "v1"]).assert().success().stdout(predicate::str::contains(format!("Tagged {} as", &id[..12])));
+}
+
+#[test]
+fn test_add_with_co_authors() {
+    let env = TestEnv::new();
+    env.gpp().arg("init").assert().success();
+    env.write_file("f.txt", "pair");
+
+    let out = env.gpp()
+        .args(&["add", "-m", "pairing", "--co-author", "Bob <bob@example.com>", "--co-author", "Eve <eve@example.com>"])
+        .output()
+        .unwrap();
+    let id = env.parse_node_id(&out.stdout);
+
+    let git_out = env.git().args(&["log", "-1", "--format=%B", &id]).output().unwrap();
+    let body = String::from_utf8_lossy(&git_out.stdout);
+    assert!(body.contains("Co-authored-by: Bob <bob@example.com>"));
+    assert!(body.contains("Co-authored-by: Eve <eve@example.com>"));
+
+    env.gpp()
+        .arg("log")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Co-author: Eve <eve@example.com>"));
+
+    env.gpp().args(&["add", "-m", "x", "--co-author", "just-a-name"]).assert().failure();
+}
+
+#[test]
+fn test_add_in_empty_repository_creates_empty_tree_root() {
+    let env = TestEnv::new();
+    env.gpp().arg("init").assert().success();
+
+    let out = env.gpp().args(&["add", "-m", "empty root"]).output().unwrap();
+    assert!(out.status.success());
+    let root = env.parse_node_id(&out.stdout);
+
+    let tree = env.git().args(&["rev-parse", &format!("{}^{{tree}}", root)]).output().unwrap();
+    assert_eq!(String::from_utf8_lossy(&tree.stdout).trim(), "4b825dc642cb6eb9a060e54bf8d69288fbee4904");
+
+    // на пустой корень нормально ложатся следующие ноды
+    env.write_file("first.txt", "content");
+    env.gpp().args(&["add", "-m", "first file"]).assert().success();
+    env.gpp().args(&["checkout", &root]).assert().success();
+    env.assert_missing("first.txt");
+}
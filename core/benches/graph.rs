@@ -0,0 +1,108 @@
+// Горячие пути на синтетических графах: add, log и выбор нод для push.
+// Всё в памяти (MockRepoBackend + MemoryStorage), так что меряется сам движок, а не git и диск.
+// Запуск: cargo bench -p gpp_core --features test-util
+use chrono::{Duration, TimeZone, Utc};
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+
+use gpp_core::backend::HistoryEntry;
+use gpp_core::LogFilter;
+use gpp_core::PushManager;
+use gpp_core::testing::{test_author, MemoryStorage, MockRepoBackend};
+use gpp_core::{Command, CommandDispatcher, NodeId, RemoteRef, VersionGraph};
+
+const SIZES: [usize; 3] = [10, 1_000, 10_000];
+
+fn id(i: usize) -> NodeId {
+    NodeId(format!("{:040x}", i))
+}
+
+fn origin() -> RemoteRef {
+    RemoteRef::without_url("origin").unwrap()
+}
+
+/// Цепочка из n нод, каждая десятая - merge с нодой пятью шагами раньше, чтобы обходы видели и ветвления
+fn history(n: usize) -> Vec<HistoryEntry> {
+    let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+    (0..n)
+        .map(|i| {
+            let mut parents = Vec::new();
+            if i > 0 {
+                parents.push(id(i - 1));
+            }
+            if i >= 5 && i % 10 == 0 {
+                parents.push(id(i - 5));
+            }
+            HistoryEntry {
+                id: id(i),
+                parents,
+                tree_id: format!("tree{}", i),
+                message: format!("node {}", i),
+                author: test_author(),
+                created_at: (start + Duration::minutes(i as i64)).fixed_offset(),
+            }
+        })
+        .collect()
+}
+
+/// Граф из n нод одной транзакцией (add_node по одной на 10k нод строил бы его минутами)
+fn graph(n: usize) -> (VersionGraph, MockRepoBackend) {
+    let backend = MockRepoBackend::new();
+    let mut graph = VersionGraph::new(Box::new(MemoryStorage::new()), Box::new(backend.clone()));
+    graph.import_history(history(n), origin()).unwrap();
+    backend.set_worktree("bench");
+    (graph, backend)
+}
+
+fn add_node(c: &mut Criterion) {
+    let mut group = c.benchmark_group("add_node");
+    for n in SIZES {
+        // свежий граф на каждый замер: иначе он рос бы с каждой итерацией
+        group.bench_with_input(BenchmarkId::from_parameter(n), &n, |b, &n| {
+            b.iter_batched_ref(
+                || graph(n),
+                |(graph, _)| graph.add_node(vec![id(n - 1)], test_author(), vec![], "bench".into(), None).unwrap(),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn log(c: &mut Criterion) {
+    let mut group = c.benchmark_group("log");
+    for n in SIZES {
+        let mut dispatcher = CommandDispatcher::new(graph(n).0);
+        for with_graph in [false, true] {
+            let name = if with_graph { "graph" } else { "plain" };
+            group.bench_with_input(BenchmarkId::new(name, n), &n, |b, _| {
+                b.iter(|| {
+                    dispatcher
+                        .dispatch(Command::Log { filter: LogFilter::default(), graph: with_graph, stat: false, from: None })
+                        .unwrap()
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+fn compute_nodes_to_push(c: &mut Criterion) {
+    let mut group = c.benchmark_group("compute_nodes_to_push");
+    for n in SIZES {
+        let (graph, backend) = graph(n);
+        let remote = origin();
+        // половина истории уже на ремоуте: обход идёт до его вершины, а не до корня
+        let remote_tips = vec![id(n / 2)];
+        group.bench_with_input(BenchmarkId::from_parameter(n), &n, |b, &n| {
+            b.iter(|| {
+                PushManager::new(&graph, &backend)
+                    .compute_nodes_to_push(&id(n - 1), &remote, &remote_tips)
+                    .unwrap()
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, add_node, log, compute_nodes_to_push);
+criterion_main!(benches);
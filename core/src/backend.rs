@@ -1,38 +1,149 @@
-use std::process::Output;
 use std::error::Error;
+use chrono::{DateTime, FixedOffset};
+use serde::Serialize;
 use crate::Node;
 use crate::types::{NodeId, RemoteRef, Author};
 
+/// События длинного push-а для индикатора прогресса
+#[derive(Debug, Clone)]
+pub enum Progress {
+    /// Сколько нод уйдёт на ремоут
+    Planned { nodes: usize },
+    /// Строка прогресса от git ("Writing objects:  42% ...")
+    Transfer(String),
+    /// Временная сетевая ошибка, через delay будет попытка номер attempt из retries
+    Retry { attempt: u32, retries: u32, delay: std::time::Duration, error: String },
+}
 
-pub trait RepoBackend {
-    // по-идее, от этого надо будет избавиться, потому что любые runcmd нужные для git должен делать сам RepoBackend
-    fn run_cmd(&self, cmd: &str, args: Vec<&str>) -> Result<Output, Box<dyn Error>>;
+/// Коммит из уже существующей истории репозитория (для gpp clone)
+#[derive(Debug, Clone)]
+pub struct HistoryEntry {
+    pub id: NodeId,
+    pub parents: Vec<NodeId>,
+    pub tree_id: String,
+    pub message: String,
+    pub author: Author,
+    /// Дата автора вместе с его поясом, как в %aI
+    pub created_at: DateTime<FixedOffset>,
+}
 
-    fn read_ref(&self, refname: String) -> Result<Option<NodeId>, Box<dyn Error>>;
+/// Подпись коммита ноды, как её видит `git verify-commit`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SignatureStatus {
+    /// Подпись верна; signer - кто подписал (как в %GS)
+    Good { signer: String },
+    Unsigned,
+    /// Подпись есть, но не проходит: испорчена, ключ отозван или просрочен, ключа нет для проверки
+    Bad(String),
+}
 
+/// Размер изменений ноды относительно первого родителя, как итоговая строка `git log --stat`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct DiffStat {
+    pub files: usize,
+    pub insertions: usize,
+    pub deletions: usize,
+}
+
+pub trait RepoBackend {
+    fn read_ref(&self, refname: &str) -> Result<Option<NodeId>, Box<dyn Error>>;
+
+    /// Дерево из текущего состояния рабочей директории. Пустая директория - это законное
+    /// пустое дерево, так что первую ноду можно создать и без единого файла.
     fn create_tree(&self) -> Result<String, Box<dyn Error>>;
 
+    /// То же дерево, что create_tree, но собранное во временном индексе: индекс, который пользователь
+    /// застейджил для обычного git, остаётся как был.
+    fn create_tree_isolated(&self) -> Result<String, Box<dyn Error>>;
+
+    /// Дерево, в котором от рабочей директории взяты только `paths`, а всё остальное - как в base_tree
+    /// (дерево родителя; None - пустое дерево). Индекс и неперечисленные изменения не трогаются.
+    fn create_tree_from_paths(&self, base_tree: Option<&str>, paths: &[String]) -> Result<String, Box<dyn Error>>;
+
+    /// Пути, которые в рабочей директории отличаются от base_tree (None - пустое дерево):
+    /// изменённые, новые и удалённые, отсортированные. Индекс не трогается.
+    fn changed_paths(&self, base_tree: Option<&str>) -> Result<Vec<String>, Box<dyn Error>>;
+
+    /// Сколько файлов и строк изменилось от tree_a (None - пустое дерево) к tree_b.
+    /// У бинарных файлов строки не считаются, только сам файл.
+    fn diff_stat(&self, tree_a: Option<&str>, tree_b: &str) -> Result<DiffStat, Box<dyn Error>>;
+
+    /// Приводит рабочую директорию и индекс к дереву tree_id (в активном контексте)
+    fn restore_tree(&self, tree_id: &str) -> Result<(), Box<dyn Error>>;
+
+    /// `timestamp` становится и author-, и committer-датой коммита,
+    /// чтобы время в git и Node.created_at не расходились. Пояс тоже уходит в коммит (Node.tz_offset).
     fn create_commit(
         &self,
         tree_oid: &str,
         parents: &[NodeId],
         message: &str,
-        author: &Author
+        author: &Author,
+        timestamp: DateTime<FixedOffset>,
     ) -> Result<NodeId, Box<dyn Error>>;
 
+    /// Копирует объект коммита во все контексты перечисленных ремоутов.
+    /// Каждый ремоут живёт в своём хранилище объектов, поэтому create_commit сам по себе недостаточен.
+    fn replicate_commit(&self, commit_id: &NodeId, remotes: &[RemoteRef]) -> Result<(), Box<dyn Error>>;
+
+    /// Откатывает то, что create_commit и replicate_commit сделали со ссылками: HEAD активного контекста
+    /// и контекстов remotes, если он всё ещё на commit_id, возвращается на первого родителя (у корня - удаляется).
+    /// Объект коммита не удаляется.
+    fn discard_commit(&self, commit_id: &NodeId, parents: &[NodeId], remotes: &[RemoteRef]) -> Result<(), Box<dyn Error>>;
+
+    /// Родители коммита так, как их записал git, в том же порядке. None - коммита нет ни в одном контексте.
+    fn commit_parents(&self, id: &NodeId) -> Result<Option<Vec<NodeId>>, Box<dyn Error>>;
+
+    /// Проверяет подпись коммита ноды в том контексте, где он есть. Ошибка - коммита нет ни в одном.
+    fn verify_signature(&self, id: &NodeId) -> Result<SignatureStatus, Box<dyn Error>>;
+
+    /// Вершины, которые ремоут гарантированно уже имеет (его tracking-ссылки refs/remotes/<name>/*)
+    fn remote_tips(&self, remote_name: &str) -> Result<Vec<NodeId>, Box<dyn Error>>;
+
+    /// Ставит все target_refs на ремоуте (ветку и, например, refs/tags/<name>) на local_tip_id одним push.
+    /// skip_hooks - не запускать pre-push хук контекста, как git push --no-verify.
     fn push_update_ref(
         &self,
         remote: &RemoteRef,
         local_tip_id: &NodeId,
-        remote_target_ref: &str
+        remote_target_refs: &[String],
+        skip_hooks: bool,
+        progress: Option<&dyn Fn(Progress)>,
     ) -> Result<(), Box<dyn Error>>;
 
-    // это тоже должен бы проверять сам RepoBackend...
-    fn is_repo_empty(&self) -> Result<bool, Box<dyn Error>>; // костыль порожденный необходимостью иметь че-нибудь в гит для коммита
+    /// В активном контексте ещё нет ни одного коммита (HEAD не указывает никуда)
+    fn is_repo_empty(&self) -> Result<bool, Box<dyn Error>>;
+
+    /// Контекст, из которого ноду можно развернуть: инициализированный, с её коммитом и деревом.
+    /// Ничего не меняет, это проверка перед checkout_node.
+    fn checkout_context(&self, node: &Node) -> Result<String, Box<dyn Error>>;
+
+    /// Переключает .git на context (из checkout_context) и разворачивает дерево ноды
+    fn checkout_node(&self, node: &Node, context: &str) -> Result<(), Box<dyn Error>>;
 
-    fn checkout_node(&self, node: &Node) -> Result<(), Box<dyn Error>>;
+    fn switch_context(&self, name: &str) -> Result<(), Box<dyn Error>>;
+
+    /// Бэкенд, закреплённый за контекстом name: все команды идут прямо в него (`--git-dir .git_<name>`),
+    /// .git не переключается. Такие хендлы для разных контекстов можно гонять параллельно,
+    /// не воюя за один .git. switch_context и checkout_node у закреплённого хендла - ошибка.
+    fn with_context(&self, name: &str) -> Result<Box<dyn RepoBackend + Send>, Box<dyn Error>>;
+
+    /// Вся история активного контекста (все ветки). Родители идут раньше детей.
+    fn list_history(&self) -> Result<Vec<HistoryEntry>, Box<dyn Error>>;
+
+    /// Имена всех инициализированных контекстов, отсортированные
+    fn list_contexts(&self) -> Result<Vec<String>, Box<dyn Error>>;
+
+    /// Контекст, на который сейчас указывает .git (None, если ссылки нет или она битая)
+    fn active_context(&self) -> Result<Option<String>, Box<dyn Error>>;
 }
 
 pub trait GraphOps {
     fn get_node(&self, id: &NodeId) -> Result<Node, Box<dyn Error>>;
+
+    /// Последние вершины, запушенные на ремоут, как их запомнил сам граф (без tracking-ссылок git)
+    fn recorded_remote_tips(&self, remote_name: &str) -> Result<Vec<NodeId>, Box<dyn Error>>;
+
+    /// Нода, чей коммит - commit (id ноды не обязательно его хеш, см. ids). None - такой ноды нет
+    fn node_for_commit(&self, commit: &NodeId) -> Result<Option<NodeId>, Box<dyn Error>>;
 }
\ No newline at end of file
@@ -1,14 +1,29 @@
+use std::collections::{HashMap, HashSet};
 use std::error::Error;
-use crate::version_graph::VersionGraph;
-use crate::backend::{RepoBackend, GraphOps}; 
-use crate::push_manager::PushManager;
-use crate::types::{NodeId, Author, RemoteRef};
+use chrono::{DateTime, Utc};
+use crate::version_graph::{AddOptions, InheritPolicy, TreeSource, VersionGraph};
+use crate::backend::{RepoBackend, GraphOps, Progress, SignatureStatus};
+use crate::push_manager::{known_remote_tips, DenyRule, PushError, PushManager, PushOutcome, RetryPolicy};
+use crate::types::{remote_name_key, validate_remote_url, Node, NodeId, Author, RemoteRef};
 use crate::plugins::{PluginManager};
+use crate::stats::StatsPlugin;
+use crate::log::{self, LogEntry, LogFilter};
+use crate::storage::GraphStorage;
+use crate::ids::IdStrategy;
+use crate::messages;
+
+const URL_REQUIRED: &str = "URL required for adding remote: pass --url or declare it with `gpp config remote.<name>.url <url>`";
 
 #[derive(Debug)]
 pub enum CmdResult {
+    /// add: id новой ноды. Печатается строкой messages::node_created, которую разбирают скрипты
+    NodeCreated(NodeId),
+    /// push: что обновилось на ремоуте. Кроме статуса печатаются строки messages::pushed_ref для CI
+    Pushed { remote: String, outcome: PushOutcome },
     Success(String),
     Output(String),
+    /// Результат log: форматирование остаётся вызывающему
+    Log(Vec<LogEntry>),
     None,
 }
 
@@ -18,24 +33,96 @@ pub enum Command {
     Add {
         message: String,
         author: Author,
+        co_authors: Vec<Author>,
         parents: Vec<NodeId>,
         target_remotes: Option<Vec<String>>,
+        /// Только эти пути из рабочей директории, пусто - все
+        paths: Vec<String>,
+        /// Дата ноды и коммита, None - сейчас
+        date: Option<DateTime<Utc>>,
+        /// --no-verify: не запускать хуки коммита
+        skip_hooks: bool,
+    },
+    Log {
+        filter: LogFilter,
+        graph: bool,
+        /// Посчитать для каждой ноды DiffStat относительно первого родителя
+        stat: bool,
+        /// Только история этой ноды (HEAD), как git log. None - весь лес от всех корней (--all)
+        from: Option<NodeId>,
     },
-    Log,
     Checkout {
         node: NodeId,
+        /// Текущий HEAD: рабочая директория сверяется с ним перед переключением
+        head: Option<NodeId>,
     },
+    SwitchContext {
+        name: String,
+    },
+    ListContexts,
     ChangeRemote {
         remote: String,
         url: Option<String>,
         node: Option<NodeId>,
         remove: bool,
+        recursive: bool,
+        /// Только показать, какие ноды получат или потеряют право, graph.json не трогать
+        dry_run: bool,
+        /// Снять право без recursive, даже если у потомков оно осталось (их push на этот ремоут сломается)
+        force: bool,
     },
     Push {
         remote_name: String,
-        remote_url: String,
+        /// None - URL, записанный в ноде для этого ремоута (chrm --url), иначе push_url_template
+        remote_url: Option<String>,
         node: Option<NodeId>,
+        /// Пушится нода, на которой висит тег, и сам тег (refs/tags/<name>)
+        tag: Option<String>,
         dry_run: bool,
+        /// --no-verify: не запускать pre-push хук
+        skip_hooks: bool,
+    },
+    Tag {
+        name: String,
+        node: NodeId,
+        /// Аннотация, ложится в meta["message"] тега
+        message: Option<String>,
+    },
+    ListTags {
+        /// По времени создания тега, иначе по имени
+        by_date: bool,
+    },
+    MergeBase {
+        a: NodeId,
+        b: NodeId,
+        all: bool,
+    },
+    /// Сколько нод node впереди ремоута и сколько позади (по его tracking-ссылкам)
+    AheadBehind {
+        remote: String,
+        node: NodeId,
+    },
+    /// Ноды с тегом и/или метаданными (все условия сразу)
+    Find {
+        tag: Option<String>,
+        /// (ключ, значение); значение None - достаточно, чтобы ключ был
+        meta: Vec<(String, Option<String>)>,
+    },
+    /// То, что видно на ремоуте: ноды по id, как в секции nodes в graph.json
+    Export {
+        remote: String,
+    },
+    /// Сверка рёбер графа с родителями коммитов в git
+    Verify,
+    /// Подписи коммитов всех нод или только тех, что уходят на remote
+    VerifySignatures {
+        remote: Option<String>,
+    },
+    /// Группы нод с одинаковым деревом (только отчёт, граф не меняется)
+    FindDuplicateTrees,
+    /// Сводка по графу, исполняется встроенным плагином "stats"
+    Stats {
+        json: bool,
     },
     Custom {
         name: String,
@@ -47,122 +134,605 @@ pub trait CommandHandler: Send + Sync {
     fn execute(&self, args: &[String], graph: &mut VersionGraph) -> Result<CmdResult, Box<dyn Error>>;
 }
 
+/// Вся сборка: бэкенд один, им владеет граф, dispatcher (push, контексты) берёт его оттуда же.
+/// CLI отдаёт сюда GitRepo, тесты - MockRepoBackend.
+pub fn build_dispatcher(backend: impl RepoBackend + 'static, storage: Box<dyn GraphStorage>) -> CommandDispatcher {
+    CommandDispatcher::new(VersionGraph::new(storage, Box::new(backend)))
+}
+
 pub struct CommandDispatcher {
     graph: VersionGraph,
-    aux_backend: Box<dyn RepoBackend>,
     plugin_mgr: PluginManager,
+    progress: Option<Box<dyn Fn(Progress)>>,
+    /// Запасной URL для push, "{remote}" заменяется на имя ремоута
+    push_url_template: Option<String>,
+    push_retry: RetryPolicy,
+    /// Ремоуты из `gpp init` (remote.<name>.url в .gitpp/config), по remote_name_key. Контекст у них
+    /// может быть ещё не создан, url может быть пустым.
+    declared_remotes: HashMap<String, RemoteRef>,
+    /// remote.<name>.deny: что нельзя пушить на ремоут, по remote_name_key
+    push_deny: HashMap<String, Vec<DenyRule>>,
     // registry: HashMap<String, Box<dyn CommandHandler>>,
 }
 
 impl CommandDispatcher {
-    pub fn new(
-        graph: VersionGraph,
-        aux_backend: Box<dyn RepoBackend>,
-    ) -> Self {
+    pub fn new(graph: VersionGraph) -> Self {
+        let mut plugin_mgr = PluginManager::new();
+        plugin_mgr.register(Box::new(StatsPlugin));
         Self {
             graph,
-            aux_backend,
-            plugin_mgr: PluginManager::new(),
+            plugin_mgr,
+            progress: None,
+            push_url_template: None,
+            push_retry: RetryPolicy::default(),
+            declared_remotes: HashMap::new(),
+            push_deny: HashMap::new(),
         }
     }
 
+    pub fn set_inherit_policy(&mut self, policy: InheritPolicy) {
+        self.graph.set_inherit_policy(policy);
+    }
+
+    pub fn set_tree_source(&mut self, source: TreeSource) {
+        self.graph.set_tree_source(source);
+    }
+
+    pub fn set_id_strategy(&mut self, strategy: Box<dyn IdStrategy>) {
+        self.graph.set_id_strategy(strategy);
+    }
+
+    pub fn set_push_url_template(&mut self, template: Option<String>) {
+        self.push_url_template = template;
+    }
+
+    pub fn set_push_retry(&mut self, retry: RetryPolicy) {
+        self.push_retry = retry;
+    }
+
+    /// Ремоут, объявленный в конфиге: его URL подставляется в chrm и push без --url
+    pub fn declare_remote(&mut self, remote: RemoteRef) {
+        self.declared_remotes.insert(remote_name_key(&remote.name), remote);
+    }
+
+    /// Ноды, подходящие под rules, на remote не уходят, даже если им этот ремоут разрешён
+    pub fn deny_on_push(&mut self, remote: &str, rules: Vec<DenyRule>) {
+        self.push_deny.entry(remote_name_key(remote)).or_default().extend(rules);
+    }
+
+    /// URL объявленного ремоута, если он задан
+    fn declared_url(&self, remote_name: &str) -> Option<String> {
+        self.declared_remotes
+            .get(&remote_name_key(remote_name))
+            .map(|r| r.url.clone())
+            .filter(|url| !url.is_empty())
+    }
+
+    /// Куда слать прогресс долгих операций (push). Без него они идут молча.
+    pub fn on_progress(&mut self, report: impl Fn(Progress) + 'static) {
+        self.progress = Some(Box::new(report));
+    }
+
+    /// Граф только на чтение: вызывающему нужно, например, разрешить ревизии до сборки команды
+    pub fn graph(&self) -> &VersionGraph {
+        &self.graph
+    }
+
     pub fn plugins(&mut self) -> &mut PluginManager {
         &mut self.plugin_mgr
     }
 
+    /// URL для push без --url: тот, что chrm записал в ноду, иначе объявленный в init, иначе из шаблона.
+    /// Хост не угадываем.
+    fn push_url(&self, node: &NodeId, remote_name: &str) -> Result<String, Box<dyn Error>> {
+        let key = remote_name_key(remote_name);
+        let recorded = self.graph.get_node(node)?
+            .remotes
+            .into_iter()
+            .find(|r| remote_name_key(&r.name) == key);
+        if recorded.as_ref().is_none_or(|r| r.url.is_empty()) {
+            if let Some(url) = self.declared_url(remote_name) {
+                return Ok(url);
+            }
+        }
+        match (recorded, &self.push_url_template) {
+            (Some(remote), _) if !remote.url.is_empty() => Ok(remote.url),
+            (_, Some(template)) => Ok(template.replace("{remote}", remote_name)),
+            (None, None) => Err(format!(
+                "Node {} does not allow pushing to remote '{}', so there is no URL to take from it. \
+                Grant it with `gpp chrm --node {} --remote {} --url <url>`",
+                node.short(), remote_name, node.0, remote_name
+            ).into()),
+            (Some(_), None) => Err(format!(
+                "No URL for remote '{}': pass --url, record it with `gpp chrm --remote {} --url <url>` \
+                or set `gpp config push.url-template <url with {{remote}}>`",
+                remote_name, remote_name
+            ).into()),
+        }
+    }
+
     pub fn dispatch(&mut self, cmd: Command) -> Result<CmdResult, Box<dyn Error>> {
         match cmd {
-            Command::Add { message, author, parents, target_remotes } => {
-                let node_id = self.graph.add_node(parents, author, message, target_remotes)?;
-                Ok(CmdResult::Success(format!("Node created: {}", node_id.0)))
+            // хуков коммита пока нет: create_commit идёт через commit-tree, а он их не вызывает.
+            // skip_hooks уже в DTO, чтобы HookRunner подхватил его без изменений в CLI
+            Command::Add { message, author, co_authors, parents, target_remotes, paths, date, skip_hooks: _ } => {
+                let options = AddOptions { paths, date };
+                let node_id = self.graph.add_node_with(parents, author, co_authors, message, target_remotes, options)?;
+                Ok(CmdResult::NodeCreated(node_id))
             }
 
-            Command::Log => {
-                let mut output = String::new();
-
+            Command::Log { filter, graph, stat, from } => {
                 let mut queue = std::collections::VecDeque::new();
                 let mut visited = std::collections::HashSet::new();
 
                 let roots = self.graph.list_roots()?;
                 if roots.is_empty() {
-                    return Ok(CmdResult::Output("History is empty.".to_string()));
+                    return Ok(CmdResult::Output(messages::history_empty()));
                 }
 
-                for r in roots {
-                    queue.push_back(r);
-                }
+                // обход тот же, что и для всего леса, просто без нод вне истории from
+                let scope: Option<HashSet<NodeId>> = match &from {
+                    Some(head) => Some(self.graph.ancestors(head)?.into_iter().collect()),
+                    None => None,
+                };
+                let in_scope = |id: &NodeId| scope.as_ref().is_none_or(|s| s.contains(id));
 
+                queue.extend(roots.into_iter().filter(|r| in_scope(r)));
+
+                let mut nodes = Vec::new();
                 while let Some(current_id) = queue.pop_front() {
                     if !visited.insert(current_id.clone()) {
                         continue;
                     }
-
                     let node = self.graph.get_node(&current_id)?;
+                    queue.extend(node.children.iter().filter(|c| in_scope(c)).cloned());
+                    nodes.push(node);
+                }
+
+                let mut entries = if graph {
+                    log::graph_entries(log::topo_order(nodes), |n| filter.matches(n))
+                } else {
+                    nodes
+                        .into_iter()
+                        .filter(|n| filter.matches(n))
+                        .map(|node| LogEntry { node, graph: None, stat: None })
+                        .collect()
+                };
+                if stat {
+                    for entry in &mut entries {
+                        entry.stat = Some(self.graph.diff_stat(&entry.node)?);
+                    }
+                }
+                Ok(CmdResult::Log(entries))
+            }
+
+            Command::Checkout { node, head } => {
+                self.graph.checkout(&node, head.as_ref())?;
+                Ok(CmdResult::Success(messages::head_moved(&node)))
+            }
+
+            Command::SwitchContext { name } => {
+                // контекст называется так же, как ремоут в графе, а имена ремоутов регистронезависимы
+                let name = self.graph.canonical_remote_name(&name)?.unwrap_or(name);
+                self.graph.backend().switch_context(&name)?;
+                Ok(CmdResult::Success(messages::context_switched(&name)))
+            }
+
+            Command::ListContexts => {
+                let contexts = self.graph.backend().list_contexts()?;
+                if contexts.is_empty() {
+                    return Ok(CmdResult::Output(messages::no_contexts()));
+                }
+                let active = self.graph.backend().active_context()?;
+
+                let mut lines: Vec<String> = contexts
+                    .iter()
+                    .map(|name| messages::context_line(name, active.as_deref() == Some(name.as_str())))
+                    .collect();
+                // объявленные в init, но контекст им создаст только первый push или переключение
+                let initialized: HashSet<String> = contexts.iter().map(|c| remote_name_key(c)).collect();
+                let mut pending: Vec<&String> = self.declared_remotes.values()
+                    .map(|r| &r.name)
+                    .filter(|name| !initialized.contains(&remote_name_key(name)))
+                    .collect();
+                pending.sort();
+                lines.extend(pending.into_iter().map(|name| messages::context_not_initialized(name)));
+                if active.is_none() {
+                    lines.push(messages::context_link_broken());
+                }
+                Ok(CmdResult::Output(lines.join("\n")))
+            }
 
-                    output.push_str(&format!("Commit:  {}\n", current_id.0));
-                    output.push_str(&format!("Message: {}\n", node.message));
-                    output.push_str(&format!("Remotes: {:?}\n", node.remotes));
+            Command::ChangeRemote { remote, url, node, remove, recursive, dry_run, force } => {
+                let target_node = node.ok_or("Node ID required for chrm")?;
 
-                    if !node.parents.is_empty() {
-                        let parents_str: Vec<String> = node.parents.iter().map(|p| p.0.clone()).collect();
-                        output.push_str(&format!("Parents: {:?}\n", parents_str));
+                if dry_run {
+                    let changes = if remove {
+                        self.graph.preview_remove_remote_permission(&target_node, &remote, recursive)?
+                    } else {
+                        let u = url.or_else(|| self.declared_url(&remote)).ok_or(URL_REQUIRED)?;
+                        validate_remote_url(&u)?;
+                        self.graph.preview_add_remote_permission(&target_node, &RemoteRef::new(remote.clone(), u)?, recursive)?
+                    };
+                    if changes.is_empty() {
+                        return Ok(CmdResult::Output(messages::permissions_unchanged(&remote)));
                     }
-                    if !node.children.is_empty() {
-                        let children_str: Vec<String> = node.children.iter().map(|c| c.0.clone()).collect();
-                        output.push_str(&format!("Children: {:?}\n", children_str));
+                    let mut lines = vec![messages::permissions_preview(changes.len(), remove, &remote)];
+                    for change in changes {
+                        let node = self.graph.get_node(&change.node)?;
+                        lines.push(format!(
+                            "  {} {}  [{}] -> [{}]",
+                            change.node.short(),
+                            node.message.lines().next().unwrap_or_default(),
+                            change.before.join(", "),
+                            change.after.join(", ")
+                        ));
                     }
+                    return Ok(CmdResult::Output(lines.join("\n")));
+                }
 
-                    output.push_str("------------------------------\n");
+                if remove {
+                    if !recursive && !force {
+                        let stranded = self.graph.descendants_with_remote(&target_node, &remote)?;
+                        if !stranded.is_empty() {
+                            let mut shown: Vec<&str> = stranded.iter().take(5).map(|id| id.short()).collect();
+                            if stranded.len() > shown.len() {
+                                shown.push("...");
+                            }
+                            return Err(format!(
+                                "{} descendant node(s) of {} still allow remote '{}' ({}); without it on {} they can no longer be pushed there.\n\
+                                hint: use --recursive to remove it from the descendants too, or --force to remove it from this node only",
+                                stranded.len(),
+                                target_node.short(),
+                                remote,
+                                shown.join(", "),
+                                target_node.short()
+                            ).into());
+                        }
+                    }
+                    let count = self.graph.remove_remote_permission(&target_node, &remote, recursive)?;
+                    Ok(CmdResult::Success(messages::permission_removed(&remote, count)))
+                } else {
+                    let u = url.or_else(|| self.declared_url(&remote)).ok_or(URL_REQUIRED)?;
+                    validate_remote_url(&u)?;
+                    let r = RemoteRef::new(remote.clone(), u)?;
+                    let count = self.graph.add_remote_permission(&target_node, r, recursive)?;
+                    Ok(CmdResult::Success(messages::permission_added(&remote, count)))
+                }
+            }
 
-                    for child_id in node.children {
-                        queue.push_back(child_id);
+            Command::Push { remote_name, remote_url, node, tag, dry_run, skip_hooks } => {
+                let target_node = match &tag {
+                    Some(name) => self.graph.find_tag(name)?.ok_or_else(|| messages::tag_not_found(name))?,
+                    None => node.ok_or_else(messages::node_required_for_push)?,
+                };
+                let remote_name = self.graph.canonical_remote_name(&remote_name)?.unwrap_or(remote_name);
+                let remote_url = match remote_url {
+                    Some(url) => url,
+                    None => self.push_url(&target_node, &remote_name)?,
+                };
+                validate_remote_url(&remote_url)?;
+                let push_mgr = PushManager::new(&self.graph, self.graph.backend())
+                    .with_retry(self.push_retry)
+                    .with_skip_hooks(skip_hooks)
+                    .with_deny(self.push_deny.get(&remote_name_key(&remote_name)).cloned().unwrap_or_default());
+                let remote_ref = RemoteRef::new(remote_name, remote_url)?;
+
+                match push_mgr.push(&target_node, &remote_ref, tag.as_deref(), dry_run, self.progress.as_deref()) {
+                    // dry-run ничего не отправил: результат - только план
+                    Ok(Some(outcome)) if dry_run => Ok(CmdResult::Output(outcome.plan.unwrap_or_default())),
+                    Ok(Some(outcome)) => {
+                        self.graph.record_remote_tip(&target_node, &remote_ref.name)?;
+                        Ok(CmdResult::Pushed { remote: remote_ref.name, outcome })
                     }
+                    Ok(None) => Ok(CmdResult::Success(messages::push_up_to_date())),
+                    Err(e) => match e.downcast_ref::<PushError>() {
+                        Some(PushError::NonFastForward { remote }) => Err(format!(
+                            "{}\nhint: someone else pushed to '{}'. Bring their commits into context '{}' \
+                            (git --git-dir .git_{} fetch <url> main), merge or rebase your nodes on top of them, then push again",
+                            e, remote, remote, remote
+                        ).into()),
+                        _ => Err(e),
+                    },
                 }
+            }
 
-                Ok(CmdResult::Output(output))
+            Command::Tag { name, node, message } => {
+                self.graph.tag_node(&node, &name, message.as_deref())?;
+                Ok(CmdResult::Success(messages::tagged(&node, &name)))
             }
 
-            Command::Checkout { node } => {
-                self.graph.checkout(&node)?;
-                Ok(CmdResult::Success(format!("HEAD is now at {}", node.0)))
+            Command::ListTags { by_date } => {
+                let mut tags = self.graph.list_tags()?;
+                if tags.is_empty() {
+                    return Ok(CmdResult::Output(messages::no_tags()));
+                }
+                if by_date {
+                    // sort_by стабильный: теги с одинаковым временем остаются по имени
+                    tags.sort_by_key(|(_, tag)| tag.created_at);
+                }
+
+                let width = tags.iter().map(|(_, tag)| tag.name.len()).max().unwrap_or(0);
+                let lines: Vec<String> = tags
+                    .iter()
+                    .map(|(id, tag)| {
+                        let date = tag.created_at.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M");
+                        let line = format!("{:<width$}  {}  {}", tag.name, id.short(), date, width = width);
+                        match tag.meta.get("message").and_then(|m| m.lines().next()) {
+                            Some(message) => format!("{}  {}", line, message),
+                            None => line,
+                        }
+                    })
+                    .collect();
+                Ok(CmdResult::Output(lines.join("\n")))
             }
 
-            Command::ChangeRemote { remote, url, node, remove } => {
-                let target_node = node.ok_or("Node ID required for chrm")?;
+            Command::AheadBehind { remote, node } => {
+                let remote = self.graph.canonical_remote_name(&remote)?.unwrap_or(remote);
+                // как у push: без tracking-ссылок верим вершине, которую запомнил граф
+                let tips = known_remote_tips(&self.graph, self.graph.backend(), &remote)?;
+                let (ahead, behind) = self.graph.ahead_behind(&node, &tips)?;
+                Ok(CmdResult::Output(messages::remote_divergence(&remote, &node, ahead, behind)))
+            }
 
-                if remove {
-                    self.graph.remove_remote_permission(&target_node, &remote)?;
-                    Ok(CmdResult::Success(format!("Removed permission for remote '{}'", remote)))
-                } else {
-                    let u = url.ok_or("URL required for adding remote")?;
-                    let r = RemoteRef { name: remote.clone(), url: u, specs: Default::default() };
-                    self.graph.add_remote_permission(&target_node, r)?;
-                    Ok(CmdResult::Success(format!("Added permission for remote '{}'", remote)))
+            Command::MergeBase { a, b, all } => {
+                let mut bases = self.graph.merge_bases(&a, &b)?;
+                if bases.is_empty() {
+                    return Err(messages::no_common_ancestor(&a, &b).into());
+                }
+                if !all {
+                    bases.truncate(1);
                 }
+                let lines: Vec<String> = bases.into_iter().map(|id| id.0).collect();
+                Ok(CmdResult::Output(lines.join("\n")))
             }
 
-            Command::Push { remote_name, remote_url, node, dry_run } => {
-                let target_node = node.ok_or("Node ID required for push")?;
-                let push_mgr = PushManager::new(&self.graph, self.aux_backend.as_ref());
-                let remote_ref = RemoteRef {
-                    name: remote_name,
-                    url: remote_url,
-                    specs: Default::default(),
+            Command::Find { tag, meta } => {
+                let matches = |node: &Node| {
+                    tag.as_ref().is_none_or(|t| node.tags.contains_key(t))
+                        && meta.iter().all(|(key, value)| match (node.metadata.get(key), value) {
+                            (Some(actual), Some(expected)) => actual == expected,
+                            (found, None) => found.is_some(),
+                            (None, Some(_)) => false,
+                        })
                 };
+                let mut lines = Vec::new();
+                for node in self.graph.find_nodes(matches)? {
+                    let node = node?;
+                    lines.push(format!("{} {}", node.id.short(), node.message.lines().next().unwrap_or_default()));
+                }
+                if lines.is_empty() {
+                    return Ok(CmdResult::Output(messages::no_matching_nodes()));
+                }
+                Ok(CmdResult::Output(lines.join("\n")))
+            }
+
+            Command::Export { remote } => {
+                let nodes = self.graph.remote_subgraph(&remote)?;
+                // как секция nodes в graph.json: объект по id
+                let by_id: std::collections::BTreeMap<&NodeId, &Node> = nodes.iter().map(|n| (&n.id, n)).collect();
+                Ok(CmdResult::Output(serde_json::to_string_pretty(&by_id)?))
+            }
+
+            Command::Verify => {
+                let (mismatches, checked) = self.graph.verify_parents()?;
+                let conflicts = self.graph.remote_conflicts()?;
+                if mismatches.is_empty() && conflicts.is_empty() {
+                    return Ok(CmdResult::Output(messages::graph_matches_git(checked)));
+                }
+                let short = |ids: &[NodeId]| ids.iter().map(|id| id.short()).collect::<Vec<_>>().join(", ");
+                let mut lines = Vec::new();
+                if !mismatches.is_empty() {
+                    lines.push(messages::graph_disagrees(mismatches.len(), checked));
+                }
+                for m in &mismatches {
+                    lines.push(match &m.git {
+                        Some(git) => messages::parents_differ(&m.node, &short(&m.graph), &short(git)),
+                        None => messages::no_such_commit(&m.node),
+                    });
+                }
+                if !conflicts.is_empty() {
+                    lines.push(messages::remotes_recorded_twice(conflicts.len()));
+                }
+                for c in &conflicts {
+                    lines.push(messages::remote_urls(&c.node, &c.remote, &c.urls.join(", ")));
+                }
+                if let Some(c) = conflicts.first() {
+                    lines.push(messages::one_url_hint(&c.node, &c.remote));
+                }
+                Err(lines.join("\n").into())
+            }
+
+            Command::VerifySignatures { remote } => {
+                let (problems, checked) = self.graph.verify_signatures(remote.as_deref())?;
+                if problems.is_empty() {
+                    return Ok(CmdResult::Output(messages::signatures_ok(checked)));
+                }
+                let mut lines = vec![messages::unsigned_nodes(problems.len(), checked)];
+                for problem in &problems {
+                    let node = self.graph.get_node(&problem.node)?;
+                    let reason = match &problem.status {
+                        SignatureStatus::Bad(reason) => reason.clone(),
+                        _ => messages::unsigned(),
+                    };
+                    lines.push(format!("  {} {}  {}", problem.node.short(), node.message.lines().next().unwrap_or_default(), reason));
+                }
+                Err(lines.join("\n").into())
+            }
 
-                match push_mgr.push(&target_node, &remote_ref, dry_run)? {
-                    true => Ok(CmdResult::Success("Push completed successfully".into())),
-                    false => Ok(CmdResult::Success("Nothing to push (up to date)".into())),
+            Command::FindDuplicateTrees => {
+                let mut groups: Vec<(String, Vec<NodeId>)> =
+                    self.graph.nodes_by_tree()?.into_iter().filter(|(_, ids)| ids.len() > 1).collect();
+                if groups.is_empty() {
+                    return Ok(CmdResult::Output(messages::no_duplicate_trees()));
+                }
+                groups.sort();
+                let mut lines = Vec::new();
+                for (tree, ids) in groups {
+                    lines.push(messages::duplicate_tree(&tree, ids.len()));
+                    for id in ids {
+                        let node = self.graph.get_node(&id)?;
+                        lines.push(format!("  {} {}", id.short(), node.message.lines().next().unwrap_or_default()));
+                    }
                 }
+                Ok(CmdResult::Output(lines.join("\n")))
+            }
+
+            Command::Stats { json } => {
+                let args = if json { vec!["--json".to_string()] } else { Vec::new() };
+                self.dispatch(Command::Custom { name: "stats".into(), args })
             }
 
             Command::Custom { name, args } => {
                 if let Some(handler) = self.plugin_mgr.get_handler(&name) {
                     handler.execute(&args, &mut self.graph)
                 } else {
-                    Err(format!("Unknown command: {}", name).into())
+                    Err(messages::unknown_command(&name).into())
                 }
             }
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{mock_dispatcher, test_author};
+
+    fn add(dispatcher: &mut CommandDispatcher, parents: Vec<NodeId>, remotes: Option<&[&str]>) -> Result<NodeId, Box<dyn Error>> {
+        let cmd = Command::Add {
+            message: "node".into(),
+            author: test_author(),
+            co_authors: vec![],
+            parents,
+            target_remotes: remotes.map(|r| r.iter().map(|s| s.to_string()).collect()),
+            paths: vec![],
+            date: None,
+            skip_hooks: false,
+        };
+        match dispatcher.dispatch(cmd)? {
+            CmdResult::NodeCreated(id) => Ok(id),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    fn push(dispatcher: &mut CommandDispatcher, node: &NodeId, remote: &str) -> Result<CmdResult, Box<dyn Error>> {
+        let cmd = Command::Push {
+            remote_name: remote.into(),
+            remote_url: None,
+            node: Some(node.clone()),
+            tag: None,
+            dry_run: false,
+            skip_hooks: false,
+        };
+        dispatcher.dispatch(cmd)
+    }
+
+    #[test]
+    fn child_inherits_remotes_and_cannot_add_new_ones() {
+        let (mut dispatcher, backend) = mock_dispatcher();
+        backend.set_worktree("1");
+        let root = add(&mut dispatcher, vec![], Some(&["origin", "work"])).unwrap();
+        backend.set_worktree("2");
+        let child = add(&mut dispatcher, vec![root.clone()], None).unwrap();
+
+        let mut remotes: Vec<String> = dispatcher.graph().get_node(&child).unwrap().remotes.into_iter().map(|r| r.name).collect();
+        remotes.sort();
+        assert_eq!(remotes, ["origin", "work"]);
+
+        backend.set_worktree("3");
+        let err = add(&mut dispatcher, vec![root], Some(&["mirror"])).unwrap_err();
+        assert!(err.to_string().contains("not present in parent nodes"), "{}", err);
+    }
+
+    #[test]
+    fn push_goes_through_permissions_and_remembers_the_tip() {
+        let (mut dispatcher, backend) = mock_dispatcher();
+        backend.set_worktree("1");
+        let root = add(&mut dispatcher, vec![], None).unwrap();
+        let grant = Command::ChangeRemote {
+            remote: "work".into(),
+            url: Some("file:///work".into()),
+            node: Some(root.clone()),
+            remove: false,
+            recursive: false,
+            dry_run: false,
+            force: false,
+        };
+        dispatcher.dispatch(grant).unwrap();
+        backend.set_worktree("2");
+        let private = add(&mut dispatcher, vec![root.clone()], Some(&["origin"])).unwrap();
+
+        let err = push(&mut dispatcher, &private, "work").unwrap_err();
+        assert!(err.to_string().contains("does not allow pushing"), "{}", err);
+
+        match push(&mut dispatcher, &root, "work").unwrap() {
+            CmdResult::Pushed { remote, outcome } => {
+                assert_eq!(remote, "work");
+                assert_eq!(outcome.refs, ["refs/heads/main"]);
+                assert_eq!(outcome.tip, root);
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert!(matches!(push(&mut dispatcher, &root, "Work").unwrap(), CmdResult::Success(msg) if msg == "Nothing to push (up to date)"));
+        assert_eq!(backend.state().pushes.len(), 1);
+    }
+
+    #[test]
+    fn removing_remote_from_ancestor_requires_recursive_or_force() {
+        let (mut dispatcher, backend) = mock_dispatcher();
+        backend.set_worktree("1");
+        let root = add(&mut dispatcher, vec![], Some(&["origin", "work"])).unwrap();
+        backend.set_worktree("2");
+        let child = add(&mut dispatcher, vec![root.clone()], None).unwrap();
+        let remove = |recursive, force| Command::ChangeRemote {
+            remote: "Work".into(),
+            url: None,
+            node: Some(root.clone()),
+            remove: true,
+            recursive,
+            dry_run: false,
+            force,
+        };
+
+        let err = dispatcher.dispatch(remove(false, false)).unwrap_err().to_string();
+        assert!(err.starts_with(&format!("1 descendant node(s) of {} still allow remote 'Work' ({})", root.short(), child.short())), "{}", err);
+        assert!(err.contains("--recursive") && err.contains("--force"), "{}", err);
+        assert_eq!(dispatcher.graph().get_node(&root).unwrap().remotes.len(), 2);
+
+        dispatcher.dispatch(remove(false, true)).unwrap();
+        assert_eq!(dispatcher.graph().get_node(&root).unwrap().remotes.len(), 1);
+        assert_eq!(dispatcher.graph().get_node(&child).unwrap().remotes.len(), 2);
+    }
+
+    #[test]
+    fn chrm_dry_run_lists_changes_without_persisting() {
+        let (mut dispatcher, backend) = mock_dispatcher();
+        backend.set_worktree("1");
+        let root = add(&mut dispatcher, vec![], Some(&["origin", "work"])).unwrap();
+        backend.set_worktree("2");
+        let child = add(&mut dispatcher, vec![root.clone()], None).unwrap();
+        backend.set_worktree("3");
+        let private = add(&mut dispatcher, vec![child.clone()], Some(&["origin"])).unwrap();
+
+        let preview = Command::ChangeRemote {
+            remote: "work".into(),
+            url: None,
+            node: Some(root.clone()),
+            remove: true,
+            recursive: true,
+            dry_run: true,
+            force: false,
+        };
+        let output = match dispatcher.dispatch(preview).unwrap() {
+            CmdResult::Output(text) => text,
+            other => panic!("unexpected result: {:?}", other),
+        };
+        // у private права на work и так нет - её в списке быть не должно
+        assert!(output.starts_with("2 node(s) would lose permission for remote 'work':"), "{}", output);
+        assert!(output.contains(&format!("{} node  [origin, work] -> [origin]", child.short())), "{}", output);
+        assert!(!output.contains(private.short()), "{}", output);
+
+        assert_eq!(dispatcher.graph().get_node(&child).unwrap().remotes.len(), 2);
+    }
+}
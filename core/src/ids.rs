@@ -0,0 +1,76 @@
+// Откуда берётся id новой ноды. По умолчанию это хеш git-коммита, но граф от git не зависит:
+// бэкенду, который не выдаёт хешей (или графу без git вовсе), id можно считать по содержимому ноды.
+use std::error::Error;
+
+use chrono::{DateTime, Utc};
+use sha1::{Digest, Sha1};
+
+use crate::types::{Author, NodeId};
+
+/// Всё, из чего состоит нода на момент выдачи id. commit - что вернул бэкенд, если он создавал коммит.
+#[derive(Debug, Clone, Copy)]
+pub struct NodeContent<'a> {
+    pub tree_id: &'a str,
+    pub parents: &'a [NodeId],
+    pub author: &'a Author,
+    pub message: &'a str,
+    pub created_at: DateTime<Utc>,
+    pub commit: Option<&'a NodeId>,
+}
+
+pub trait IdStrategy {
+    fn generate(&self, content: &NodeContent) -> Result<NodeId, Box<dyn Error>>;
+}
+
+/// id ноды = хеш её коммита (graph.ids = commit). Так было всегда, и так id совпадают с тем, что видит git.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CommitHashIds;
+
+impl IdStrategy for CommitHashIds {
+    fn generate(&self, content: &NodeContent) -> Result<NodeId, Box<dyn Error>> {
+        content.commit.cloned().ok_or_else(|| "The backend created no commit, so there is no hash to use as the node id".into())
+    }
+}
+
+/// id ноды = sha1 от дерева, родителей, автора, времени и сообщения (graph.ids = content).
+/// От бэкенда не зависит: у той же ноды тот же id, какая бы VCS ни хранила коммит.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ContentHashIds;
+
+impl IdStrategy for ContentHashIds {
+    fn generate(&self, content: &NodeContent) -> Result<NodeId, Box<dyn Error>> {
+        // свой заголовок, чтобы id никогда не совпал с хешем git-объекта
+        let mut text = format!("gpp-node\ntree {}\n", content.tree_id);
+        for parent in content.parents {
+            text.push_str(&format!("parent {}\n", parent.0));
+        }
+        text.push_str(&format!("author {} {}\n\n{}", content.author, content.created_at.timestamp(), content.message));
+        Ok(NodeId(format!("{:x}", Sha1::digest(text.as_bytes()))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn content_ids_depend_on_content_only() {
+        let author = Author { name: "a".into(), email: "a@b".into() };
+        let parents = [NodeId("p".into())];
+        let content = NodeContent {
+            tree_id: "t",
+            parents: &parents,
+            author: &author,
+            message: "m",
+            created_at: DateTime::parse_from_rfc3339("2024-01-01T00:00:00Z").unwrap().to_utc(),
+            commit: Some(&parents[0]),
+        };
+        let id = ContentHashIds.generate(&content).unwrap();
+        assert_eq!(id.0.len(), 40);
+        assert_eq!(ContentHashIds.generate(&NodeContent { commit: None, ..content }).unwrap(), id);
+        assert_ne!(ContentHashIds.generate(&NodeContent { message: "other", ..content }).unwrap(), id);
+
+        assert_eq!(CommitHashIds.generate(&content).unwrap(), parents[0]);
+        assert!(CommitHashIds.generate(&NodeContent { commit: None, ..content }).is_err());
+    }
+}
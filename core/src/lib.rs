@@ -1,13 +1,54 @@
 // core/src/lib.rs
+//! Движок графа версий Git++ без привязки к git и файлам.
+//!
+//! Поддерживаемый API - то, что реэкспортировано в корне крейта:
+//! - [`build_dispatcher`] / [`CommandDispatcher`]: точка входа для встраивания. Команды - это [`Command`],
+//!   результат - [`CmdResult`]. Свои команды добавляются через [`CommandHandler`] и `dispatcher.plugins()`.
+//! - [`Node`], [`NodeId`], [`Author`], [`RemoteRef`], [`Tag`]: данные графа.
+//! - [`GraphStorage`] и [`RepoBackend`]: что нужно реализовать, чтобы подставить своё хранилище и свою VCS.
+//! - [`VersionGraph`]: граф поверх них, если dispatcher не нужен.
+//! - [`IdStrategy`]: как новая нода получает id (хеш коммита или хеш содержимого).
+//!
+//! Готовый фасад над graph.json и git (`Repository`) живёт в gpp_cli: он собирает storage-file и
+//! backend-git, а core от них не зависит. Для них же (и для CLI) в корне есть то, что им нужно из внутренних
+//! модулей: [`migrate`] для хранилища, [`PushManager`] и [`PushError`] для бэкенда, [`LogFilter`] и [`RevContext`]
+//! для разбора аргументов, [`text`] - тексты статуса. Сами модули закрыты, их содержимое может меняться.
 pub mod types;
 pub mod storage;
 pub mod backend;
 pub mod version_graph;
-pub mod push_manager;
+pub mod ids;
+pub(crate) mod push_manager;
 pub mod dispatcher;
 pub mod plugins;
+pub(crate) mod log;
+pub(crate) mod migrations;
+pub(crate) mod revision;
+mod stats;
+pub(crate) mod messages;
+#[cfg(any(test, feature = "test-util"))]
+pub mod testing;
 
-pub use types::*;
-pub use backend::*;
-pub use version_graph::*;
-pub use dispatcher::{CommandDispatcher, Command, CmdResult, CommandHandler};
\ No newline at end of file
+pub use types::{Author, Node, NodeId, NodePayload, RemoteRef, Tag};
+pub use storage::{GraphStorage, StorageError, Transaction, TxHandle};
+pub use backend::{GraphOps, RepoBackend};
+pub use version_graph::VersionGraph;
+pub use ids::IdStrategy;
+pub use dispatcher::{build_dispatcher, CommandDispatcher, Command, CmdResult, CommandHandler};
+pub use log::{parse_date, GraphLines, LogEntry, LogFilter, TextMatch};
+pub use revision::RevContext;
+pub use push_manager::{DenyRule, PushError, PushManager, PushOutcome, RetryPolicy};
+pub use migrations::migrate;
+#[allow(deprecated)]
+pub use types::CommitId;
+
+/// Тексты статуса, которые печатают CLI и бэкенды. Язык и --quiet выбираются здесь же.
+pub mod text {
+    pub use crate::messages::{
+        clone_started, cloned, context_lacks_node, context_link_kind, context_setup, corrupt_copy_kept,
+        dangling_head, error_label, files_prompt, graph_healthy, graph_restored, gui_starting, head_cleared,
+        init_done, init_started, no_snapshots, node_created, push_completed, push_retrying, pushed_ref, quiet,
+        remote_declared, remote_head_set, repo_exists, set_lang, set_quiet, snapshot_created, snapshot_restored,
+        stash_restored, stash_saved, status, success_label, switching_context, warning_label, Lang,
+    };
+}
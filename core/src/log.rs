@@ -0,0 +1,438 @@
+// Фильтры для gpp log. Обход графа их не касается: отфильтрованные ноды просто не печатаются,
+// но через них по-прежнему идём к потомкам.
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use regex::{Regex, RegexBuilder};
+use serde::Serialize;
+
+use crate::backend::DiffStat;
+use crate::types::{remote_name_key, Node, NodeId};
+
+/// Одна нода в результате log. Как её показывать (цвета, --oneline, --json) решает CLI.
+#[derive(Debug, Clone, Serialize)]
+pub struct LogEntry {
+    pub node: Node,
+    /// Только для --graph
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub graph: Option<GraphLines>,
+    /// Только для --stat
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stat: Option<DiffStat>,
+}
+
+/// Псевдографика для одной ноды: соединительные строки перед ней и префикс её собственной строки
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct GraphLines {
+    pub connectors: Vec<String>,
+    pub prefix: String,
+}
+
+/// Шаблон поиска. По умолчанию - подстрока без учёта регистра, как `git log -i`.
+#[derive(Debug, Clone)]
+pub enum TextMatch {
+    Substring(String),
+    Regex(Regex),
+}
+
+impl TextMatch {
+    pub fn new(pattern: &str, regex: bool) -> Result<Self, regex::Error> {
+        if regex {
+            Ok(Self::Regex(RegexBuilder::new(pattern).case_insensitive(true).build()?))
+        } else {
+            Ok(Self::Substring(pattern.to_lowercase()))
+        }
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        match self {
+            Self::Substring(needle) => text.to_lowercase().contains(needle),
+            Self::Regex(re) => re.is_match(text),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct LogFilter {
+    /// Ищется в "имя <email>"
+    pub author: Option<TextMatch>,
+    /// Ищется в сообщении
+    pub grep: Option<TextMatch>,
+    /// Включительные границы по created_at
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    /// Только ноды, которым разрешён этот ремоут (то, что видно на нём)
+    pub remote: Option<String>,
+}
+
+impl LogFilter {
+    pub fn matches(&self, node: &Node) -> bool {
+        if let Some(author) = &self.author {
+            let who = format!("{} <{}>", node.author.name, node.author.email);
+            if !author.is_match(&who) {
+                return false;
+            }
+        }
+        if let Some(grep) = &self.grep {
+            if !grep.is_match(&node.message) {
+                return false;
+            }
+        }
+        if self.since.is_some_and(|since| node.created_at < since) {
+            return false;
+        }
+        if self.until.is_some_and(|until| node.created_at > until) {
+            return false;
+        }
+        if let Some(remote) = &self.remote {
+            let key = remote_name_key(remote);
+            if !node.remotes.iter().any(|r| remote_name_key(&r.name) == key) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Порядок вывода истории: каждая нода раньше всех своих родителей, новые сверху (как git log).
+/// Из готовых к выводу берётся самая свежая, при равенстве времени - по id, чтобы порядок был стабильным.
+pub(crate) fn topo_order(nodes: Vec<Node>) -> Vec<Node> {
+    let ids: HashSet<NodeId> = nodes.iter().map(|n| n.id.clone()).collect();
+    let mut pending_children: HashMap<NodeId, usize> = nodes
+        .iter()
+        .map(|n| (n.id.clone(), n.children.iter().filter(|c| ids.contains(*c)).count()))
+        .collect();
+    let mut by_id: HashMap<NodeId, Node> = nodes.into_iter().map(|n| (n.id.clone(), n)).collect();
+
+    let mut ready: BinaryHeap<(DateTime<Utc>, NodeId)> = pending_children
+        .iter()
+        .filter(|(_, count)| **count == 0)
+        .map(|(id, _)| (by_id[id].created_at, id.clone()))
+        .collect();
+
+    let mut order = Vec::with_capacity(by_id.len());
+    while let Some((_, id)) = ready.pop() {
+        let Some(node) = by_id.remove(&id) else { continue };
+        let unique_parents: HashSet<&NodeId> = node.parents.iter().collect();
+        for parent in unique_parents {
+            if let Some(count) = pending_children.get_mut(parent) {
+                *count = count.saturating_sub(1);
+                if *count == 0 {
+                    if let Some(p) = by_id.get(parent) {
+                        ready.push((p.created_at, parent.clone()));
+                    }
+                }
+            }
+        }
+        order.push(node);
+    }
+    order
+}
+
+/// Строка псевдографики для `log --graph`: либо строка ноды (node - индекс в срезе), либо соединительная
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct GraphRow {
+    pub node: Option<usize>,
+    pub prefix: String,
+}
+
+/// Соединительная строка, в которой линия j уходит влево, а все линии правее сдвигаются на одну позицию
+fn shift_left_row(width: usize, j: usize, draw_j: bool) -> String {
+    let mut row = vec![' '; 2 * width];
+    for i in 0..width {
+        if i < j {
+            row[2 * i] = '|';
+        } else if i > j || draw_j {
+            row[2 * i - 1] = '/';
+        }
+    }
+    row.into_iter().collect::<String>().trim_end().to_string()
+}
+
+/// Соединительная строка для ответвления: новая линия появляется справа от `from`, линии правее сдвигаются вправо
+fn shift_right_row(width: usize, from: usize) -> String {
+    let mut row = vec![' '; 2 * width + 2];
+    for i in 0..width {
+        if i <= from {
+            row[2 * i] = '|';
+        } else {
+            row[2 * i + 1] = '\\';
+        }
+    }
+    row[2 * from + 1] = '\\';
+    row.into_iter().collect::<String>().trim_end().to_string()
+}
+
+/// Раскладывает DAG по вертикальным линиям (lanes) и рисует его как `git log --graph`: `*` - нода,
+/// `|` - линия, `\` - ответвление к второму родителю, `/` - слияние линий.
+/// nodes должны идти в порядке topo_order. Ноды, для которых show = false, своей строки не получают,
+/// но линии через них идут как обычно.
+pub(crate) fn render_graph(nodes: &[Node], show: impl Fn(&Node) -> bool) -> Vec<GraphRow> {
+    // каждая линия "ждёт" ноду, которая появится в ней следующей
+    let mut lanes: Vec<NodeId> = Vec::new();
+    let mut rows = Vec::new();
+
+    for (idx, node) in nodes.iter().enumerate() {
+        let col = match lanes.iter().position(|l| l == &node.id) {
+            Some(c) => c,
+            None => {
+                lanes.push(node.id.clone());
+                lanes.len() - 1
+            }
+        };
+
+        // несколько линий ждут одну и ту же ноду (общий родитель веток) - сводим их в col
+        while let Some(j) = lanes.iter().rposition(|l| l == &node.id).filter(|j| *j != col) {
+            rows.push(GraphRow { node: None, prefix: shift_left_row(lanes.len(), j, true) });
+            lanes.remove(j);
+        }
+
+        if show(node) {
+            let prefix: Vec<&str> = (0..lanes.len()).map(|i| if i == col { "*" } else { "|" }).collect();
+            rows.push(GraphRow { node: Some(idx), prefix: prefix.join(" ") });
+        }
+
+        match node.parents.split_first() {
+            None => {
+                // корень: линия заканчивается, правые съезжают на её место
+                let width = lanes.len();
+                lanes.remove(col);
+                if col < lanes.len() {
+                    rows.push(GraphRow { node: None, prefix: shift_left_row(width, col, false) });
+                }
+            }
+            Some((first, rest)) => {
+                lanes[col] = first.clone();
+                for (k, parent) in rest.iter().enumerate() {
+                    rows.push(GraphRow { node: None, prefix: shift_right_row(lanes.len(), col + k) });
+                    lanes.insert(col + k + 1, parent.clone());
+                }
+            }
+        }
+    }
+
+    rows
+}
+
+/// Раскладывает render_graph по нодам: соединительные строки цепляются к ближайшей следующей показанной ноде
+pub(crate) fn graph_entries(ordered: Vec<Node>, show: impl Fn(&Node) -> bool) -> Vec<LogEntry> {
+    let rows = render_graph(&ordered, show);
+    let mut slots: Vec<Option<Node>> = ordered.into_iter().map(Some).collect();
+
+    let mut entries = Vec::new();
+    let mut connectors = Vec::new();
+    for row in rows {
+        match row.node {
+            Some(i) => {
+                let Some(node) = slots[i].take() else { continue };
+                let graph = GraphLines { connectors: std::mem::take(&mut connectors), prefix: row.prefix };
+                entries.push(LogEntry { node, graph: Some(graph), stat: None });
+            }
+            None => connectors.push(row.prefix),
+        }
+    }
+    entries
+}
+
+/// Разбирает дату для --since/--until: ISO (`2024-05-01`, `2024-05-01 12:00:00`, RFC 3339),
+/// `now`/`today`/`yesterday` и относительные формы как в git: `2.weeks`, `3.days.ago`, `5 hours ago`.
+/// Даты без зоны считаются локальными.
+pub fn parse_date(input: &str, now: DateTime<Utc>) -> Result<DateTime<Utc>, String> {
+    let text = input.trim().to_lowercase();
+    let local_midnight = |date: NaiveDate| {
+        Local.from_local_datetime(&date.and_hms_opt(0, 0, 0).unwrap_or_default())
+            .earliest()
+            .map(|d| d.with_timezone(&Utc))
+    };
+
+    match text.as_str() {
+        "now" => return Ok(now),
+        "today" => return local_midnight(now.with_timezone(&Local).date_naive()).ok_or_else(|| invalid_date(input)),
+        "yesterday" => {
+            let day = now.with_timezone(&Local).date_naive() - Duration::days(1);
+            return local_midnight(day).ok_or_else(|| invalid_date(input));
+        }
+        _ => {}
+    }
+
+    if let Ok(dt) = DateTime::parse_from_rfc3339(&text.to_uppercase()) {
+        return Ok(dt.with_timezone(&Utc));
+    }
+    for format in ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dt%H:%M:%S", "%Y-%m-%d %H:%M"] {
+        if let Ok(naive) = NaiveDateTime::parse_from_str(&text, format) {
+            return Local.from_local_datetime(&naive)
+                .earliest()
+                .map(|d| d.with_timezone(&Utc))
+                .ok_or_else(|| invalid_date(input));
+        }
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(&text, "%Y-%m-%d") {
+        return local_midnight(date).ok_or_else(|| invalid_date(input));
+    }
+
+    // относительные: "2.weeks", "2.weeks.ago", "2 weeks ago"
+    let parts: Vec<&str> = text.split(['.', ' ']).filter(|p| !p.is_empty()).collect();
+    let parts = match parts.as_slice() {
+        [rest @ .., "ago"] => rest,
+        all => all,
+    };
+    if let [amount, unit] = parts {
+        let amount: i64 = amount.parse().map_err(|_| invalid_date(input))?;
+        let unit_secs = match unit.trim_end_matches('s') {
+            "second" | "sec" => 1,
+            "minute" | "min" => 60,
+            "hour" => 3600,
+            "day" => 86_400,
+            "week" => 7 * 86_400,
+            "month" => 30 * 86_400,
+            "year" => 365 * 86_400,
+            _ => return Err(invalid_date(input)),
+        };
+        // 99999999999999.years не должно ронять процесс: любое переполнение - та же ошибка формата
+        return amount
+            .checked_mul(unit_secs)
+            .and_then(Duration::try_seconds)
+            .and_then(|delta| now.checked_sub_signed(delta))
+            .ok_or_else(|| invalid_date(input));
+    }
+
+    Err(invalid_date(input))
+}
+
+fn invalid_date(input: &str) -> String {
+    format!(
+        "Invalid date '{}': expected YYYY-MM-DD, RFC 3339 or a relative form like 2.weeks",
+        input
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{mock_graph, test_author};
+    use crate::types::Author;
+    use crate::backend::GraphOps;
+
+    fn node(author: &str, message: &str) -> Node {
+        let (mut graph, backend) = mock_graph();
+        backend.set_worktree(message);
+        let who = Author { name: author.into(), email: format!("{}@example.com", author.to_lowercase()) };
+        let id = graph.add_node(vec![], who, vec![], message.into(), None).unwrap();
+        graph.get_node(&id).unwrap()
+    }
+
+    #[test]
+    fn substring_match_is_case_insensitive() {
+        let filter = LogFilter {
+            author: Some(TextMatch::new("ALICE", false).unwrap()),
+            grep: Some(TextMatch::new("fix", false).unwrap()),
+            ..Default::default()
+        };
+        assert!(filter.matches(&node("Alice", "Fix the parser")));
+        assert!(!filter.matches(&node("Bob", "Fix the parser")));
+        assert!(!filter.matches(&node("Alice", "Add feature")));
+    }
+
+    #[test]
+    fn remote_filter_keeps_permitted_nodes() {
+        let (mut graph, backend) = mock_graph();
+        backend.set_worktree("shared");
+        let shared = graph.add_node(vec![], test_author(), vec![], "shared".into(), Some(vec!["public".into(), "secret".into()])).unwrap();
+        backend.set_worktree("private");
+        let private = graph.add_node(vec![shared.clone()], test_author(), vec![], "private".into(), Some(vec!["secret".into()])).unwrap();
+
+        let filter = LogFilter { remote: Some("Public".into()), ..Default::default() };
+        assert!(filter.matches(&graph.get_node(&shared).unwrap()));
+        assert!(!filter.matches(&graph.get_node(&private).unwrap()));
+    }
+
+    #[test]
+    fn regex_mode_and_invalid_pattern() {
+        let filter = LogFilter { grep: Some(TextMatch::new("^(fix|hotfix):", true).unwrap()), ..Default::default() };
+        assert!(filter.matches(&node("a", "hotfix: crash")));
+        assert!(!filter.matches(&node("a", "docs: mention fix:")));
+        assert!(TextMatch::new("(", true).is_err());
+    }
+
+    #[test]
+    fn parses_absolute_and_relative_dates() {
+        let now = DateTime::parse_from_rfc3339("2024-06-15T12:00:00Z").unwrap().with_timezone(&Utc);
+
+        assert_eq!(parse_date("2.weeks", now).unwrap(), now - Duration::weeks(2));
+        assert_eq!(parse_date("3.days.ago", now).unwrap(), now - Duration::days(3));
+        assert_eq!(parse_date("1 hour ago", now).unwrap(), now - Duration::hours(1));
+        assert_eq!(parse_date("2024-01-02T03:04:05Z", now).unwrap().to_rfc3339(), "2024-01-02T03:04:05+00:00");
+        assert!(parse_date("2024-01-02", now).is_ok());
+        assert!(parse_date("last tuesday", now).is_err());
+        assert!(parse_date("2.fortnights", now).is_err());
+        assert!(parse_date("99999999999999.years", now).is_err());
+        assert!(parse_date("9999999999.years", now).is_err());
+    }
+
+    #[test]
+    fn date_range_is_inclusive() {
+        let n = node("a", "m");
+        let at = n.created_at;
+        let filter = LogFilter { since: Some(at), until: Some(at), ..Default::default() };
+        assert!(filter.matches(&n));
+        let filter = LogFilter { since: Some(at + Duration::seconds(1)), ..Default::default() };
+        assert!(!filter.matches(&n));
+    }
+
+    fn dag(edges: &[(&str, &[&str])]) -> Vec<Node> {
+        let base = node("a", "m");
+        let mut nodes: Vec<Node> = edges
+            .iter()
+            .enumerate()
+            .map(|(i, (id, parents))| Node {
+                id: NodeId(id.to_string()),
+                parents: parents.iter().map(|p| NodeId(p.to_string())).collect(),
+                children: Default::default(),
+                message: id.to_string(),
+                created_at: base.created_at + Duration::seconds(i as i64),
+                ..base.clone()
+            })
+            .collect();
+        let links: Vec<(NodeId, NodeId)> = nodes
+            .iter()
+            .flat_map(|n| n.parents.iter().map(move |p| (p.clone(), n.id.clone())))
+            .collect();
+        for (parent, child) in links {
+            if let Some(p) = nodes.iter_mut().find(|n| n.id == parent) {
+                p.children.insert(child);
+            }
+        }
+        nodes
+    }
+
+    fn draw(nodes: Vec<Node>) -> String {
+        let ordered = topo_order(nodes);
+        render_graph(&ordered, |_| true)
+            .into_iter()
+            .map(|row| match row.node {
+                Some(i) => format!("{} {}", row.prefix, ordered[i].message),
+                None => row.prefix,
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn topo_order_puts_children_first() {
+        let ordered = topo_order(dag(&[("r", &[]), ("a", &["r"]), ("b", &["r"]), ("m", &["a", "b"])]));
+        let ids: Vec<&str> = ordered.iter().map(|n| n.id.0.as_str()).collect();
+        assert_eq!(ids, vec!["m", "b", "a", "r"]);
+    }
+
+    #[test]
+    fn graph_draws_merge_and_fork() {
+        let nodes = dag(&[("r", &[]), ("a", &["r"]), ("b", &["r"]), ("m", &["a", "b"])]);
+        assert_eq!(draw(nodes), "* m\n|\\\n| * b\n* | a\n|/\n* r");
+    }
+
+    #[test]
+    fn graph_handles_multiple_roots() {
+        let nodes = dag(&[("r1", &[]), ("r2", &[]), ("c", &["r1"])]);
+        assert_eq!(draw(nodes), "* c\n| * r2\n* r1");
+    }
+}
@@ -0,0 +1,592 @@
+// Всё, что gpp печатает пользователю как статус (не ошибки - у них свои типы), собрано здесь:
+// одна формулировка на событие, и язык у всего вывода один - выбранный через set_lang.
+// Скрипты и тесты разбирают только строки node_created и pushed_ref, они от языка не зависят.
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+use crate::types::NodeId;
+
+/// Язык вывода. По умолчанию английский.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Lang {
+    #[default]
+    En,
+    Ru,
+}
+
+impl FromStr for Lang {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "en" | "english" => Ok(Lang::En),
+            "ru" | "russian" => Ok(Lang::Ru),
+            other => Err(format!("Unknown language '{}': expected en or ru", other)),
+        }
+    }
+}
+
+static LANG: OnceLock<Lang> = OnceLock::new();
+
+/// Выбирает язык на весь процесс. Вызывается один раз при старте, повторные вызовы ничего не меняют.
+pub fn set_lang(lang: Lang) {
+    let _ = LANG.set(lang);
+}
+
+pub fn lang() -> Lang {
+    LANG.get().copied().unwrap_or_default()
+}
+
+static QUIET: OnceLock<bool> = OnceLock::new();
+
+/// --quiet: статусные строки не печатаются, остаются ошибки и сам результат команды
+pub fn set_quiet(quiet: bool) {
+    let _ = QUIET.set(quiet);
+}
+
+pub fn quiet() -> bool {
+    QUIET.get().copied().unwrap_or_default()
+}
+
+/// Печатает статусную строку (не результат команды), если не --quiet
+pub fn status(line: impl std::fmt::Display) {
+    if !quiet() {
+        println!("{}", line);
+    }
+}
+
+/// Единственная строка, которую читают машины: `Node created: <полный id>`, на любом языке
+pub fn node_created(id: &NodeId) -> String {
+    format!("Node created: {}", id.0)
+}
+
+pub fn success_label() -> &'static str {
+    match lang() {
+        Lang::En => "SUCCESS:",
+        Lang::Ru => "ГОТОВО:",
+    }
+}
+
+pub fn error_label() -> &'static str {
+    match lang() {
+        Lang::En => "ERROR:",
+        Lang::Ru => "ОШИБКА:",
+    }
+}
+
+pub fn warning_label() -> &'static str {
+    match lang() {
+        Lang::En => "WARNING:",
+        Lang::Ru => "ВНИМАНИЕ:",
+    }
+}
+
+pub fn history_empty() -> String {
+    match lang() {
+        Lang::En => "History is empty.".to_string(),
+        Lang::Ru => "История пуста.".to_string(),
+    }
+}
+
+pub fn head_moved(id: &NodeId) -> String {
+    match lang() {
+        Lang::En => format!("HEAD is now at {}", id.0),
+        Lang::Ru => format!("HEAD теперь на {}", id.0),
+    }
+}
+
+pub fn remote_head_set(remote: &str, node: &NodeId) -> String {
+    match lang() {
+        Lang::En => format!("HEAD for remote '{}' is now at {}", remote, node.0),
+        Lang::Ru => format!("HEAD ремоута '{}' теперь на {}", remote, node.0),
+    }
+}
+
+pub fn head_cleared() -> String {
+    match lang() {
+        Lang::En => "HEAD cleared, the next node will start a new root".to_string(),
+        Lang::Ru => "HEAD снят, следующая нода станет новым корнем".to_string(),
+    }
+}
+
+pub fn dangling_head(head: &NodeId) -> String {
+    match lang() {
+        Lang::En => format!(
+            "HEAD points to {} which is not in the graph. Reset it with `gpp checkout <node>` or `gpp checkout --orphan`",
+            head.short()
+        ),
+        Lang::Ru => format!(
+            "HEAD указывает на {}, которой нет в графе. Переставьте его: `gpp checkout <node>` или `gpp checkout --orphan`",
+            head.short()
+        ),
+    }
+}
+
+pub fn context_switched(name: &str) -> String {
+    match lang() {
+        Lang::En => format!("Switched to context '{}'", name),
+        Lang::Ru => format!("Активный контекст: '{}'", name),
+    }
+}
+
+pub fn no_contexts() -> String {
+    match lang() {
+        Lang::En => "No contexts initialized.".to_string(),
+        Lang::Ru => "Ни одного контекста не создано.".to_string(),
+    }
+}
+
+pub fn context_line(name: &str, active: bool) -> String {
+    format!("{} {}", if active { "*" } else { " " }, name)
+}
+
+pub fn context_not_initialized(name: &str) -> String {
+    match lang() {
+        Lang::En => format!("  {} (not initialized)", name),
+        Lang::Ru => format!("  {} (не создан)", name),
+    }
+}
+
+pub fn context_link_broken() -> String {
+    match lang() {
+        Lang::En => "(.git does not point to any context)".to_string(),
+        Lang::Ru => "(.git не указывает ни на один контекст)".to_string(),
+    }
+}
+
+/// kind - "symlink", "junction" и т.п. от бэкенда; foreign - ссылку сделали на другой ОС
+pub fn context_link_kind(kind: &str, foreign: bool) -> String {
+    match (lang(), foreign) {
+        (Lang::En, false) => format!("(.git is a {})", kind),
+        (Lang::En, true) => format!(
+            "(.git is a {} made on another OS, `gpp context <name>` recreates it for this one)",
+            kind
+        ),
+        (Lang::Ru, false) => format!("(.git - это {})", kind),
+        (Lang::Ru, true) => format!(
+            "(.git - это {}, сделанный на другой ОС, `gpp context <name>` пересоздаст его под эту)",
+            kind
+        ),
+    }
+}
+
+pub fn permissions_unchanged(remote: &str) -> String {
+    match lang() {
+        Lang::En => format!("Nothing would change for remote '{}'", remote),
+        Lang::Ru => format!("Для ремоута '{}' ничего не изменится", remote),
+    }
+}
+
+pub fn permissions_preview(count: usize, remove: bool, remote: &str) -> String {
+    match (lang(), remove) {
+        (Lang::En, false) => format!("{} node(s) would gain permission for remote '{}':", count, remote),
+        (Lang::En, true) => format!("{} node(s) would lose permission for remote '{}':", count, remote),
+        (Lang::Ru, false) => format!("Право на ремоут '{}' получат нод: {}", remote, count),
+        (Lang::Ru, true) => format!("Право на ремоут '{}' потеряют нод: {}", remote, count),
+    }
+}
+
+pub fn permission_added(remote: &str, count: usize) -> String {
+    match lang() {
+        Lang::En => format!("Added permission for remote '{}' ({} node(s))", remote, count),
+        Lang::Ru => format!("Право на ремоут '{}' выдано (нод: {})", remote, count),
+    }
+}
+
+pub fn permission_removed(remote: &str, count: usize) -> String {
+    match lang() {
+        Lang::En => format!("Removed permission for remote '{}' ({} node(s))", remote, count),
+        Lang::Ru => format!("Право на ремоут '{}' снято (нод: {})", remote, count),
+    }
+}
+
+pub fn push_completed() -> String {
+    match lang() {
+        Lang::En => "Push completed successfully".to_string(),
+        Lang::Ru => "Push выполнен".to_string(),
+    }
+}
+
+pub fn push_up_to_date() -> String {
+    match lang() {
+        Lang::En => "Nothing to push (up to date)".to_string(),
+        Lang::Ru => "Отправлять нечего, ремоут в актуальном состоянии".to_string(),
+    }
+}
+
+pub fn push_already_present(node: &NodeId, remote: &str) -> String {
+    match lang() {
+        Lang::En => format!("All nodes up to {} are already on remote '{}'.", node.short(), remote),
+        Lang::Ru => format!("Все ноды до {} уже есть на ремоуте '{}'.", node.short(), remote),
+    }
+}
+
+/// nodes - (id, первая строка сообщения) в порядке обхода от вершины; old_tip - что сейчас в refs/heads/*
+/// на ремоуте, насколько мы знаем (None - ветки там ещё нет)
+pub fn push_plan(
+    remote: &str,
+    url: &str,
+    nodes: &[(NodeId, String)],
+    target_refs: &[String],
+    old_tip: Option<&NodeId>,
+    tip: &NodeId,
+) -> String {
+    let (title, remote_label, nodes_label, ref_label, tip_label, none) = match lang() {
+        Lang::En => ("--- DRY RUN: selective push ---", "Remote:   ", "New nodes:", "Git ref:  ", "New tip:  ", "(none)"),
+        Lang::Ru => ("--- DRY RUN: селективный push ---", "Ремоут:   ", "Новых нод:", "Ссылка:   ", "Вершина:  ", "(нет)"),
+    };
+    let mut lines = vec![
+        title.to_string(),
+        format!("  {} '{}' ({})", remote_label, remote, url),
+        format!("  {} {}", nodes_label, nodes.len()),
+    ];
+    lines.extend(nodes.iter().map(|(id, message)| format!("    {} {}", id.short(), message)));
+    for target in target_refs {
+        // тег создаётся заново, у ветки показываем, откуда она сдвинется
+        let old = old_tip.filter(|_| target.starts_with("refs/heads/")).map_or(none, |old| old.short());
+        lines.push(format!("  {} {}  {} -> {}", ref_label, target, old, tip.short()));
+    }
+    lines.push(format!("  {} {}", tip_label, tip.0));
+    lines.push("-".repeat(title.chars().count()));
+    lines.join("\n")
+}
+
+pub fn push_sending(nodes: usize, remote: &str) -> String {
+    match lang() {
+        Lang::En => format!("Sending {} node(s) to '{}'...", nodes, remote),
+        Lang::Ru => format!("Отправка нод на '{}': {}...", remote, nodes),
+    }
+}
+
+pub fn push_ref_updated(target: &str, tip: &NodeId) -> String {
+    match lang() {
+        Lang::En => format!("Updated ref {} -> {}", target, tip.0),
+        Lang::Ru => format!("Обновлена ссылка {} -> {}", target, tip.0),
+    }
+}
+
+/// Итог push для CI: ремоут, ссылка и коммит, который на ней теперь. Не переводится, как node_created
+pub fn pushed_ref(remote: &str, target: &str, tip: &NodeId) -> String {
+    format!("Pushed: {} {} {}", remote, target, tip.0)
+}
+
+pub fn push_retrying(attempt: u32, retries: u32, delay_secs: u64) -> String {
+    match lang() {
+        Lang::En => format!("Retrying push ({}/{}) in {}s...", attempt, retries, delay_secs),
+        Lang::Ru => format!("Повтор push ({}/{}) через {} с...", attempt, retries, delay_secs),
+    }
+}
+
+pub fn tagged(node: &NodeId, name: &str) -> String {
+    match lang() {
+        Lang::En => format!("Tagged {} as '{}'", node.short(), name),
+        Lang::Ru => format!("Тег '{}' повешен на {}", name, node.short()),
+    }
+}
+
+pub fn no_tags() -> String {
+    match lang() {
+        Lang::En => "No tags.".to_string(),
+        Lang::Ru => "Тегов нет.".to_string(),
+    }
+}
+
+pub fn no_matching_nodes() -> String {
+    match lang() {
+        Lang::En => "No matching nodes.".to_string(),
+        Lang::Ru => "Подходящих нод нет.".to_string(),
+    }
+}
+
+pub fn no_duplicate_trees() -> String {
+    match lang() {
+        Lang::En => "No nodes share a tree.".to_string(),
+        Lang::Ru => "Нод с одинаковым деревом нет.".to_string(),
+    }
+}
+
+pub fn duplicate_tree(tree: &str, count: usize) -> String {
+    // tree_id из add_node_raw или правленого руками graph.json может быть любой строкой: режем по символам
+    let tree = NodeId(tree.to_string());
+    let tree = tree.short();
+    match lang() {
+        Lang::En => format!("Tree {} is shared by {} nodes:", tree, count),
+        Lang::Ru => format!("Дерево {} общее у нод: {}", tree, count),
+    }
+}
+
+/// ahead/behind - сколько нод node впереди ремоута и позади него
+pub fn remote_divergence(remote: &str, node: &NodeId, ahead: usize, behind: usize) -> String {
+    let node = node.short();
+    match (lang(), ahead, behind) {
+        (Lang::En, 0, 0) => format!("{} is up to date with '{}'.", node, remote),
+        (Lang::En, _, 0) => format!("{} is ahead of '{}' by {} node(s).", node, remote, ahead),
+        (Lang::En, 0, _) => format!("{} is behind '{}' by {} node(s).", node, remote, behind),
+        (Lang::En, _, _) => format!("{} and '{}' have diverged: ahead {}, behind {}.", node, remote, ahead, behind),
+        (Lang::Ru, 0, 0) => format!("{} совпадает с '{}'.", node, remote),
+        (Lang::Ru, _, 0) => format!("{} впереди '{}' на {} нод(ы).", node, remote, ahead),
+        (Lang::Ru, 0, _) => format!("{} отстаёт от '{}' на {} нод(ы).", node, remote, behind),
+        (Lang::Ru, _, _) => format!("{} и '{}' разошлись: впереди на {}, позади на {}.", node, remote, ahead, behind),
+    }
+}
+
+pub fn signatures_ok(checked: usize) -> String {
+    match lang() {
+        Lang::En => format!("All {} node(s) have valid signatures.", checked),
+        Lang::Ru => format!("Подписи верны у всех нод (проверено: {}).", checked),
+    }
+}
+
+pub fn graph_matches_git(checked: usize) -> String {
+    match lang() {
+        Lang::En => format!("Graph edges match git for {} node(s).", checked),
+        Lang::Ru => format!("Рёбра графа совпадают с git (проверено нод: {}).", checked),
+    }
+}
+
+pub fn graph_disagrees(count: usize, checked: usize) -> String {
+    match lang() {
+        Lang::En => format!("Graph disagrees with git on {} of {} node(s):", count, checked),
+        Lang::Ru => format!("Граф расходится с git у {} из {} нод:", count, checked),
+    }
+}
+
+pub fn parents_differ(node: &NodeId, graph: &str, git: &str) -> String {
+    match lang() {
+        Lang::En => format!("  {}  graph [{}], git [{}]", node.short(), graph, git),
+        Lang::Ru => format!("  {}  в графе [{}], в git [{}]", node.short(), graph, git),
+    }
+}
+
+pub fn no_such_commit(node: &NodeId) -> String {
+    match lang() {
+        Lang::En => format!("  {}  no such commit in git", node.short()),
+        Lang::Ru => format!("  {}  такого коммита в git нет", node.short()),
+    }
+}
+
+pub fn remotes_recorded_twice(count: usize) -> String {
+    match lang() {
+        Lang::En => format!("{} remote(s) are recorded more than once on a node:", count),
+        Lang::Ru => format!("Ремоуты, записанные на ноде больше одного раза: {}", count),
+    }
+}
+
+pub fn remote_urls(node: &NodeId, remote: &str, urls: &str) -> String {
+    match lang() {
+        Lang::En => format!("  {}  '{}' with urls [{}]", node.short(), remote, urls),
+        Lang::Ru => format!("  {}  '{}' с адресами [{}]", node.short(), remote, urls),
+    }
+}
+
+pub fn one_url_hint(node: &NodeId, remote: &str) -> String {
+    match lang() {
+        Lang::En => format!(
+            "hint: keep one url per remote with `gpp chrm --node {} --remote {} --url <url>`",
+            node.short(),
+            remote
+        ),
+        Lang::Ru => format!(
+            "подсказка: оставьте один адрес на ремоут через `gpp chrm --node {} --remote {} --url <url>`",
+            node.short(),
+            remote
+        ),
+    }
+}
+
+pub fn unsigned_nodes(count: usize, checked: usize) -> String {
+    match lang() {
+        Lang::En => format!("{} of {} node(s) lack a valid signature:", count, checked),
+        Lang::Ru => format!("Нет верной подписи у {} из {} нод:", count, checked),
+    }
+}
+
+pub fn unsigned() -> String {
+    match lang() {
+        Lang::En => "unsigned".to_string(),
+        Lang::Ru => "без подписи".to_string(),
+    }
+}
+
+pub fn tag_not_found(name: &str) -> String {
+    match lang() {
+        Lang::En => format!("Tag '{}' not found", name),
+        Lang::Ru => format!("Тег '{}' не найден", name),
+    }
+}
+
+pub fn node_required_for_push() -> String {
+    match lang() {
+        Lang::En => "Node ID required for push".to_string(),
+        Lang::Ru => "Для пуша нужен ID ноды".to_string(),
+    }
+}
+
+pub fn no_common_ancestor(a: &NodeId, b: &NodeId) -> String {
+    match lang() {
+        Lang::En => format!("Nodes {} and {} have no common ancestor", a.short(), b.short()),
+        Lang::Ru => format!("У нод {} и {} нет общего предка", a.short(), b.short()),
+    }
+}
+
+pub fn unknown_command(name: &str) -> String {
+    match lang() {
+        Lang::En => format!("Unknown command: {}", name),
+        Lang::Ru => format!("Неизвестная команда: {}", name),
+    }
+}
+
+pub fn repo_exists() -> String {
+    match lang() {
+        Lang::En => "A Git++ repository already exists here".to_string(),
+        Lang::Ru => "Репозиторий Git++ уже существует".to_string(),
+    }
+}
+
+pub fn init_started() -> String {
+    match lang() {
+        Lang::En => "Initializing Git++...".to_string(),
+        Lang::Ru => "Инициализация Git++...".to_string(),
+    }
+}
+
+pub fn context_setup(name: &str) -> String {
+    match lang() {
+        Lang::En => format!("Setting up context '{}'...", name),
+        Lang::Ru => format!("Настройка контекста '{}'...", name),
+    }
+}
+
+pub fn switching_context(id: &NodeId, context: &str) -> String {
+    match lang() {
+        Lang::En => format!("Node {} belongs to '{}'. Switching context...", id.0, context),
+        Lang::Ru => format!("Нода {} принадлежит '{}'. Переключаю контекст...", id.0, context),
+    }
+}
+
+/// preferred - контекст, к которому нода относится; в нём её нет, поэтому checkout идёт в context
+pub fn context_lacks_node(preferred: &str, id: &NodeId, context: &str) -> String {
+    match lang() {
+        Lang::En => format!("Context '{}' does not contain node {}, switching to '{}' instead...", preferred, id.short(), context),
+        Lang::Ru => format!("В контексте '{}' нет ноды {}, переключаю на '{}'...", preferred, id.short(), context),
+    }
+}
+
+pub fn remote_declared(name: &str) -> String {
+    match lang() {
+        Lang::En => format!("Remote '{}' declared, its context will be created on first use", name),
+        Lang::Ru => format!("Ремоут '{}' объявлен, контекст будет создан при первом использовании", name),
+    }
+}
+
+pub fn init_done() -> String {
+    match lang() {
+        Lang::En => "Done!".to_string(),
+        Lang::Ru => "Готово!".to_string(),
+    }
+}
+
+pub fn clone_started(url: &str, dir: &str) -> String {
+    match lang() {
+        Lang::En => format!("Cloning {} into '{}'...", url, dir),
+        Lang::Ru => format!("Клонирование {} в '{}'...", url, dir),
+    }
+}
+
+pub fn cloned(dir: &str, imported: usize) -> String {
+    match lang() {
+        Lang::En => format!("Cloned into '{}': {} node(s) imported", dir, imported),
+        Lang::Ru => format!("Склонировано в '{}', импортировано нод: {}", dir, imported),
+    }
+}
+
+pub fn gui_starting() -> String {
+    match lang() {
+        Lang::En => "Starting the graphical interface...".to_string(),
+        Lang::Ru => "Запуск графического интерфейса...".to_string(),
+    }
+}
+
+pub fn graph_healthy() -> String {
+    match lang() {
+        Lang::En => "graph.json is healthy, nothing to repair.".to_string(),
+        Lang::Ru => "graph.json в порядке, чинить нечего.".to_string(),
+    }
+}
+
+pub fn graph_restored(backup: &Path) -> String {
+    match lang() {
+        Lang::En => format!("Restored graph from {}", backup.display()),
+        Lang::Ru => format!("Граф восстановлен из {}", backup.display()),
+    }
+}
+
+pub fn corrupt_copy_kept(path: &Path) -> String {
+    match lang() {
+        Lang::En => format!("Corrupt file kept at {}", path.display()),
+        Lang::Ru => format!("Испорченный файл сохранён в {}", path.display()),
+    }
+}
+
+pub fn no_snapshots() -> String {
+    match lang() {
+        Lang::En => "No snapshots.".to_string(),
+        Lang::Ru => "Снапшотов нет.".to_string(),
+    }
+}
+
+pub fn snapshot_restored(name: &str, backup: &str) -> String {
+    match lang() {
+        Lang::En => format!("Restored snapshot '{}' (previous state saved as '{}')", name, backup),
+        Lang::Ru => format!("Снапшот '{}' восстановлен (прежнее состояние сохранено как '{}')", name, backup),
+    }
+}
+
+pub fn snapshot_created(name: &str) -> String {
+    match lang() {
+        Lang::En => format!("Snapshot '{}' created", name),
+        Lang::Ru => format!("Снапшот '{}' создан", name),
+    }
+}
+
+pub fn stash_saved(head: &NodeId) -> String {
+    match lang() {
+        Lang::En => format!("Saved working tree, reset to {}", head.short()),
+        Lang::Ru => format!("Рабочая директория отложена, сброшена на {}", head.short()),
+    }
+}
+
+pub fn stash_restored() -> String {
+    match lang() {
+        Lang::En => "Restored stashed changes".to_string(),
+        Lang::Ru => "Отложенные изменения возвращены".to_string(),
+    }
+}
+
+pub fn files_prompt() -> String {
+    match lang() {
+        Lang::En => "Files for the node (Space to toggle, Enter to confirm)".to_string(),
+        Lang::Ru => "Файлы для ноды (пробел - отметить, Enter - готово)".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn language_names_parse() {
+        assert_eq!("en".parse::<Lang>().unwrap(), Lang::En);
+        assert_eq!(" RU ".parse::<Lang>().unwrap(), Lang::Ru);
+        assert!("de".parse::<Lang>().is_err());
+        assert_eq!(Lang::default(), Lang::En);
+        // строка для скриптов от языка не зависит
+        assert_eq!(node_created(&NodeId("abc".into())), "Node created: abc");
+    }
+
+    #[test]
+    fn duplicate_tree_shortens_non_ascii_ids() {
+        assert!(duplicate_tree("дерево-без-хеша", 2).contains("дерево-"));
+    }
+}
@@ -0,0 +1,117 @@
+// Миграции graph.json между версиями формата (storage::FORMAT_VERSION).
+// Каждая миграция работает с сырым JSON всего графа (id -> нода), поэтому может переименовывать
+// и перекладывать поля, которые текущая схема Node уже не прочитала бы.
+use std::collections::BTreeMap;
+
+use serde_json::{json, Value};
+
+use crate::storage::{Result, StorageError, FORMAT_VERSION};
+
+pub type Migration = fn(Value) -> Value;
+
+/// (from, to, migration) по порядку. Новая версия формата = новая строчка в конце.
+const MIGRATIONS: &[(u32, u32, Migration)] = &[
+    (0, 1, fill_node_defaults),
+    (1, 2, split_remote_tips),
+];
+
+/// Поднимает граф версии `from` до FORMAT_VERSION, по одной миграции за шаг
+pub fn migrate(mut graph: Value, from: u32) -> Result<Value> {
+    let mut version = from;
+    while version < FORMAT_VERSION {
+        let (_, to, step) = MIGRATIONS
+            .iter()
+            .find(|(f, _, _)| *f == version)
+            .ok_or_else(|| StorageError::Tx(format!("No migration from repository format {}", version)))?;
+        graph = step(graph);
+        version = *to;
+    }
+    Ok(graph)
+}
+
+/// 0 -> 1: графы до версионирования могли не иметь tags/metadata/co_authors у нод
+fn fill_node_defaults(mut graph: Value) -> Value {
+    if let Some(nodes) = graph.as_object_mut() {
+        for node in nodes.values_mut() {
+            if let Some(fields) = node.as_object_mut() {
+                fields.entry("tags").or_insert_with(|| json!({}));
+                fields.entry("metadata").or_insert_with(|| json!({}));
+                fields.entry("co_authors").or_insert_with(|| json!([]));
+            }
+        }
+    }
+    graph
+}
+
+/// 1 -> 2: ноды уезжают в секцию nodes, рядом появляется remote_tips (ремоут -> последняя запушенная нода).
+/// Раньше вершина была меткой remote_tip.<ремоут> в метаданных ноды, метки переносятся туда.
+fn split_remote_tips(graph: Value) -> Value {
+    let Value::Object(mut nodes) = graph else {
+        return graph;
+    };
+    // ремоут -> (время push, id)
+    let mut tips: BTreeMap<String, (String, String)> = BTreeMap::new();
+    for (id, node) in nodes.iter_mut() {
+        let Some(metadata) = node.get_mut("metadata").and_then(Value::as_object_mut) else {
+            continue;
+        };
+        let keys: Vec<String> = metadata.keys().filter(|k| k.starts_with("remote_tip.")).cloned().collect();
+        for key in keys {
+            let pushed_at = metadata.remove(&key).and_then(|v| v.as_str().map(str::to_string)).unwrap_or_default();
+            let remote = key["remote_tip.".len()..].to_string();
+            // метка у ремоута одна, но если граф правили руками - побеждает последний push
+            if tips.get(&remote).is_none_or(|(at, _)| *at < pushed_at) {
+                tips.insert(remote, (pushed_at, id.clone()));
+            }
+        }
+    }
+    let tips: BTreeMap<String, String> = tips.into_iter().map(|(remote, (_, id))| (remote, id)).collect();
+    json!({ "nodes": nodes, "remote_tips": tips })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use crate::types::{Node, NodeId};
+
+    #[test]
+    fn v0_graph_loads_as_current() {
+        let v0 = json!({
+            "n1": {
+                "id": "n1",
+                "parents": [],
+                "children": [],
+                "author": { "name": "a", "email": "a@b" },
+                "message": "old",
+                "created_at": "2023-01-01T00:00:00Z",
+                "payload": { "tree_id": "t" },
+                "remotes": []
+            }
+        });
+        assert!(serde_json::from_value::<HashMap<NodeId, Node>>(v0.clone()).is_err());
+
+        let nodes: HashMap<NodeId, Node> = serde_json::from_value(migrate(v0, 0).unwrap()["nodes"].take()).unwrap();
+        let node = &nodes[&NodeId("n1".into())];
+        assert_eq!(node.message, "old");
+        assert!(node.metadata.is_empty() && node.tags.is_empty() && node.co_authors.is_empty());
+    }
+
+    #[test]
+    fn v1_remote_tip_marks_move_out_of_metadata() {
+        let v1 = json!({
+            "n1": { "id": "n1", "metadata": { "remote_tip.origin": "2024-01-01T00:00:00+00:00", "team": "core" } },
+            "n2": { "id": "n2", "metadata": { "remote_tip.origin": "2024-02-01T00:00:00+00:00" } }
+        });
+        let v2 = migrate(v1, 1).unwrap();
+        assert_eq!(v2["remote_tips"], json!({ "origin": "n2" }));
+        assert_eq!(v2["nodes"]["n1"]["metadata"], json!({ "team": "core" }));
+        assert_eq!(v2["nodes"]["n2"]["metadata"], json!({}));
+    }
+
+    #[test]
+    fn current_version_is_untouched() {
+        let graph = json!({ "n1": { "id": "n1" } });
+        assert_eq!(migrate(graph.clone(), FORMAT_VERSION).unwrap(), graph);
+    }
+}
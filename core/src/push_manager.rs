@@ -1,58 +1,310 @@
 use std::collections::{HashSet, VecDeque};
 use std::error::Error;
 use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
 
-use crate::types::{NodeId, RemoteRef};
-use crate::backend::{RepoBackend, GraphOps};
+use crate::types::{Node, NodeId, RemoteRef};
+use crate::backend::{RepoBackend, GraphOps, Progress};
+use crate::messages;
 
 #[derive(Debug)]
-pub struct PushError(String);
+pub enum PushError {
+    /// Нода или её предок не разрешает пушить в этот ремоут
+    Permission(String),
+    /// На ремоуте есть коммиты, которых нет у нас: git отклонил push как non-fast-forward
+    NonFastForward { remote: String },
+    /// Сеть подвела (DNS, обрыв соединения): ремоут ничего не отклонял, push можно повторить
+    Network { remote: String, message: String },
+    /// Пушить некуда: у ремоута пустой URL (так бывает у корней, созданных без --url)
+    MissingUrl { remote: String },
+    /// Нода с запрещённым для ремоута тегом или метаданными (remote.<name>.deny), права тут ни при чём
+    Denied { remote: String, node: NodeId, rule: DenyRule },
+}
 
 impl fmt::Display for PushError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "Push Error: {}", self.0)
+        match self {
+            Self::Permission(msg) => write!(f, "Push Error: {}", msg),
+            Self::NonFastForward { remote } => write!(
+                f,
+                "Push Error: remote '{}' has commits that are not in the local graph (non-fast-forward)",
+                remote
+            ),
+            Self::Network { remote, message } => write!(f, "Push Error: network failure talking to '{}': {}", remote, message),
+            Self::MissingUrl { remote } => write!(
+                f,
+                "Push Error: remote '{}' has no URL configured; run `gpp chrm --remote {} --url <url>`",
+                remote, remote
+            ),
+            Self::Denied { remote, node, rule } => write!(
+                f,
+                "Push Error: node {} matches '{}', which must never reach remote '{}' (remote.{}.deny in .gitpp/config)",
+                node.short(), rule, remote, remote
+            ),
+        }
     }
 }
 
 impl Error for PushError {}
 
+/// Правило из remote.<name>.deny: ноду с таким тегом или метаданными на этот ремоут не отправлять.
+/// Записывается как tag:<имя>, meta:<ключ> (ключ есть с любым значением) или meta:<ключ>=<значение>.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DenyRule {
+    Tag(String),
+    Meta { key: String, value: Option<String> },
+}
+
+impl DenyRule {
+    pub fn matches(&self, node: &Node) -> bool {
+        match self {
+            Self::Tag(name) => node.tags.contains_key(name),
+            Self::Meta { key, value } => node
+                .metadata
+                .get(key)
+                .is_some_and(|actual| value.as_ref().is_none_or(|v| v == actual)),
+        }
+    }
+}
+
+impl FromStr for DenyRule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let rule = match s.trim().split_once(':') {
+            Some(("tag", name)) if !name.trim().is_empty() => Self::Tag(name.trim().to_string()),
+            Some(("meta", spec)) => match spec.split_once('=') {
+                Some((key, value)) => Self::Meta { key: key.trim().to_string(), value: Some(value.trim().to_string()) },
+                None => Self::Meta { key: spec.trim().to_string(), value: None },
+            },
+            _ => return Err(format!("Invalid deny rule '{}': expected tag:<name>, meta:<key> or meta:<key>=<value>", s.trim())),
+        };
+        if matches!(&rule, Self::Meta { key, .. } if key.is_empty()) {
+            return Err(format!("Invalid deny rule '{}': metadata key is empty", s.trim()));
+        }
+        Ok(rule)
+    }
+}
+
+impl fmt::Display for DenyRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Tag(name) => write!(f, "tag:{}", name),
+            Self::Meta { key, value: Some(value) } => write!(f, "meta:{}={}", key, value),
+            Self::Meta { key, value: None } => write!(f, "meta:{}", key),
+        }
+    }
+}
+
+/// Что ушло на ремоут: обновлённые ссылки и коммит, на который они теперь указывают
+/// (обычно это id ноды, но при graph.ids = content - Node.commit). При dry-run - что ушло бы.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PushOutcome {
+    pub refs: Vec<String>,
+    pub tip: NodeId,
+    /// Только при dry-run: план push для показа пользователю (новые ноды и сдвиг ссылок)
+    pub plan: Option<String>,
+}
+
+/// Повторы push после PushError::Network. Пауза перед n-м повтором - base_delay * 2^(n-1), но не больше MAX_BACKOFF.
+/// Отказы ремоута (non-fast-forward, права, авторизация) не повторяются никогда.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    pub retries: u32,
+    pub base_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self { retries: 2, base_delay: Duration::from_secs(1) }
+    }
+}
+
+/// Дольше между повторами не ждём: при net.retries = 40 удвоение дошло бы до десятилетий
+pub const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+impl RetryPolicy {
+    /// Пауза перед повтором номер attempt (с 1)
+    pub fn delay(&self, attempt: u32) -> Duration {
+        2u32.checked_pow(attempt.saturating_sub(1))
+            .and_then(|factor| self.base_delay.checked_mul(factor))
+            .map_or(MAX_BACKOFF, |delay| delay.min(MAX_BACKOFF))
+    }
+}
+
+/// Ноды, которые ремоут уже имеет: его tracking-ссылки, а если их нет (пропал .git_<remote>) -
+/// то, что запомнил граф. Коммиты, которых в графе нет (кто-то другой запушил), остаются как есть.
+pub fn known_remote_tips(graph: &dyn GraphOps, backend: &dyn RepoBackend, remote_name: &str) -> Result<Vec<NodeId>, Box<dyn Error>> {
+    let commits = backend.remote_tips(remote_name)?;
+    if commits.is_empty() {
+        return graph.recorded_remote_tips(remote_name);
+    }
+    let mut tips = Vec::new();
+    for commit in commits {
+        tips.push(graph.node_for_commit(&commit)?.unwrap_or(commit));
+    }
+    Ok(tips)
+}
+
 pub struct PushManager<'a> {
     graph: &'a dyn GraphOps,
     backend: &'a dyn RepoBackend,
+    retry: RetryPolicy,
+    skip_hooks: bool,
+    deny: Vec<DenyRule>,
 }
 
 impl<'a> PushManager<'a> {
     
     pub fn new(graph: &'a dyn GraphOps, backend: &'a dyn RepoBackend) -> Self {
-        Self { graph, backend }
+        Self { graph, backend, retry: RetryPolicy::default(), skip_hooks: false, deny: Vec::new() }
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Не запускать pre-push хуки (push --no-verify)
+    pub fn with_skip_hooks(mut self, skip_hooks: bool) -> Self {
+        self.skip_hooks = skip_hooks;
+        self
+    }
+
+    /// Правила remote.<name>.deny того ремоута, на который пушим
+    pub fn with_deny(mut self, rules: Vec<DenyRule>) -> Self {
+        self.deny = rules;
+        self
+    }
+
+    /// Первая из отправляемых нод, которую запрещает правило deny
+    fn check_deny(&self, nodes: &[NodeId], remote: &RemoteRef) -> Result<(), Box<dyn Error>> {
+        if self.deny.is_empty() {
+            return Ok(());
+        }
+        for id in nodes {
+            let node = self.graph.get_node(id)?;
+            if let Some(rule) = self.deny.iter().find(|rule| rule.matches(&node)) {
+                return Err(Box::new(PushError::Denied { remote: remote.name.clone(), node: id.clone(), rule: rule.clone() }));
+            }
+        }
+        Ok(())
+    }
+
+    /// push_update_ref с повторами по self.retry, каждый повтор сообщается в progress
+    fn push_with_retry(
+        &self,
+        remote: &RemoteRef,
+        node_id: &NodeId,
+        target_refs: &[String],
+        progress: Option<&dyn Fn(Progress)>,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut attempt = 0;
+        loop {
+            let err = match self.backend.push_update_ref(remote, node_id, target_refs, self.skip_hooks, progress) {
+                Ok(()) => return Ok(()),
+                Err(e) => e,
+            };
+            let transient = matches!(err.downcast_ref::<PushError>(), Some(PushError::Network { .. }));
+            if !transient || attempt >= self.retry.retries {
+                return Err(err);
+            }
+            attempt += 1;
+            let delay = self.retry.delay(attempt);
+            if let Some(report) = progress {
+                report(Progress::Retry { attempt, retries: self.retry.retries, delay, error: err.to_string() });
+            }
+            std::thread::sleep(delay);
+        }
     }
     
-    fn compute_nodes_to_push(
+    /// Всё, что уже есть на ремоуте: предки всех известных его вершин.
+    /// Вершины, которых нет в графе (кто-то другой запушил), просто пропускаем.
+    fn collect_present(&self, remote_tips: &[NodeId]) -> HashSet<NodeId> {
+        let mut present = HashSet::new();
+        let mut queue: VecDeque<NodeId> = remote_tips.iter().cloned().collect();
+
+        while let Some(current_id) = queue.pop_front() {
+            if !present.insert(current_id.clone()) {
+                continue;
+            }
+            if let Ok(node) = self.graph.get_node(&current_id) {
+                for parent_id in node.parents {
+                    if !present.contains(&parent_id) {
+                        queue.push_back(parent_id);
+                    }
+                }
+            }
+        }
+
+        present
+    }
+
+    /// Известная вершина ремоута, от которой push сдвинет ветку: ближайшая к node_id среди его предков.
+    /// None - ни одна не лежит под node_id (ветки на ремоуте ещё нет).
+    fn previous_tip(&self, node_id: &NodeId, remote_tips: &[NodeId]) -> Option<NodeId> {
+        let tips: HashSet<&NodeId> = remote_tips.iter().collect();
+        let mut visited = HashSet::new();
+        let mut queue = VecDeque::from([node_id.clone()]);
+        while let Some(current_id) = queue.pop_front() {
+            if tips.contains(&current_id) {
+                return Some(current_id);
+            }
+            if !visited.insert(current_id.clone()) {
+                continue;
+            }
+            if let Ok(node) = self.graph.get_node(&current_id) {
+                queue.extend(node.parents);
+            }
+        }
+        None
+    }
+
+    /// Ноды, которые придётся отправить: start_node и его предки, кроме уже имеющихся на ремоуте
+    /// (remote_tips и всё ниже них). Ошибка, если хоть одной из них ремоут не разрешён.
+    pub fn compute_nodes_to_push(
         &self,
         start_node: &NodeId,
         remote: &RemoteRef,
-        remote_head: Option<&NodeId>,
+        remote_tips: &[NodeId],
     ) -> Result<Vec<NodeId>, Box<dyn Error>> {
+        let present = self.collect_present(remote_tips);
+
         let mut to_push = Vec::new();
         let mut visited = HashSet::new();
+        // вместе с id храним расстояние от start_node, чтобы в ошибке было видно, какой предок сломал цепочку
         let mut queue = VecDeque::new();
 
-        queue.push_back(start_node.clone());
+        queue.push_back((start_node.clone(), 0usize));
         visited.insert(start_node.clone());
 
-        while let Some(current_id) = queue.pop_front() {
-            if let Some(head) = remote_head {
-                if &current_id == head {
-                    continue;
-                }
+        while let Some((current_id, depth)) = queue.pop_front() {
+            if present.contains(&current_id) {
+                continue;
             }
 
             let node = self.graph.get_node(&current_id)?;
 
-            if !node.remotes.contains(remote) {
-                return Err(Box::new(PushError(format!(
-                    "Node {:?} does not allow pushing to remote '{}'",
-                    current_id, remote.name
+            if !node.allows_remote(remote) {
+                let mut allowed: Vec<&str> = node.remotes.iter().map(|r| r.name.as_str()).collect();
+                allowed.sort();
+                let position = if depth == 0 {
+                    "the node being pushed".to_string()
+                } else {
+                    format!("ancestor of {} ({} generation(s) back)", start_node.short(), depth)
+                };
+                return Err(Box::new(PushError::Permission(format!(
+                    "Node {} \"{}\" ({}) does not allow pushing to remote '{}'. \
+                    It allows: [{}]. To grant the permission to the whole ancestry run: \
+                    gpp chrm --node {} --remote {} --url <url> --recursive",
+                    current_id.short(),
+                    node.message.lines().next().unwrap_or(""),
+                    position,
+                    remote.name,
+                    allowed.join(", "),
+                    start_node.0,
+                    remote.name
                 ))));
             }
 
@@ -61,7 +313,7 @@ impl<'a> PushManager<'a> {
             for parent_id in node.parents {
                 if !visited.contains(&parent_id) {
                     visited.insert(parent_id.clone());
-                    queue.push_back(parent_id);
+                    queue.push_back((parent_id, depth + 1));
                 }
             }
         }
@@ -69,41 +321,336 @@ impl<'a> PushManager<'a> {
         Ok(to_push)
     }
     
+    /// Пушит node_id в ветку main ремоута. С tag на ремоуте создаётся ещё и refs/tags/<tag>
+    /// (даже если сами ноды там уже есть). None - отправлять было нечего.
     pub fn push(
         &self,
         node_id: &NodeId,
         remote: &RemoteRef,
+        tag: Option<&str>,
         dry_run: bool,
-    ) -> Result<bool, Box<dyn Error>> {
+        progress: Option<&dyn Fn(Progress)>,
+    ) -> Result<Option<PushOutcome>, Box<dyn Error>> {
+        // git push на пустой URL падает невнятно, и то уже после обхода графа
+        if remote.url.trim().is_empty() {
+            return Err(Box::new(PushError::MissingUrl { remote: remote.name.clone() }));
+        }
+
         let remote_branch = "main";
         let remote_ref_name = format!("refs/heads/{}", remote_branch);
+        let tag_ref_name = tag.map(|t| format!("refs/tags/{}", t));
+
+        let remote_tips = known_remote_tips(self.graph, self.backend, &remote.name)?;
 
-        let cached_remote_ref = format!("refs/remotes/{}/{}", remote.name, remote_branch);
-        let remote_head = self.backend.read_ref(cached_remote_ref)?;
+        let nodes_to_push = self.compute_nodes_to_push(node_id, remote, &remote_tips)?;
 
-        let nodes_to_push = self.compute_nodes_to_push(node_id, remote, remote_head.as_ref())?;
+        let mut target_refs = Vec::new();
+        if !nodes_to_push.is_empty() {
+            target_refs.push(remote_ref_name);
+        }
+        target_refs.extend(tag_ref_name);
+
+        if target_refs.is_empty() {
+            messages::status(messages::push_already_present(node_id, &remote.name));
+            return Ok(None);
+        }
 
-        if nodes_to_push.is_empty() {
-            println!("Все ноды до {:?} уже находятся на удаленном репозитории '{}'.", node_id, remote.name);
-            return Ok(false);
+        // тег уходит на ремоут и тогда, когда сама нода там уже есть
+        let mut checked = nodes_to_push.clone();
+        if tag.is_some() && !checked.contains(node_id) {
+            checked.push(node_id.clone());
         }
+        self.check_deny(&checked, remote)?;
 
+        let commit = self.graph.get_node(node_id)?.commit_id().clone();
         if dry_run {
-            println!("--- DRY RUN: Селективный Пуш ---");
-            println!("  Удаленный репозиторий: '{}' ({})", remote.name, remote.url);
-            println!("  Будет отправлено {} новых нод.", nodes_to_push.len());
-            println!("  Целевая Git-ссылка: {}", remote_ref_name);
-            println!("  Новая вершина: {:?}", node_id);
-            println!("---------------------------------");
-            return Ok(true);
+            let mut nodes = Vec::new();
+            for id in &nodes_to_push {
+                let message = self.graph.get_node(id)?.message.lines().next().unwrap_or("").to_string();
+                nodes.push((id.clone(), message));
+            }
+            let old_tip = self.previous_tip(node_id, &remote_tips);
+            let plan = messages::push_plan(&remote.name, &remote.url, &nodes, &target_refs, old_tip.as_ref(), node_id);
+            return Ok(Some(PushOutcome { refs: target_refs, tip: commit, plan: Some(plan) }));
+        }
+
+        messages::status(messages::push_sending(nodes_to_push.len(), &remote.name));
+        if let Some(report) = progress {
+            report(Progress::Planned { nodes: nodes_to_push.len() });
         }
 
-        println!("Отправка {} нод на '{}'...", nodes_to_push.len(), remote.name);
+        self.push_with_retry(remote, &commit, &target_refs, progress)?;
+
+        for target in &target_refs {
+            messages::status(messages::push_ref_updated(target, node_id));
+        }
+
+        Ok(Some(PushOutcome { refs: target_refs, tip: commit, plan: None }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{mock_graph, test_author};
+    use crate::version_graph::VersionGraph;
+
+    fn remote(name: &str) -> RemoteRef {
+        RemoteRef::new(name, format!("https://example.com/{}.git", name)).unwrap()
+    }
+
+    fn chain(graph: &mut VersionGraph, backend: &crate::testing::MockRepoBackend, len: usize) -> Vec<NodeId> {
+        let mut ids: Vec<NodeId> = Vec::new();
+        for i in 0..len {
+            backend.set_worktree(&format!("v{}", i));
+            let parents = ids.last().cloned().into_iter().collect();
+            ids.push(graph.add_node(parents, test_author(), vec![], format!("n{}", i), None).unwrap());
+        }
+        ids
+    }
 
-        self.backend.push_update_ref(remote, node_id, &remote_ref_name)?;
+    #[test]
+    fn pushes_whole_ancestry_when_remote_is_empty() {
+        let (mut graph, backend) = mock_graph();
+        let ids = chain(&mut graph, &backend, 3);
+
+        let push_mgr = PushManager::new(&graph, &backend);
+        let nodes = push_mgr.compute_nodes_to_push(&ids[2], &remote("origin"), &[]).unwrap();
+        assert_eq!(nodes.len(), 3);
+    }
+
+    #[test]
+    fn stops_at_remote_head() {
+        let (mut graph, backend) = mock_graph();
+        let ids = chain(&mut graph, &backend, 3);
+
+        let push_mgr = PushManager::new(&graph, &backend);
+        let nodes = push_mgr.compute_nodes_to_push(&ids[2], &remote("origin"), &[ids[0].clone()]).unwrap();
+        assert_eq!(nodes, vec![ids[2].clone(), ids[1].clone()]);
+    }
+
+    #[test]
+    fn push_updates_remote_ref_in_backend() {
+        let (mut graph, backend) = mock_graph();
+        let ids = chain(&mut graph, &backend, 2);
+
+        let push_mgr = PushManager::new(&graph, &backend);
+        assert!(push_mgr.push(&ids[1], &remote("origin"), None, false, None).unwrap().is_some());
+
+        let state = backend.state();
+        assert_eq!(state.pushes.len(), 1);
+        assert_eq!(state.pushes[0].tip, ids[1]);
+        assert_eq!(state.pushes[0].target_refs, vec!["refs/heads/main".to_string()]);
+    }
+
+    #[test]
+    fn empty_url_is_rejected_before_anything_is_pushed() {
+        let (mut graph, backend) = mock_graph();
+        let ids = chain(&mut graph, &backend, 1);
+
+        let err = PushManager::new(&graph, &backend)
+            .push(&ids[0], &RemoteRef::without_url("origin").unwrap(), None, true, None)
+            .unwrap_err();
+        assert!(matches!(err.downcast_ref::<PushError>(), Some(PushError::MissingUrl { remote }) if remote == "origin"));
+        assert!(err.to_string().contains("gpp chrm --remote origin --url"));
+        assert!(backend.state().pushes.is_empty());
+    }
+
+    #[test]
+    fn rejects_node_without_permission() {
+        let (mut graph, backend) = mock_graph();
+        let ids = chain(&mut graph, &backend, 1);
+
+        let push_mgr = PushManager::new(&graph, &backend);
+        assert!(push_mgr.push(&ids[0], &remote("secret"), None, true, None).is_err());
+    }
+
+    #[test]
+    fn url_less_permission_matches_any_url_but_recorded_url_must_match() {
+        let (mut graph, backend) = mock_graph();
+        let ids = chain(&mut graph, &backend, 1);
+        graph.add_remote_permission(&ids[0], RemoteRef::new("work", "https://a.example/repo").unwrap(), false).unwrap();
+
+        let push_mgr = PushManager::new(&graph, &backend);
+        // origin у корня без URL
+        assert!(push_mgr.push(&ids[0], &RemoteRef::new("origin", "https://any.example/x").unwrap(), None, true, None).is_ok());
+        assert!(push_mgr.push(&ids[0], &RemoteRef::new("work", "https://a.example/repo").unwrap(), None, true, None).is_ok());
+        assert!(push_mgr.push(&ids[0], &RemoteRef::new("work", "https://b.example/repo").unwrap(), None, true, None).is_err());
+    }
 
-        println!("Успешно обновлена ссылка {} -> {:?}", remote_ref_name, node_id);
+    #[test]
+    fn permission_error_names_the_breaking_ancestor() {
+        let (mut graph, backend) = mock_graph();
+        let ids = chain(&mut graph, &backend, 3);
+        graph.add_remote_permission(&ids[2], remote("work"), false).unwrap();
+        graph.add_remote_permission(&ids[1], remote("work"), false).unwrap();
 
-        Ok(true)
+        let push_mgr = PushManager::new(&graph, &backend);
+        let err = push_mgr.push(&ids[2], &remote("work"), None, true, None).unwrap_err().to_string();
+        assert!(err.contains(ids[0].short()));
+        assert!(err.contains("\"n0\""));
+        assert!(err.contains("2 generation(s) back"));
+        assert!(err.contains("It allows: [origin]"));
+        assert!(err.contains("--recursive"));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn skips_everything_reachable_from_any_remote_tip() {
+        let (mut graph, backend) = mock_graph();
+        let ids = chain(&mut graph, &backend, 2);
+        // две ветки от ids[1]: ремоут уже знает сиблинга, локально растим другую
+        backend.set_worktree("sibling");
+        let sibling = graph.add_node(vec![ids[1].clone()], test_author(), vec![], "sibling".into(), None).unwrap();
+        backend.set_worktree("local");
+        let local = graph.add_node(vec![ids[1].clone()], test_author(), vec![], "local".into(), None).unwrap();
+
+        backend.set_ref("refs/remotes/origin/feature", &sibling);
+        let push_mgr = PushManager::new(&graph, &backend);
+        let tips = backend.remote_tips("origin").unwrap();
+        let nodes = push_mgr.compute_nodes_to_push(&local, &remote("origin"), &tips).unwrap();
+        assert_eq!(nodes, vec![local]);
+    }
+
+    #[test]
+    fn deny_rules_stop_the_push_before_anything_is_sent() {
+        let (mut graph, backend) = mock_graph();
+        let ids = chain(&mut graph, &backend, 3);
+        graph.tag_node(&ids[1], "internal-only", None).unwrap();
+
+        let rules: Vec<DenyRule> = vec!["meta:classification=secret".parse().unwrap(), "tag:internal-only".parse().unwrap()];
+        let push_mgr = PushManager::new(&graph, &backend).with_deny(rules.clone());
+        let err = push_mgr.push(&ids[2], &remote("origin"), None, true, None).unwrap_err();
+        assert!(matches!(err.downcast_ref::<PushError>(), Some(PushError::Denied { rule: DenyRule::Tag(_), .. })));
+        assert!(err.to_string().contains(ids[1].short()));
+        assert!(push_mgr.push(&ids[2], &remote("origin"), None, false, None).is_err());
+        assert!(backend.state().pushes.is_empty());
+
+        // то, что ниже запрещённой ноды, уйти может
+        assert!(push_mgr.push(&ids[0], &remote("origin"), None, false, None).unwrap().is_some());
+
+        let mut node = graph.get_node(&ids[0]).unwrap();
+        assert!(!rules[0].matches(&node));
+        node.metadata.insert("classification".into(), "secret".into());
+        assert!(rules[0].matches(&node));
+        assert!("meta:classification".parse::<DenyRule>().unwrap().matches(&node));
+        assert!("meta:classification=public".parse::<DenyRule>().is_ok_and(|r| !r.matches(&node)));
+        for bad in ["secret", "tag:", "meta:", "meta:=x", "label:x"] {
+            assert!(bad.parse::<DenyRule>().is_err(), "'{}' should be rejected", bad);
+        }
+    }
+
+    #[test]
+    fn second_push_is_a_noop() {
+        let (mut graph, backend) = mock_graph();
+        let ids = chain(&mut graph, &backend, 2);
+
+        let push_mgr = PushManager::new(&graph, &backend);
+        assert!(push_mgr.push(&ids[1], &remote("origin"), None, false, None).unwrap().is_some());
+        assert!(push_mgr.push(&ids[1], &remote("origin"), None, false, None).unwrap().is_none());
+    }
+
+    #[test]
+    fn recorded_tip_replaces_lost_tracking_refs() {
+        let (mut graph, backend) = mock_graph();
+        let ids = chain(&mut graph, &backend, 3);
+        graph.record_remote_tip(&ids[0], "origin").unwrap();
+        graph.record_remote_tip(&ids[1], "Origin").unwrap();
+        assert_eq!(graph.recorded_remote_tips("origin").unwrap(), vec![ids[1].clone()]);
+
+        // tracking-ссылок у бэкенда нет: уходит только то, что после запомненной вершины
+        let push_mgr = PushManager::new(&graph, &backend);
+        assert_eq!(push_mgr.compute_nodes_to_push(&ids[2], &remote("origin"), &[]).unwrap().len(), 3);
+        assert!(push_mgr.push(&ids[1], &remote("origin"), None, false, None).unwrap().is_none());
+        assert!(push_mgr.push(&ids[2], &remote("origin"), None, false, None).unwrap().is_some());
+        assert_eq!(backend.state().pushes.len(), 1);
+    }
+
+    #[test]
+    fn push_reports_progress() {
+        let (mut graph, backend) = mock_graph();
+        let ids = chain(&mut graph, &backend, 3);
+
+        let events = std::cell::RefCell::new(Vec::new());
+        let report = |p: Progress| events.borrow_mut().push(p);
+        let push_mgr = PushManager::new(&graph, &backend);
+        push_mgr.push(&ids[2], &remote("origin"), None, false, Some(&report)).unwrap();
+
+        let events = events.into_inner();
+        assert!(matches!(events.first(), Some(Progress::Planned { nodes: 3 })));
+        assert!(events.iter().any(|e| matches!(e, Progress::Transfer(_))));
+    }
+
+    #[test]
+    fn transient_network_errors_are_retried_with_backoff() {
+        let (mut graph, backend) = mock_graph();
+        let ids = chain(&mut graph, &backend, 2);
+        backend.state().transient_push_failures = 2;
+
+        let events = std::cell::RefCell::new(Vec::new());
+        let report = |p: Progress| events.borrow_mut().push(p);
+        let retry = RetryPolicy { retries: 2, base_delay: Duration::from_millis(1) };
+        let push_mgr = PushManager::new(&graph, &backend).with_retry(retry);
+        assert!(push_mgr.push(&ids[1], &remote("origin"), None, false, Some(&report)).unwrap().is_some());
+        assert_eq!(backend.state().pushes.len(), 1);
+
+        let delays: Vec<(u32, Duration)> = events
+            .into_inner()
+            .into_iter()
+            .filter_map(|e| match e {
+                Progress::Retry { attempt, delay, .. } => Some((attempt, delay)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(delays, vec![(1, Duration::from_millis(1)), (2, Duration::from_millis(2))]);
+    }
+
+    #[test]
+    fn backoff_is_capped() {
+        let retry = RetryPolicy { retries: 40, base_delay: Duration::from_secs(1) };
+        assert_eq!(retry.delay(3), Duration::from_secs(4));
+        assert_eq!(retry.delay(7), MAX_BACKOFF);
+        assert_eq!(retry.delay(33), MAX_BACKOFF);
+        assert_eq!(retry.delay(40), MAX_BACKOFF);
+    }
+
+    #[test]
+    fn gives_up_after_configured_retries() {
+        let (mut graph, backend) = mock_graph();
+        let ids = chain(&mut graph, &backend, 1);
+        backend.state().transient_push_failures = 2;
+
+        let retry = RetryPolicy { retries: 1, base_delay: Duration::ZERO };
+        let push_mgr = PushManager::new(&graph, &backend).with_retry(retry);
+        let err = push_mgr.push(&ids[0], &remote("origin"), None, false, None).unwrap_err();
+        assert!(matches!(err.downcast_ref::<PushError>(), Some(PushError::Network { .. })));
+        assert!(backend.state().pushes.is_empty());
+    }
+
+    #[test]
+    fn tag_push_creates_tag_ref_even_when_nodes_are_present() {
+        let (mut graph, backend) = mock_graph();
+        let ids = chain(&mut graph, &backend, 2);
+
+        let push_mgr = PushManager::new(&graph, &backend);
+        assert!(push_mgr.push(&ids[1], &remote("origin"), Some("v1.0"), false, None).unwrap().is_some());
+        assert_eq!(
+            backend.state().pushes[0].target_refs,
+            vec!["refs/heads/main".to_string(), "refs/tags/v1.0".to_string()]
+        );
+
+        let outcome = push_mgr.push(&ids[1], &remote("origin"), Some("v1.1"), false, None).unwrap().unwrap();
+        assert_eq!(outcome, PushOutcome { refs: vec!["refs/tags/v1.1".to_string()], tip: ids[1].clone(), plan: None });
+        assert_eq!(backend.state().pushes[1].target_refs, outcome.refs);
+    }
+
+    #[test]
+    fn skip_hooks_reaches_the_backend() {
+        let (mut graph, backend) = mock_graph();
+        let ids = chain(&mut graph, &backend, 2);
+
+        PushManager::new(&graph, &backend).push(&ids[0], &remote("origin"), None, false, None).unwrap();
+        PushManager::new(&graph, &backend).with_skip_hooks(true).push(&ids[1], &remote("origin"), None, false, None).unwrap();
+        let skipped: Vec<bool> = backend.state().pushes.iter().map(|p| p.skip_hooks).collect();
+        assert_eq!(skipped, vec![false, true]);
+    }
+}
@@ -0,0 +1,66 @@
+// Ревизии в стиле git: полный id или его префикс, имя тега, HEAD (или @), "-" (предыдущий HEAD)
+// и суффиксы ~N (N-й предок по первым родителям) и ^N (N-й родитель). Разрешает их VersionGraph::resolve_rev.
+use crate::types::NodeId;
+
+/// Минимальная длина префикса id, как в git
+pub(crate) const MIN_PREFIX_LEN: usize = 4;
+
+/// HEAD и предыдущий HEAD живут вне графа (в .gitpp), поэтому их передаёт вызывающий
+#[derive(Debug, Clone, Default)]
+pub struct RevContext {
+    pub head: Option<NodeId>,
+    pub previous_head: Option<NodeId>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum RevStep {
+    /// ~N: N раз к первому родителю
+    Ancestor(usize),
+    /// ^N: N-й родитель (^0 - сама нода)
+    Parent(usize),
+}
+
+/// Делит ревизию на основу и цепочку суффиксов: "v1.0~2^2" -> ("v1.0", [Ancestor(2), Parent(2)])
+pub(crate) fn split_rev(rev: &str) -> Result<(&str, Vec<RevStep>), String> {
+    let split_at = rev.find(['~', '^']).unwrap_or(rev.len());
+    let (base, mut rest) = rev.split_at(split_at);
+    if base.is_empty() {
+        return Err(format!("Invalid revision '{}'", rev));
+    }
+
+    let mut steps = Vec::new();
+    while let Some(op) = rest.chars().next() {
+        rest = &rest[1..];
+        let digits = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+        // "~" и "^" без числа значат 1
+        let n = if digits == 0 {
+            1
+        } else {
+            rest[..digits].parse().map_err(|_| format!("Invalid revision '{}'", rev))?
+        };
+        rest = &rest[digits..];
+        steps.push(match op {
+            '~' => RevStep::Ancestor(n),
+            '^' => RevStep::Parent(n),
+            _ => return Err(format!("Invalid revision '{}'", rev)),
+        });
+    }
+    Ok((base, steps))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_suffix_chain() {
+        assert_eq!(split_rev("HEAD").unwrap(), ("HEAD", vec![]));
+        assert_eq!(split_rev("HEAD~2").unwrap(), ("HEAD", vec![RevStep::Ancestor(2)]));
+        assert_eq!(
+            split_rev("v1.0^^2~").unwrap(),
+            ("v1.0", vec![RevStep::Parent(1), RevStep::Parent(2), RevStep::Ancestor(1)])
+        );
+        assert!(split_rev("~1").is_err());
+        assert!(split_rev("HEAD~x").is_err());
+    }
+}
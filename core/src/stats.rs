@@ -0,0 +1,158 @@
+// gpp stats - сводка по графу. Сделана встроенным плагином, чтобы путь Plugin -> Command::Custom
+// был рабочим, а не только объявленным.
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::error::Error;
+
+use serde::Serialize;
+
+use crate::dispatcher::{CmdResult, CommandHandler};
+use crate::plugins::Plugin;
+use crate::types::{Node, NodeId};
+use crate::version_graph::VersionGraph;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct GraphStats {
+    pub nodes: usize,
+    pub roots: usize,
+    /// Ноды без детей
+    pub tips: usize,
+    pub merges: usize,
+    /// Число нод на самом длинном пути от корня до вершины
+    pub max_depth: usize,
+    /// Среднее число детей у нод, у которых они есть
+    pub branching_factor: f64,
+    /// Авторы и соавторы, без повторов
+    pub authors: usize,
+    pub nodes_per_remote: BTreeMap<String, usize>,
+}
+
+impl GraphStats {
+    /// Только чтение: все ноды, достижимые от корней
+    pub fn collect(graph: &VersionGraph) -> Result<Self, Box<dyn Error>> {
+        let roots = graph.list_roots()?;
+        let nodes = graph.load_all_nodes()?;
+
+        let mut stats = GraphStats { nodes: nodes.len(), roots: roots.len(), ..Default::default() };
+        let mut authors = HashSet::new();
+        let mut children_total = 0;
+        let mut with_children = 0;
+        for node in nodes.values() {
+            if node.children.is_empty() {
+                stats.tips += 1;
+            } else {
+                with_children += 1;
+                children_total += node.children.len();
+            }
+            if node.parents.len() > 1 {
+                stats.merges += 1;
+            }
+            for remote in &node.remotes {
+                *stats.nodes_per_remote.entry(remote.name.clone()).or_default() += 1;
+            }
+            authors.insert(node.author.to_string());
+            authors.extend(node.co_authors.iter().map(|a| a.to_string()));
+        }
+        stats.authors = authors.len();
+        if with_children > 0 {
+            stats.branching_factor = children_total as f64 / with_children as f64;
+        }
+
+        let mut memo = HashMap::new();
+        stats.max_depth = nodes.keys().map(|id| depth(id, &nodes, &mut memo)).max().unwrap_or(0);
+        Ok(stats)
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = vec![
+            format!("Nodes:            {}", self.nodes),
+            format!("Roots:            {}", self.roots),
+            format!("Tips:             {}", self.tips),
+            format!("Merges:           {}", self.merges),
+            format!("Max depth:        {}", self.max_depth),
+            format!("Branching factor: {:.2}", self.branching_factor),
+            format!("Authors:          {}", self.authors),
+        ];
+        if !self.nodes_per_remote.is_empty() {
+            out.push("Nodes per remote:".to_string());
+            let width = self.nodes_per_remote.keys().map(|k| k.len()).max().unwrap_or(0);
+            for (remote, count) in &self.nodes_per_remote {
+                out.push(format!("  {:<width$}  {}", remote, count, width = width));
+            }
+        }
+        out.join("\n")
+    }
+}
+
+/// Длина самого длинного пути от корня до ноды (в нодах)
+fn depth(id: &NodeId, nodes: &HashMap<NodeId, Node>, memo: &mut HashMap<NodeId, usize>) -> usize {
+    if let Some(d) = memo.get(id) {
+        return *d;
+    }
+    let parents = nodes.get(id).map(|n| n.parents.clone()).unwrap_or_default();
+    let d = 1 + parents.iter().map(|p| depth(p, nodes, memo)).max().unwrap_or(0);
+    memo.insert(id.clone(), d);
+    d
+}
+
+pub struct StatsPlugin;
+
+impl Plugin for StatsPlugin {
+    fn name(&self) -> &str {
+        "stats"
+    }
+
+    fn description(&self) -> &str {
+        "Graph statistics: nodes, roots, tips, depth, branching, remotes, authors"
+    }
+
+    fn create_handler(&self) -> Box<dyn CommandHandler> {
+        Box::new(StatsHandler)
+    }
+}
+
+struct StatsHandler;
+
+impl CommandHandler for StatsHandler {
+    fn execute(&self, args: &[String], graph: &mut VersionGraph) -> Result<CmdResult, Box<dyn Error>> {
+        let stats = GraphStats::collect(graph)?;
+        if args.iter().any(|a| a == "--json") {
+            Ok(CmdResult::Output(serde_json::to_string_pretty(&stats)?))
+        } else {
+            Ok(CmdResult::Output(stats.render()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{mock_graph, test_author};
+    use crate::types::{Author, RemoteRef};
+
+    #[test]
+    fn counts_shape_of_the_graph() {
+        let (mut graph, backend) = mock_graph();
+        backend.set_worktree("1");
+        let root = graph.add_node(vec![], test_author(), vec![], "root".into(), None).unwrap();
+        backend.set_worktree("2");
+        let left = graph.add_node(vec![root.clone()], test_author(), vec![], "left".into(), None).unwrap();
+        backend.set_worktree("3");
+        let bob = Author { name: "Bob".into(), email: "bob@example.com".into() };
+        let right = graph.add_node(vec![root.clone()], test_author(), vec![bob], "right".into(), None).unwrap();
+        backend.set_worktree("4");
+        graph.add_node(vec![left, right.clone()], test_author(), vec![], "merge".into(), None).unwrap();
+        let work = RemoteRef::new("work", "w").unwrap();
+        graph.add_remote_permission(&right, work, false).unwrap();
+
+        let stats = GraphStats::collect(&graph).unwrap();
+        assert_eq!(stats.nodes, 4);
+        assert_eq!(stats.roots, 1);
+        assert_eq!(stats.tips, 1);
+        assert_eq!(stats.merges, 1);
+        assert_eq!(stats.max_depth, 3);
+        assert_eq!(stats.branching_factor, 4.0 / 3.0);
+        assert_eq!(stats.authors, 2);
+        assert_eq!(stats.nodes_per_remote["origin"], 4);
+        assert_eq!(stats.nodes_per_remote["work"], 1);
+    }
+}
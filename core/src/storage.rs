@@ -1,6 +1,10 @@
 use crate::types::{Node, NodeId};
 use thiserror::Error;
 
+/// Версия формата хранилища графа. Поднимается при любом изменении схемы Node, которое старый код
+/// прочитал бы неправильно. 0 - репозитории, созданные до появления версии.
+pub const FORMAT_VERSION: u32 = 2;
+
 #[derive(Debug, Error)]
 pub enum StorageError {
     #[error("IO error: {0}")]
@@ -9,6 +13,21 @@ pub enum StorageError {
     #[error("Serde error: {0}")]
     Serde(#[from] serde_json::Error),
 
+    #[error("Corrupt graph file {}: {source} (line {line}, column {column}, byte {offset})", path.display())]
+    Corrupt {
+        path: std::path::PathBuf,
+        line: usize,
+        column: usize,
+        offset: usize,
+        source: serde_json::Error,
+    },
+
+    #[error("Repository format {found} is newer than this gpp supports (up to {supported}), upgrade gpp")]
+    UnsupportedFormat { found: u32, supported: u32 },
+
+    #[error("{} was changed by another gpp process since it was loaded, nothing was written. Re-run the command", .0.display())]
+    Conflict(std::path::PathBuf),
+
     #[error("Unknown node {0:?}")]
     NodeNotFound(NodeId),
 
@@ -30,9 +49,65 @@ pub trait GraphStorage {
 
     fn list_roots(&self) -> Result<Vec<NodeId>>;
 
+    /// Последняя нода, запушенная на ремоут. remote - уже remote_name_key, а не написание пользователя.
+    fn remote_tip(&self, remote: &str) -> Result<Option<NodeId>>;
+
+    /// Запоминает вершину ремоута; как и persist_node, на диск попадает с commit_tx
+    fn set_remote_tip(&mut self, remote: &str, tip: &NodeId) -> Result<()>;
+
     fn begin_tx(&self) -> Result<TxHandle>;
 
     fn commit_tx(&self, tx: TxHandle) -> Result<()>;
 
     fn rollback_tx(&self, tx: TxHandle) -> Result<()>;
-}
\ No newline at end of file
+}
+
+/// Транзакция над GraphStorage, которую нельзя забыть закрыть: если commit() не вызван
+/// (ранний return, `?` посреди записи), Drop её откатывает. Провалившийся commit тоже откатывается.
+pub struct Transaction<'a> {
+    storage: &'a mut dyn GraphStorage,
+    handle: Option<TxHandle>,
+}
+
+impl<'a> Transaction<'a> {
+    pub fn begin(storage: &'a mut dyn GraphStorage) -> Result<Self> {
+        let handle = storage.begin_tx()?;
+        Ok(Self { storage, handle: Some(handle) })
+    }
+
+    pub fn load_node(&self, id: &NodeId) -> Result<Node> {
+        self.storage.load_node(id)
+    }
+
+    pub fn persist_node(&mut self, node: &Node) -> Result<()> {
+        self.storage.persist_node(node)
+    }
+
+    pub fn set_remote_tip(&mut self, remote: &str, tip: &NodeId) -> Result<()> {
+        self.storage.set_remote_tip(remote, tip)
+    }
+
+    pub fn commit(mut self) -> Result<()> {
+        let handle = self.handle.take().expect("transaction is open until commit or rollback");
+        if let Err(err) = self.storage.commit_tx(handle.clone()) {
+            // ошибка отката тут вторична, наружу уходит причина
+            let _ = self.storage.rollback_tx(handle);
+            return Err(err);
+        }
+        Ok(())
+    }
+
+    /// Явный откат, когда его ошибка важна. Иначе достаточно просто бросить транзакцию.
+    pub fn rollback(mut self) -> Result<()> {
+        let handle = self.handle.take().expect("transaction is open until commit or rollback");
+        self.storage.rollback_tx(handle)
+    }
+}
+
+impl Drop for Transaction<'_> {
+    fn drop(&mut self) {
+        if let Some(handle) = self.handle.take() {
+            let _ = self.storage.rollback_tx(handle);
+        }
+    }
+}
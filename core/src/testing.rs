@@ -0,0 +1,438 @@
+// Тестовые двойники для core: бэкенд и хранилище целиком в памяти, без git и файлов.
+// Доступны в юнит-тестах core и снаружи через фичу `test-util`.
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeSet, HashMap};
+use std::error::Error;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, FixedOffset};
+
+use crate::backend::{DiffStat, HistoryEntry, Progress, RepoBackend, SignatureStatus};
+use crate::dispatcher::{build_dispatcher, CommandDispatcher};
+use crate::push_manager::PushError;
+use crate::storage::{GraphStorage, Result, StorageError, TxHandle};
+use crate::types::{Author, Node, NodeId, RemoteRef};
+use crate::version_graph::VersionGraph;
+
+pub fn test_author() -> Author {
+    Author { name: "Tester".into(), email: "tester@example.com".into() }
+}
+
+/// VersionGraph поверх MemoryStorage и MockRepoBackend, плюс ручка на состояние бэкенда
+pub fn mock_graph() -> (VersionGraph, MockRepoBackend) {
+    let backend = MockRepoBackend::new();
+    let graph = VersionGraph::new(Box::new(MemoryStorage::new()), Box::new(backend.clone()));
+    (graph, backend)
+}
+
+/// То же для команд целиком: dispatcher собран так же, как в CLI, только без git и graph.json
+pub fn mock_dispatcher() -> (CommandDispatcher, MockRepoBackend) {
+    let backend = MockRepoBackend::new();
+    let dispatcher = build_dispatcher(backend.clone(), Box::new(MemoryStorage::new()));
+    (dispatcher, backend)
+}
+
+#[derive(Debug, Clone)]
+pub struct MockCommit {
+    pub tree_id: String,
+    pub parents: Vec<NodeId>,
+    pub message: String,
+    pub timestamp: DateTime<FixedOffset>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MockPush {
+    pub remote: String,
+    pub url: String,
+    pub tip: NodeId,
+    pub target_refs: Vec<String>,
+    pub skip_hooks: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct MockState {
+    /// Содержимое "рабочей директории", из которого строится дерево
+    pub worktree: String,
+    pub trees: HashMap<String, String>,
+    pub commits: HashMap<String, MockCommit>,
+    pub refs: HashMap<String, NodeId>,
+    pub pushes: Vec<MockPush>,
+    pub contexts: BTreeSet<String>,
+    pub active_context: Option<String>,
+    /// Какие коммиты были скопированы в какой контекст
+    pub replicated: Vec<(String, NodeId)>,
+    pub checked_out: Option<NodeId>,
+    /// Что отдаёт changed_paths: в моке нет файлов, чтобы вычислять это честно
+    pub changed_paths: Vec<String>,
+    /// Столько следующих push_update_ref упадут с PushError::Network
+    pub transient_push_failures: usize,
+    /// Подписи коммитов по id; чего здесь нет - не подписано
+    pub signatures: HashMap<String, SignatureStatus>,
+}
+
+/// RepoBackend без git: объекты и ссылки лежат в памяти.
+/// Клоны разделяют состояние, так что тест может отдать один экземпляр в VersionGraph и смотреть через другой.
+#[derive(Debug, Clone, Default)]
+pub struct MockRepoBackend {
+    state: Arc<Mutex<MockState>>,
+    /// Контекст хендла из with_context; состояние при этом общее
+    context: Option<String>,
+}
+
+fn short_hash<T: Hash>(value: &T) -> String {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+impl MockRepoBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Меняет "рабочую директорию", следующий create_tree даст другое дерево
+    pub fn set_worktree(&self, content: &str) {
+        self.state().worktree = content.to_string();
+    }
+
+    pub fn set_ref(&self, refname: &str, id: &NodeId) {
+        self.state().refs.insert(refname.to_string(), id.clone());
+    }
+
+    pub fn state(&self) -> std::sync::MutexGuard<'_, MockState> {
+        self.state.lock().expect("mock state poisoned")
+    }
+}
+
+impl RepoBackend for MockRepoBackend {
+    fn read_ref(&self, refname: &str) -> std::result::Result<Option<NodeId>, Box<dyn Error>> {
+        Ok(self.state().refs.get(refname).cloned())
+    }
+
+    fn create_tree(&self) -> std::result::Result<String, Box<dyn Error>> {
+        let mut state = self.state();
+        let content = state.worktree.clone();
+        let tree_id = short_hash(&("tree", &content));
+        state.trees.insert(tree_id.clone(), content);
+        Ok(tree_id)
+    }
+
+    fn create_tree_isolated(&self) -> std::result::Result<String, Box<dyn Error>> {
+        // индекса у мока нет, деревья те же
+        self.create_tree()
+    }
+
+    fn create_tree_from_paths(&self, base_tree: Option<&str>, paths: &[String]) -> std::result::Result<String, Box<dyn Error>> {
+        let mut state = self.state();
+        let base = match base_tree {
+            Some(tree) => state.trees.get(tree).cloned().ok_or_else(|| format!("Unknown tree {}", tree))?,
+            None => String::new(),
+        };
+        // файлов в моке нет: дерево = база + какие пути взяты из текущей "рабочей директории"
+        let content = format!("{}\n{} <- {}", base, paths.join(" "), state.worktree);
+        let tree_id = short_hash(&("tree", &content));
+        state.trees.insert(tree_id.clone(), content);
+        Ok(tree_id)
+    }
+
+    fn changed_paths(&self, _base_tree: Option<&str>) -> std::result::Result<Vec<String>, Box<dyn Error>> {
+        Ok(self.state().changed_paths.clone())
+    }
+
+    fn diff_stat(&self, tree_a: Option<&str>, tree_b: &str) -> std::result::Result<DiffStat, Box<dyn Error>> {
+        // "файл" в моке один - вся рабочая директория, строки считаем целиком
+        let state = self.state();
+        let content = |tree: &str| state.trees.get(tree).cloned().ok_or_else(|| format!("Unknown tree {}", tree));
+        let before = tree_a.map(content).transpose()?.unwrap_or_default();
+        let after = content(tree_b)?;
+        if before == after {
+            return Ok(DiffStat::default());
+        }
+        Ok(DiffStat { files: 1, insertions: after.lines().count(), deletions: before.lines().count() })
+    }
+
+    fn restore_tree(&self, tree_id: &str) -> std::result::Result<(), Box<dyn Error>> {
+        let mut state = self.state();
+        let content = state.trees.get(tree_id).cloned().ok_or_else(|| format!("Unknown tree {}", tree_id))?;
+        state.worktree = content;
+        Ok(())
+    }
+
+    fn create_commit(
+        &self,
+        tree_oid: &str,
+        parents: &[NodeId],
+        message: &str,
+        _author: &Author,
+        timestamp: DateTime<FixedOffset>,
+    ) -> std::result::Result<NodeId, Box<dyn Error>> {
+        let mut state = self.state();
+        if !state.trees.contains_key(tree_oid) {
+            return Err(format!("Unknown tree {}", tree_oid).into());
+        }
+        let id = short_hash(&("commit", tree_oid, parents, message));
+        state.commits.insert(id.clone(), MockCommit {
+            tree_id: tree_oid.to_string(),
+            parents: parents.to_vec(),
+            message: message.to_string(),
+            timestamp,
+        });
+        state.refs.insert("HEAD".to_string(), NodeId(id.clone()));
+        Ok(NodeId(id))
+    }
+
+    fn replicate_commit(&self, commit_id: &NodeId, remotes: &[RemoteRef]) -> std::result::Result<(), Box<dyn Error>> {
+        let mut state = self.state();
+        for remote in remotes {
+            if state.contexts.contains(&remote.name) {
+                state.replicated.push((remote.name.clone(), commit_id.clone()));
+            }
+        }
+        Ok(())
+    }
+
+    fn discard_commit(&self, commit_id: &NodeId, parents: &[NodeId], _remotes: &[RemoteRef]) -> std::result::Result<(), Box<dyn Error>> {
+        let mut state = self.state();
+        if state.refs.get("HEAD") == Some(commit_id) {
+            match parents.first() {
+                Some(parent) => state.refs.insert("HEAD".to_string(), parent.clone()),
+                None => state.refs.remove("HEAD"),
+            };
+        }
+        state.replicated.retain(|(_, id)| id != commit_id);
+        Ok(())
+    }
+
+    fn verify_signature(&self, id: &NodeId) -> std::result::Result<SignatureStatus, Box<dyn Error>> {
+        let state = self.state();
+        if !state.commits.contains_key(&id.0) {
+            return Err(format!("Unknown commit {}", id.0).into());
+        }
+        Ok(state.signatures.get(&id.0).cloned().unwrap_or(SignatureStatus::Unsigned))
+    }
+
+    fn commit_parents(&self, id: &NodeId) -> std::result::Result<Option<Vec<NodeId>>, Box<dyn Error>> {
+        Ok(self.state().commits.get(&id.0).map(|c| c.parents.clone()))
+    }
+
+    fn remote_tips(&self, remote_name: &str) -> std::result::Result<Vec<NodeId>, Box<dyn Error>> {
+        let prefix = format!("refs/remotes/{}/", remote_name);
+        Ok(self.state().refs.iter()
+            .filter(|(name, _)| name.starts_with(&prefix))
+            .map(|(_, id)| id.clone())
+            .collect())
+    }
+
+    fn push_update_ref(
+        &self,
+        remote: &RemoteRef,
+        local_tip_id: &NodeId,
+        remote_target_refs: &[String],
+        skip_hooks: bool,
+        progress: Option<&dyn Fn(Progress)>,
+    ) -> std::result::Result<(), Box<dyn Error>> {
+        if let Some(report) = progress {
+            report(Progress::Transfer(format!("Writing objects: 100% ({})", local_tip_id.short())));
+        }
+        let mut state = self.state();
+        if state.transient_push_failures > 0 {
+            state.transient_push_failures -= 1;
+            let message = "Could not resolve host: example.com".to_string();
+            return Err(Box::new(PushError::Network { remote: remote.name.clone(), message }));
+        }
+        if !state.commits.contains_key(&local_tip_id.0) {
+            return Err(format!("Unknown commit {}", local_tip_id.0).into());
+        }
+        state.contexts.insert(remote.name.clone());
+        for target in remote_target_refs.iter().filter(|r| r.starts_with("refs/heads/")) {
+            let tracking = target.replacen("refs/heads/", &format!("refs/remotes/{}/", remote.name), 1);
+            state.refs.insert(tracking, local_tip_id.clone());
+        }
+        state.pushes.push(MockPush {
+            remote: remote.name.clone(),
+            url: remote.url.clone(),
+            tip: local_tip_id.clone(),
+            target_refs: remote_target_refs.to_vec(),
+            skip_hooks,
+        });
+        Ok(())
+    }
+
+    fn is_repo_empty(&self) -> std::result::Result<bool, Box<dyn Error>> {
+        Ok(!self.state().refs.contains_key("HEAD"))
+    }
+
+    fn checkout_context(&self, node: &Node) -> std::result::Result<String, Box<dyn Error>> {
+        if !self.state().commits.contains_key(&node.commit_id().0) {
+            return Err(format!("Node {} is not materialized in any initialized context", node.id.short()).into());
+        }
+        Ok(node.preferred_context().unwrap_or("origin").to_string())
+    }
+
+    fn checkout_node(&self, node: &Node, context: &str) -> std::result::Result<(), Box<dyn Error>> {
+        self.switch_context(context)?;
+        self.state().checked_out = Some(node.id.clone());
+        Ok(())
+    }
+
+    fn switch_context(&self, name: &str) -> std::result::Result<(), Box<dyn Error>> {
+        if let Some(pinned) = &self.context {
+            return Err(format!("Backend is pinned to context '{}', cannot switch to '{}'", pinned, name).into());
+        }
+        let mut state = self.state();
+        state.contexts.insert(name.to_string());
+        state.active_context = Some(name.to_string());
+        Ok(())
+    }
+
+    fn with_context(&self, name: &str) -> std::result::Result<Box<dyn RepoBackend + Send>, Box<dyn Error>> {
+        self.state().contexts.insert(name.to_string());
+        Ok(Box::new(Self { state: self.state.clone(), context: Some(name.to_string()) }))
+    }
+
+    fn list_history(&self) -> std::result::Result<Vec<HistoryEntry>, Box<dyn Error>> {
+        let state = self.state();
+        let mut ids: Vec<&String> = state.commits.keys().collect();
+        ids.sort();
+
+        // простая топосортировка: коммит выдаём, когда все его родители уже выданы
+        let mut emitted: Vec<HistoryEntry> = Vec::new();
+        while emitted.len() < ids.len() {
+            let before = emitted.len();
+            for id in &ids {
+                let commit = &state.commits[*id];
+                let done = |p: &NodeId| emitted.iter().any(|e| &e.id == p);
+                if !done(&NodeId((*id).clone())) && commit.parents.iter().all(done) {
+                    emitted.push(HistoryEntry {
+                        id: NodeId((*id).clone()),
+                        parents: commit.parents.clone(),
+                        tree_id: commit.tree_id.clone(),
+                        message: commit.message.clone(),
+                        author: test_author(),
+                        created_at: commit.timestamp,
+                    });
+                }
+            }
+            if emitted.len() == before {
+                return Err("Mock history references unknown parents".into());
+            }
+        }
+        Ok(emitted)
+    }
+
+    fn list_contexts(&self) -> std::result::Result<Vec<String>, Box<dyn Error>> {
+        Ok(self.state().contexts.iter().cloned().collect())
+    }
+
+    fn active_context(&self) -> std::result::Result<Option<String>, Box<dyn Error>> {
+        if let Some(pinned) = &self.context {
+            return Ok(Some(pinned.clone()));
+        }
+        Ok(self.state().active_context.clone())
+    }
+}
+
+/// Ноды и вершины ремоутов на момент begin_tx
+type Saved = (HashMap<NodeId, Node>, HashMap<String, NodeId>);
+
+/// GraphStorage в памяти. begin_tx снимает копию карты, rollback_tx её восстанавливает.
+#[derive(Debug, Default)]
+pub struct MemoryStorage {
+    nodes: Mutex<HashMap<NodeId, Node>>,
+    remote_tips: Mutex<HashMap<String, NodeId>>,
+    snapshot: Mutex<Option<Saved>>,
+    /// Сколько commit_tx пройдут успешно, дальше - ошибка. None - без ограничений.
+    commits_left: Mutex<Option<usize>>,
+    /// То же для persist_node
+    persists_left: Mutex<Option<usize>>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Хранилище, у которого commit_tx ломается после `n` успешных коммитов (проверка отката)
+    pub fn failing_after(n: usize) -> Self {
+        Self { commits_left: Mutex::new(Some(n)), ..Self::default() }
+    }
+
+    /// Хранилище, у которого persist_node ломается после `n` успешных записей (сбой посреди транзакции)
+    pub fn failing_persist_after(n: usize) -> Self {
+        Self { persists_left: Mutex::new(Some(n)), ..Self::default() }
+    }
+
+    fn nodes(&self) -> Result<std::sync::MutexGuard<'_, HashMap<NodeId, Node>>> {
+        self.nodes.lock().map_err(|_| StorageError::Tx("Lock poisoned".into()))
+    }
+
+    fn remote_tips(&self) -> Result<std::sync::MutexGuard<'_, HashMap<String, NodeId>>> {
+        self.remote_tips.lock().map_err(|_| StorageError::Tx("Lock poisoned".into()))
+    }
+
+    fn snapshot(&self) -> Result<std::sync::MutexGuard<'_, Option<Saved>>> {
+        self.snapshot.lock().map_err(|_| StorageError::Tx("Lock poisoned".into()))
+    }
+}
+
+impl GraphStorage for MemoryStorage {
+    fn persist_node(&mut self, node: &Node) -> Result<()> {
+        let mut left = self.persists_left.lock().map_err(|_| StorageError::Tx("Lock poisoned".into()))?;
+        match left.as_mut() {
+            Some(0) => return Err(StorageError::Tx("Simulated persist failure".into())),
+            Some(n) => *n -= 1,
+            None => {}
+        }
+        drop(left);
+        self.nodes()?.insert(node.id.clone(), node.clone());
+        Ok(())
+    }
+
+    fn load_node(&self, id: &NodeId) -> Result<Node> {
+        self.nodes()?
+            .get(id)
+            .cloned()
+            .ok_or_else(|| StorageError::NodeNotFound(id.clone()))
+    }
+
+    fn list_roots(&self) -> Result<Vec<NodeId>> {
+        Ok(self.nodes()?.values()
+            .filter(|n| n.parents.is_empty())
+            .map(|n| n.id.clone())
+            .collect())
+    }
+
+    fn remote_tip(&self, remote: &str) -> Result<Option<NodeId>> {
+        Ok(self.remote_tips()?.get(remote).cloned())
+    }
+
+    fn set_remote_tip(&mut self, remote: &str, tip: &NodeId) -> Result<()> {
+        self.remote_tips()?.insert(remote.to_string(), tip.clone());
+        Ok(())
+    }
+
+    fn begin_tx(&self) -> Result<TxHandle> {
+        *self.snapshot()? = Some((self.nodes()?.clone(), self.remote_tips()?.clone()));
+        Ok(TxHandle { path: Default::default() })
+    }
+
+    fn commit_tx(&self, _tx: TxHandle) -> Result<()> {
+        let mut left = self.commits_left.lock().map_err(|_| StorageError::Tx("Lock poisoned".into()))?;
+        match left.as_mut() {
+            Some(0) => return Err(StorageError::Tx("Simulated commit failure".into())),
+            Some(n) => *n -= 1,
+            None => {}
+        }
+        *self.snapshot()? = None;
+        Ok(())
+    }
+
+    fn rollback_tx(&self, _tx: TxHandle) -> Result<()> {
+        if let Some((nodes, remote_tips)) = self.snapshot()?.take() {
+            *self.nodes()? = nodes;
+            *self.remote_tips()? = remote_tips;
+        }
+        Ok(())
+    }
+}
@@ -1,51 +1,227 @@
-use serde::{Serialize, Deserialize};
-use std::collections::{HashMap, HashSet};
+use serde::{Serialize, Serializer, Deserialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
 use std::hash::{Hash, Hasher};
-use chrono::{DateTime, Utc};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::OnceLock;
+use chrono::{DateTime, FixedOffset, Offset, Utc};
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub struct NodeId(pub String);
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
-pub struct CommitId(pub String);
+/// Хеш коммита хранится в `Node::commit` как тот же NodeId
+#[deprecated(note = "use NodeId")]
+pub type CommitId = NodeId;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Длина короткого id по умолчанию, как у git
+pub const DEFAULT_ABBREV_LEN: usize = 7;
+
+static ABBREV_LEN: OnceLock<usize> = OnceLock::new();
+
+/// Длина короткого id на весь процесс (ui.abbrev-len). Как и messages::set_lang, задаётся один раз при старте.
+pub fn set_abbrev_len(len: usize) {
+    let _ = ABBREV_LEN.set(len);
+}
+
+pub fn abbrev_len() -> usize {
+    ABBREV_LEN.get().copied().unwrap_or(DEFAULT_ABBREV_LEN)
+}
+
+/// Первые len символов id. Id короче len (не git-овый) возвращается целиком, без паники.
+pub fn short_id(id: &NodeId, len: usize) -> &str {
+    let end = id.0.char_indices().nth(len).map(|(i, _)| i).unwrap_or(id.0.len());
+    &id.0[..end]
+}
+
+impl NodeId {
+    /// Короткая форма id для сообщений, лога и GUI, длиной abbrev_len()
+    pub fn short(&self) -> &str {
+        short_id(self, abbrev_len())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Author {
     pub name: String,
     pub email: String,
 }
 
+impl fmt::Display for Author {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} <{}>", self.name, self.email)
+    }
+}
+
+impl FromStr for Author {
+    type Err = String;
+
+    /// Формат как в git: "Имя Фамилия <email>"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid author '{}': expected \"Name <email>\"", s);
+        let (name, rest) = s.trim().split_once('<').ok_or_else(invalid)?;
+        let email = rest.strip_suffix('>').ok_or_else(invalid)?.trim();
+        let name = name.trim();
+        if name.is_empty() || email.is_empty() {
+            return Err(invalid());
+        }
+        Ok(Author { name: name.to_string(), email: email.to_string() })
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NodePayload {
     pub tree_id: String,
 }
 
 
+/// Имена ремоутов регистронезависимы: `Origin` и `origin` - один ремоут. Иначе получились бы
+/// контексты `.git_Origin` и `.git_origin`, которые на macOS/Windows вообще одна папка.
+/// В графе хранится написание, с которым ремоут появился первым.
+pub fn remote_name_key(name: &str) -> String {
+    name.to_lowercase()
+}
+
+/// Схемы URL, которые git понимает сам (без remote helper-ов)
+const URL_SCHEMES: &[&str] = &["ssh", "git", "git+ssh", "ssh+git", "http", "https", "ftp", "ftps", "file"];
+
+/// Грубая проверка, что git примет url как адрес ремоута: `scheme://...`, `<transport>::<адрес>`,
+/// scp-вид `[user@]host:path` или существующий локальный путь. Ловит опечатки до похода в сеть.
+pub fn validate_remote_url(url: &str) -> Result<(), String> {
+    let url = url.trim();
+    let hint = "expected git@host:path, https://host/path, file:///path or an existing local path";
+    if url.is_empty() {
+        return Err(format!("Remote URL is empty ({})", hint));
+    }
+    if std::path::Path::new(url).exists() {
+        return Ok(());
+    }
+    if let Some((scheme, rest)) = url.split_once("://") {
+        if !URL_SCHEMES.contains(&scheme.to_lowercase().as_str()) {
+            return Err(format!("Unsupported URL scheme '{}' in '{}' ({})", scheme, url, hint));
+        }
+        if rest.is_empty() || rest.contains(char::is_whitespace) {
+            return Err(format!("Malformed URL '{}' ({})", url, hint));
+        }
+        return Ok(());
+    }
+    // ext::cmd, persistent-https::... - адрес разбирает сам helper
+    if let Some((transport, _)) = url.split_once("::") {
+        if !transport.is_empty() && transport.chars().all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c)) {
+            return Ok(());
+        }
+    }
+    if let Some((host, path)) = url.split_once(':') {
+        if !host.is_empty() && !host.contains(['/', '\\']) && !path.is_empty() && !url.contains(char::is_whitespace) {
+            return Ok(());
+        }
+    }
+    if url.contains(['/', '\\']) || url.starts_with('.') {
+        return Err(format!("Local path '{}' does not exist", url));
+    }
+    Err(format!("'{}' does not look like a git remote URL ({})", url, hint))
+}
+
+/// Каталог, на который указывает локальный URL: `file://...` или существующий путь (относительно base).
+/// ssh/https/scp-вид и прочие сетевые адреса - None.
+pub fn local_remote_path(url: &str, base: &Path) -> Option<PathBuf> {
+    let url = url.trim();
+    if let Some(path) = url.strip_prefix("file://") {
+        return Some(PathBuf::from(path));
+    }
+    if url.is_empty() || url.contains("://") {
+        return None;
+    }
+    let path = base.join(url);
+    path.exists().then_some(path)
+}
+
+/// Локальный путь становится абсолютным: git запускается из корня репозитория, а `../mirror.git`
+/// пользователь писал относительно своей папки. file:// и сетевые URL не трогаются.
+pub fn normalize_remote_url(url: &str, base: &Path) -> String {
+    match local_remote_path(url, base) {
+        Some(path) if !url.trim().starts_with("file://") => {
+            path.canonicalize().map_or_else(|_| url.to_string(), |p| p.to_string_lossy().into_owned())
+        }
+        _ => url.to_string(),
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RemoteRef {
     pub name: String,
     pub url: String,
+    #[serde(serialize_with = "sorted_map")]
     pub specs: HashMap<String, String>,
 }
 
+impl RemoteRef {
+    /// Ремоут с проверенным именем и обязательным URL (chrm, push, clone)
+    pub fn new(name: impl Into<String>, url: impl Into<String>) -> Result<Self, String> {
+        let remote = Self::without_url(name)?;
+        let url = url.into().trim().to_string();
+        if url.is_empty() {
+            return Err(format!("Remote '{}' needs a URL", remote.name));
+        }
+        Ok(Self { url, ..remote })
+    }
+
+    /// Право на ремоут, URL которого ещё неизвестен: корневые ноды, origin по умолчанию
+    pub fn without_url(name: impl Into<String>) -> Result<Self, String> {
+        let name = name.into().trim().to_string();
+        // имя уходит в имя папки контекста (.git_<name>) и в refs/remotes/<name>/, отсюда и набор символов
+        let valid_char = |c: char| c.is_ascii_alphanumeric() || "-_.".contains(c);
+        if name.is_empty() || name.starts_with(['-', '.']) || name.ends_with('.') || !name.chars().all(valid_char) {
+            return Err(format!("Invalid remote name '{}': use letters, digits, '-', '_' and '.'", name));
+        }
+        Ok(Self { name, url: String::new(), specs: HashMap::new() })
+    }
+
+    /// Добавляет refspec-подобную настройку, для цепочек: `RemoteRef::new(..)?.with_spec(k, v)`
+    pub fn with_spec(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.specs.insert(key.into(), value.into());
+        self
+    }
+}
+
 impl PartialEq for RemoteRef {
     fn eq(&self, other: &Self) -> bool {
-        self.name == other.name && self.url == other.url
+        remote_name_key(&self.name) == remote_name_key(&other.name) && self.url == other.url
     }
 }
 impl Eq for RemoteRef {}
 
 impl Hash for RemoteRef {
     fn hash<H: Hasher>(&self, state: &mut H) {
-        self.name.hash(state);
+        remote_name_key(&self.name).hash(state);
         self.url.hash(state);
     }
 }
 
+// Хеш-коллекции пишем отсортированными, чтобы graph.json был стабильным между запусками
+// и нормально диффался. Читаются они как раньше - порядок в файле на чтение не влияет.
+
+fn sorted_set<T: Serialize + Ord, S: Serializer>(set: &HashSet<T>, serializer: S) -> Result<S::Ok, S::Error> {
+    let mut items: Vec<&T> = set.iter().collect();
+    items.sort();
+    serializer.collect_seq(items)
+}
+
+fn sorted_remotes<S: Serializer>(set: &HashSet<RemoteRef>, serializer: S) -> Result<S::Ok, S::Error> {
+    let mut items: Vec<&RemoteRef> = set.iter().collect();
+    items.sort_by(|a, b| (&a.name, &a.url).cmp(&(&b.name, &b.url)));
+    serializer.collect_seq(items)
+}
+
+fn sorted_map<K: Serialize + Ord, V: Serialize, S: Serializer>(map: &HashMap<K, V>, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_map(map.iter().collect::<BTreeMap<_, _>>())
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Tag {
     pub name: String,
     pub created_at: DateTime<Utc>,
+    #[serde(serialize_with = "sorted_map")]
     pub meta: HashMap<String, String>,
 }
 
@@ -55,30 +231,90 @@ pub struct Node {
 
     pub parents: Vec<NodeId>,
 
+    #[serde(serialize_with = "sorted_set")]
     pub children: HashSet<NodeId>,
 
     pub author: Author,
 
+    /// Соавторы (парное программирование), в git-коммит уходят трейлерами Co-authored-by
+    #[serde(default)]
+    pub co_authors: Vec<Author>,
+
     pub message: String,
 
     pub created_at: DateTime<Utc>,
 
+    /// Коммит ноды в git, если id ноды - не его хеш (graph.ids = content). None - id и есть коммит
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit: Option<NodeId>,
+
+    /// Смещение пояса автора от UTC в секундах (как +0300 в git). У старых нод его нет - считаем UTC
+    #[serde(default)]
+    pub tz_offset: i32,
+
     pub payload: NodePayload,
 
+    #[serde(serialize_with = "sorted_remotes")]
     pub remotes: HashSet<RemoteRef>,
 
+    #[serde(serialize_with = "sorted_map")]
     pub tags: HashMap<String, Tag>,
 
+    #[serde(serialize_with = "sorted_map")]
     pub metadata: HashMap<String, String>,
 }
 
 impl Node {
+    /// Что отдавать бэкенду вместо id ноды
+    pub fn commit_id(&self) -> &NodeId {
+        self.commit.as_ref().unwrap_or(&self.id)
+    }
+
+    /// created_at в поясе автора - так дату показывает git log
+    pub fn authored_at(&self) -> DateTime<FixedOffset> {
+        let offset = FixedOffset::east_opt(self.tz_offset).unwrap_or_else(|| Utc.fix());
+        self.created_at.with_timezone(&offset)
+    }
+
+    /// Имя ремоута у ноды одно (без учёта регистра), иначе push --remote не знал бы, какой URL брать.
+    /// Ремоут с уже известным именем заменяет прежний - так chrm --url меняет URL ноды, - но имя
+    /// остаётся в том написании, с которым ремоут появился первым. Право без URL прежний URL не затирает.
     pub fn add_remote(&mut self, remote: RemoteRef) {
-        self.remotes.insert(remote);
+        let key = remote_name_key(&remote.name);
+        let known = self.remotes.iter().find(|r| remote_name_key(&r.name) == key).map(|r| r.name.clone());
+        match known {
+            Some(_) if remote.url.is_empty() => {}
+            Some(name) => {
+                self.remotes.retain(|r| remote_name_key(&r.name) != key);
+                self.remotes.insert(RemoteRef { name, ..remote });
+            }
+            None => {
+                self.remotes.insert(remote);
+            }
+        }
+    }
+
+    /// Имена ремоутов, записанные у ноды больше одного раза (с разными URL), с этими URL.
+    /// add_remote такого не допускает, но graph.json мог быть записан до этого правила или правлен руками.
+    pub fn duplicate_remotes(&self) -> Vec<(String, Vec<String>)> {
+        let mut by_name: BTreeMap<String, Vec<&RemoteRef>> = BTreeMap::new();
+        for remote in &self.remotes {
+            by_name.entry(remote_name_key(&remote.name)).or_default().push(remote);
+        }
+        by_name
+            .into_values()
+            .filter(|group| group.len() > 1)
+            .map(|group| {
+                let mut urls: Vec<String> = group.iter().map(|r| r.url.clone()).collect();
+                urls.sort();
+                (group[0].name.clone(), urls)
+            })
+            .collect()
     }
 
     pub fn remove_remote(&mut self, remote_name: &str) {
-        self.remotes.retain(|r| r.name != remote_name);
+        let key = remote_name_key(remote_name);
+        self.remotes.retain(|r| remote_name_key(&r.name) != key);
     }
 
     pub fn remove_all_remotes(&mut self) {
@@ -92,4 +328,158 @@ impl Node {
     pub fn remove_tag(&mut self, tag_name: &str) {
         self.tags.remove(tag_name);
     }
-}
\ No newline at end of file
+
+    /// Можно ли пушить ноду в remote. Право без URL (корни из init, origin по умолчанию)
+    /// подходит к ремоуту с таким именем по любому URL.
+    pub fn allows_remote(&self, remote: &RemoteRef) -> bool {
+        self.remotes.iter().any(|r| {
+            r == remote || (r.url.is_empty() && remote_name_key(&r.name) == remote_name_key(&remote.name))
+        })
+    }
+
+    /// Контекст, в который переключается checkout этой ноды.
+    /// Порядок в HashSet случайный, поэтому выбираем детерминированно: сначала origin, иначе минимальное имя.
+    pub fn preferred_context(&self) -> Option<&str> {
+        if let Some(origin) = self.remotes.iter().find(|r| remote_name_key(&r.name) == "origin") {
+            return Some(origin.name.as_str());
+        }
+        self.remotes.iter().map(|r| r.name.as_str()).min()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn local_remote_urls_become_absolute() {
+        let base = std::env::temp_dir().join(format!("gpp-url-{}", std::process::id()));
+        std::fs::create_dir_all(base.join("repo")).unwrap();
+        std::fs::create_dir_all(base.join("mirror.git")).unwrap();
+        let mirror = base.join("mirror.git").canonicalize().unwrap();
+
+        assert_eq!(normalize_remote_url("../mirror.git", &base.join("repo")), mirror.to_string_lossy());
+        assert_eq!(normalize_remote_url("git@host:me/repo.git", &base), "git@host:me/repo.git");
+        assert_eq!(normalize_remote_url("https://host/repo.git", &base), "https://host/repo.git");
+        assert_eq!(normalize_remote_url("file:///srv/mirror.git", &base), "file:///srv/mirror.git");
+        assert_eq!(local_remote_path("file:///srv/mirror.git", &base), Some(PathBuf::from("/srv/mirror.git")));
+        assert_eq!(local_remote_path("git@host:me/repo.git", &base), None);
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn short_id_does_not_panic_on_short_ids() {
+        let id = NodeId("a1b2c3d4e5".into());
+        assert_eq!(short_id(&id, 4), "a1b2");
+        assert_eq!(short_id(&id, 40), "a1b2c3d4e5");
+        assert_eq!(short_id(&NodeId("ид".into()), 7), "ид");
+    }
+
+    fn remote(name: &str) -> RemoteRef {
+        RemoteRef::new(name, format!("{}.git", name)).unwrap()
+    }
+
+    fn node_with(remotes: &[&str], children: &[&str]) -> Node {
+        Node {
+            id: NodeId("n".into()),
+            parents: vec![],
+            children: children.iter().map(|c| NodeId(c.to_string())).collect(),
+            author: Author { name: "a".into(), email: "a@b".into() },
+            co_authors: vec![],
+            message: "m".into(),
+            created_at: DateTime::<Utc>::MIN_UTC,
+            commit: None,
+            tz_offset: 0,
+            payload: NodePayload { tree_id: "t".into() },
+            remotes: remotes.iter().map(|r| remote(r)).collect(),
+            tags: HashMap::new(),
+            metadata: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn serialization_is_sorted_and_stable() {
+        let a = node_with(&["zeta", "origin", "alpha", "work"], &["c3", "c1", "c2"]);
+        let b = node_with(&["work", "alpha", "zeta", "origin"], &["c2", "c3", "c1"]);
+        let json_a = serde_json::to_string(&a).unwrap();
+        assert_eq!(json_a, serde_json::to_string(&b).unwrap());
+
+        let pos = |needle: &str| json_a.find(needle).unwrap();
+        assert!(pos("\"alpha\"") < pos("\"origin\"") && pos("\"origin\"") < pos("\"work\""));
+        assert!(pos("\"c1\"") < pos("\"c2\"") && pos("\"c2\"") < pos("\"c3\""));
+    }
+
+    #[test]
+    fn unordered_files_still_load() {
+        let json = serde_json::to_string(&node_with(&["origin"], &[]))
+            .unwrap()
+            .replace("\"children\":[]", "\"children\":[\"b\",\"a\"]");
+        let node: Node = serde_json::from_str(&json).unwrap();
+        assert_eq!(node.children.len(), 2);
+    }
+
+    #[test]
+    fn files_without_co_authors_still_load() {
+        let json = serde_json::to_string(&node_with(&["origin"], &[])).unwrap().replace("\"co_authors\":[],", "");
+        assert!(!json.contains("co_authors"));
+        let node: Node = serde_json::from_str(&json).unwrap();
+        assert!(node.co_authors.is_empty());
+    }
+
+    #[test]
+    fn author_parses_git_format() {
+        let author: Author = "Ada Lovelace <ada@example.com>".parse().unwrap();
+        assert_eq!(author, Author { name: "Ada Lovelace".into(), email: "ada@example.com".into() });
+        assert_eq!(author.to_string(), "Ada Lovelace <ada@example.com>");
+        assert!("ada@example.com".parse::<Author>().is_err());
+        assert!("<ada@example.com>".parse::<Author>().is_err());
+    }
+
+    #[test]
+    fn remote_ref_validates_name_and_url() {
+        let remote = RemoteRef::new(" work ", "  git@example.com:corp/repo.git\n").unwrap().with_spec("push", "refs/heads/main");
+        assert_eq!(remote.name, "work");
+        assert_eq!(remote.url, "git@example.com:corp/repo.git");
+        assert_eq!(remote.specs["push"], "refs/heads/main");
+
+        assert!(RemoteRef::new("work", "  ").is_err());
+        assert_eq!(RemoteRef::without_url("origin").unwrap().url, "");
+        for bad in ["", "my remote", "a/b", "-x", ".hidden", "x.", "naïve"] {
+            assert!(RemoteRef::without_url(bad).is_err(), "'{}' should be rejected", bad);
+        }
+    }
+
+    #[test]
+    fn remote_urls_are_checked_for_shape() {
+        for good in ["git@github.com:me/repo.git", "https://host/repo", "ssh://git@host:22/repo", "file:///srv/repo", "ext::sleep 5", "."] {
+            assert!(validate_remote_url(good).is_ok(), "'{}' should be accepted", good);
+        }
+        for bad in ["", "repo", "htps://host/repo", "https://", "./no/such/dir", "git@host:"] {
+            assert!(validate_remote_url(bad).is_err(), "'{}' should be rejected", bad);
+        }
+    }
+
+    #[test]
+    fn remote_names_stay_unique_on_a_node() {
+        let mut node = node_with(&[], &[]);
+        node.add_remote(RemoteRef::without_url("origin").unwrap());
+        node.add_remote(RemoteRef::new("origin", "url-a").unwrap());
+        node.add_remote(RemoteRef::new("Origin", "url-b").unwrap());
+        // право без URL уже известный URL не затирает
+        node.add_remote(RemoteRef::without_url("ORIGIN").unwrap());
+        let remotes: Vec<_> = node.remotes.iter().map(|r| (r.name.as_str(), r.url.as_str())).collect();
+        assert_eq!(remotes, vec![("origin", "url-b")]);
+        assert!(node.duplicate_remotes().is_empty());
+
+        // так мог выглядеть старый graph.json
+        node.remotes.insert(RemoteRef::new("Origin", "url-a").unwrap());
+        assert_eq!(node.duplicate_remotes().len(), 1);
+        assert_eq!(node.duplicate_remotes()[0].1, vec!["url-a".to_string(), "url-b".to_string()]);
+
+        // origin в любом регистре важнее остальных, а контекст берётся в написании ноды
+        let mut node = node_with(&[], &[]);
+        node.add_remote(RemoteRef::new("alpha", "url-a").unwrap());
+        node.add_remote(RemoteRef::new("Origin", "url-b").unwrap());
+        assert_eq!(node.preferred_context(), Some("Origin"));
+    }
+}
@@ -1,42 +1,155 @@
 use std::error::Error;
-use std::collections::{HashSet, HashMap};
-use chrono::Utc;
+use std::collections::{HashSet, HashMap, VecDeque};
+use chrono::{DateTime, Local, Timelike, Utc};
 
-use crate::types::{Node, NodeId, Author, NodePayload, RemoteRef};
-use crate::backend::{RepoBackend, GraphOps};
-use crate::storage::GraphStorage;
+use crate::types::{remote_name_key, Node, NodeId, Author, NodePayload, RemoteRef, Tag};
+use crate::backend::{DiffStat, RepoBackend, GraphOps, HistoryEntry, SignatureStatus};
+use crate::ids::{CommitHashIds, IdStrategy, NodeContent};
+use crate::storage::{GraphStorage, StorageError, Transaction};
+use crate::revision::{self, RevContext, RevStep};
+
+/// Какие ремоуты получает нода с несколькими родителями, если их не указали явно.
+///
+/// Union (по умолчанию) - объединение: нода может уйти туда, куда может хоть один родитель.
+/// Удобно, но слияние публичной ноды с секретной молча даёт результату право на публичный ремоут,
+/// и секретный код уезжает туда при следующем push.
+/// Intersection - пересечение: только туда, куда согласны все родители. Для сценария с секретным
+/// ремоутом это безопаснее, расширять права приходится явно через chrm.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InheritPolicy {
+    #[default]
+    Union,
+    Intersection,
+}
+
+/// Как собирать дерево из рабочей директории (add, проверки чистоты в stash и checkout).
+///
+/// Index (по умолчанию) - `git add -A` в индекс активного контекста, как делает обычный git.
+/// Побочный эффект: то, что пользователь застейджил сам, заменяется содержимым рабочей директории.
+/// Worktree - через временный индекс, настоящий не меняется. Удобно, если тем же репозиторием
+/// пользуются и через git напрямую.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TreeSource {
+    #[default]
+    Index,
+    Worktree,
+}
+
+/// Необязательные параметры add_node_with
+#[derive(Debug, Clone, Default)]
+pub struct AddOptions {
+    /// Взять из рабочей директории только эти пути, остальное - как в первом родителе. Пусто - всё.
+    pub paths: Vec<String>,
+    /// Дата ноды и коммита (author и committer), None - сейчас
+    pub date: Option<DateTime<Utc>>,
+}
+
+/// Что chrm сделает с одной нодой: ремоуты до и после (для --dry-run)
+#[derive(Debug, Clone, PartialEq)]
+pub struct RemoteChange {
+    pub node: NodeId,
+    pub before: Vec<String>,
+    pub after: Vec<String>,
+}
+
+/// Нода, у которой рёбра графа не совпадают с родителями её коммита в git (gpp verify)
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParentMismatch {
+    pub node: NodeId,
+    pub graph: Vec<NodeId>,
+    /// None - коммита нет в git вообще
+    pub git: Option<Vec<NodeId>>,
+}
+
+/// Нода, у которой одно имя ремоута записано с разными URL (gpp verify)
+#[derive(Debug, Clone, PartialEq)]
+pub struct RemoteConflict {
+    pub node: NodeId,
+    pub remote: String,
+    pub urls: Vec<String>,
+}
+
+/// Нода, коммит которой не подписан или подпись не проходит (gpp verify --signatures)
+#[derive(Debug, Clone, PartialEq)]
+pub struct SignatureProblem {
+    pub node: NodeId,
+    pub status: SignatureStatus,
+}
 
 pub struct VersionGraph {
     storage: Box<dyn GraphStorage>,
     backend: Box<dyn RepoBackend>,
+    inherit_policy: InheritPolicy,
+    tree_source: TreeSource,
+    id_strategy: Box<dyn IdStrategy>,
 }
 
 impl VersionGraph {
     pub fn new(storage: Box<dyn GraphStorage>, backend: Box<dyn RepoBackend>) -> Self {
-        Self { storage, backend }
+        Self {
+            storage,
+            backend,
+            inherit_policy: InheritPolicy::default(),
+            tree_source: TreeSource::default(),
+            id_strategy: Box::new(CommitHashIds),
+        }
     }
 
-    pub fn add_node(
-        &mut self,
-        parents: Vec<NodeId>,
-        author: Author,
-        message: String,
-        requested_remotes: Option<Vec<String>>,
-    ) -> Result<NodeId, Box<dyn Error>> {
+    pub fn set_inherit_policy(&mut self, policy: InheritPolicy) {
+        self.inherit_policy = policy;
+    }
+
+    pub fn set_tree_source(&mut self, source: TreeSource) {
+        self.tree_source = source;
+    }
 
-        // Собираем все допустимые ремоуты от родителей (Union)
+    /// Как add_node выдаёт id новым нодам. Уже созданные ноды свои id сохраняют
+    pub fn set_id_strategy(&mut self, strategy: Box<dyn IdStrategy>) {
+        self.id_strategy = strategy;
+    }
+
+    /// Дерево рабочей директории так, как велит tree_source
+    fn worktree_tree(&self) -> Result<String, Box<dyn Error>> {
+        match self.tree_source {
+            TreeSource::Index => self.backend.create_tree(),
+            TreeSource::Worktree => self.backend.create_tree_isolated(),
+        }
+    }
+
+    /// Тот же экземпляр, через который граф пишет коммиты: у push и контекстов нет своей копии
+    pub fn backend(&self) -> &dyn RepoBackend {
+        self.backend.as_ref()
+    }
+
+    /// Какие ремоуты получит нода с такими родителями: явно запрошенные (проверяются на подмножество
+    /// прав родителей) или унаследованные по inherit_policy. Ничего не пишет, поэтому годится
+    /// для предварительной проверки перед коммитом.
+    pub fn validate_remotes(
+        &self,
+        parents: &[NodeId],
+        requested: Option<Vec<String>>,
+    ) -> Result<HashSet<RemoteRef>, Box<dyn Error>> {
+        // Собираем допустимые ремоуты от родителей: объединение или пересечение, смотря по политике.
+        // Ключ - имя без учёта регистра, значение хранит написание из графа.
         let mut allowed_remotes: HashMap<String, RemoteRef> = HashMap::new();
 
-        for parent_id in &parents {
+        for (i, parent_id) in parents.iter().enumerate() {
             let p_node = self.storage.load_node(parent_id)?;
-            for remote in p_node.remotes {
-                // надо проверять на конфликт URL, но пока пропустим.
-                allowed_remotes.insert(remote.name.clone(), remote);
+            match self.inherit_policy {
+                InheritPolicy::Intersection if i > 0 => {
+                    allowed_remotes.retain(|key, _| p_node.remotes.iter().any(|r| &remote_name_key(&r.name) == key));
+                }
+                _ => {
+                    for remote in p_node.remotes {
+                        // надо проверять на конфликт URL, но пока пропустим.
+                        allowed_remotes.entry(remote_name_key(&remote.name)).or_insert(remote);
+                    }
+                }
             }
         }
 
         // 2. Определяем итоговый список ремоутов для новой ноды
-        let final_remotes: HashSet<RemoteRef> = if let Some(req_names) = requested_remotes {
+        let final_remotes: HashSet<RemoteRef> = if let Some(req_names) = requested {
             // Ветка А: Пользователь явно запросил конкретные ремоуты
             let mut result = HashSet::new();
 
@@ -45,17 +158,20 @@ impl VersionGraph {
                 // Мы не можем валидировать "наследие".
                 // Тут архитектурный вопрос: откуда брать URL для RemoteRef?
                 // Для простоты, если это корень, разрешаем создавать RemoteRef без URL (или с пустым).
+                let mut seen = HashSet::new();
                 for name in req_names {
-                    result.insert(RemoteRef {
-                        name,
-                        url: "".to_string(), // TODO: Надо бы брать из git config
-                        specs: Default::default(),
-                    });
+                    if !seen.insert(remote_name_key(&name)) {
+                        return Err(format!("Remote '{}' is listed twice (remote names are case-insensitive)", name).into());
+                    }
+                    // ремоут, уже известный графу, пишем так, как он там записан
+                    let name = self.canonical_remote_name(&name)?.unwrap_or(name);
+                    // TODO: URL надо бы брать из git config
+                    result.insert(RemoteRef::without_url(name)?);
                 }
             } else {
                 // Стандартный случай: Валидация подмножества
                 for name in req_names {
-                    match allowed_remotes.get(&name) {
+                    match allowed_remotes.get(&remote_name_key(&name)) {
                         Some(r_ref) => {
                             result.insert(r_ref.clone());
                         },
@@ -64,7 +180,7 @@ impl VersionGraph {
                                 "Validation Error: Remote '{}' is not present in parent nodes. \
                                 Cannot extend history seamlessly. Parents have: {:?}",
                                 name,
-                                allowed_remotes.keys().collect::<Vec<_>>()
+                                allowed_remotes.values().map(|r| &r.name).collect::<Vec<_>>()
                             ).into());
                         }
                     }
@@ -72,91 +188,1340 @@ impl VersionGraph {
             }
             result
         } else {
-            // Ветка Б: Пользователь ничего не указал -> Наследуем ВСЁ (Union)
+            // Ветка Б: Пользователь ничего не указал -> Наследуем всё, что разрешает inherit_policy
             if parents.is_empty() {
                 // Если корень и не указали ремоутов -> наверно "origin"?
-                HashSet::from([RemoteRef {
-                    name: "origin".into(),
-                    url: "".into(),
-                    specs: Default::default()
-                }])
+                HashSet::from([RemoteRef::without_url("origin")?])
             } else {
                 allowed_remotes.into_values().collect()
             }
         };
 
-        let tree_id = self.backend.create_tree()?;
-        let commit_id = self.backend.create_commit(&tree_id, &parents, &message, &author)?;
+        Ok(final_remotes)
+    }
+
+    pub fn add_node(
+        &mut self,
+        parents: Vec<NodeId>,
+        author: Author,
+        co_authors: Vec<Author>,
+        message: String,
+        requested_remotes: Option<Vec<String>>,
+    ) -> Result<NodeId, Box<dyn Error>> {
+        self.add_node_with(parents, author, co_authors, message, requested_remotes, AddOptions::default())
+    }
+
+    /// add_node с путями и/или заданной датой, см. AddOptions
+    pub fn add_node_with(
+        &mut self,
+        parents: Vec<NodeId>,
+        author: Author,
+        co_authors: Vec<Author>,
+        message: String,
+        requested_remotes: Option<Vec<String>>,
+        options: AddOptions,
+    ) -> Result<NodeId, Box<dyn Error>> {
+        let AddOptions { paths, date } = options;
+        if let Some(date) = date.filter(|d| d.timestamp() < 0) {
+            return Err(format!("git cannot record dates before 1970: {}", date.to_rfc3339()).into());
+        }
+        let final_remotes = self.validate_remotes(&parents, requested_remotes)?;
+
+        // соавторы - трейлерами в конце сообщения, как их понимает GitHub
+        let mut commit_message = message.clone();
+        if !co_authors.is_empty() {
+            commit_message.push('\n');
+            for co_author in &co_authors {
+                commit_message.push_str(&format!("\nCo-authored-by: {}", co_author));
+            }
+        }
+
+        // git хранит время с точностью до секунды, в графе держим ровно то же значение
+        let created_at = date.unwrap_or_else(Utc::now);
+        let created_at = created_at.with_nanosecond(0).unwrap_or(created_at);
+        // пояс - тот, что был у машины автора в этот момент (летнее время учитывается)
+        let authored_at = created_at.with_timezone(&Local).fixed_offset();
+        // у git свои родители: коммиты родительских нод, а не их id
+        let parent_nodes = parents.iter().map(|p| self.storage.load_node(p)).collect::<Result<Vec<_>, _>>()?;
+        let parent_commits: Vec<NodeId> = parent_nodes.iter().map(|p| p.commit_id().clone()).collect();
+        let tree_id = if paths.is_empty() {
+            self.worktree_tree()?
+        } else {
+            let base_tree = parent_nodes.first().map(|p| p.payload.tree_id.as_str());
+            self.backend.create_tree_from_paths(base_tree, &paths)?
+        };
+        let commit_id = self.backend.create_commit(&tree_id, &parent_commits, &commit_message, &author, authored_at)?;
+        // create_commit пишет в ТЕКУЩИЙ активный контекст, остальные контексты ноды должны получить объект тоже
+        let remotes_vec: Vec<RemoteRef> = final_remotes.iter().cloned().collect();
+        self.backend.replicate_commit(&commit_id, &remotes_vec)?;
+
+        let generated = self.id_strategy.generate(&NodeContent {
+            tree_id: &tree_id,
+            parents: &parents,
+            author: &author,
+            message: &message,
+            created_at,
+            commit: Some(&commit_id),
+        });
+        let id = match generated {
+            Ok(id) => id,
+            Err(err) => {
+                self.backend.discard_commit(&commit_id, &parent_commits, &remotes_vec)?;
+                return Err(err);
+            }
+        };
 
         let node = Node {
-            id: commit_id.clone(),
+            id: id.clone(),
             parents: parents.clone(),
             children: HashSet::new(),
             author,
+            co_authors,
             message,
-            created_at: Utc::now(),
+            created_at,
+            commit: (id != commit_id).then(|| commit_id.clone()),
+            tz_offset: authored_at.offset().local_minus_utc(),
             payload: NodePayload { tree_id },
             remotes: final_remotes,
             tags: HashMap::new(),
             metadata: HashMap::new(),
         };
 
-        let tx = self.storage.begin_tx()?;
-        self.storage.persist_node(&node)?;
+        // git-объект к этому моменту создан, а HEAD контекстов передвинут на него. Если граф не сохранился,
+        // откатываем и граф, и ссылки: иначе HEAD указывал бы на коммит, которого в графе нет.
+        // Сам объект остаётся недостижимым, его уберёт git gc.
+        let mut tx = Transaction::begin(self.storage.as_mut())?;
+        // тот же коммит (дерево, родители, сообщение, автор и секунда совпали) git отдаёт с тем же хешем.
+        // persist_node перезаписал бы ноду, и пропали бы её дети, права, теги и метаданные.
+        let saved: Result<(), Box<dyn Error>> = if tx.load_node(&id).is_ok() {
+            Err(format!(
+                "Identical commit {} already exists in the graph (same tree, parents, message and time); nothing was added",
+                id.short()
+            ).into())
+        } else {
+            // при ошибке транзакция откатывается сама: commit откатывает её при сбое, брошенная - в Drop
+            persist_new_node(&mut tx, &node).and_then(|_| tx.commit()).map_err(Into::into)
+        };
+        if let Err(err) = saved {
+            let undo = self.backend.discard_commit(&commit_id, &parent_commits, &remotes_vec);
+            return match undo {
+                Ok(()) => Err(err),
+                Err(undo_err) => Err(format!("{} (git refs were not moved back: {})", err, undo_err).into()),
+            };
+        }
+
+        Ok(id)
+    }
+
+    /// Записывает ноду только в граф, без бэкенда: дерево и id задаёт вызывающий (тесты,
+    /// версионирование не-git данных). Ремоуты проверяются и наследуются так же, как в add_node.
+    pub fn add_node_raw(
+        &mut self,
+        parents: Vec<NodeId>,
+        author: Author,
+        message: String,
+        requested_remotes: Option<Vec<String>>,
+        tree_id: String,
+        id: NodeId,
+    ) -> Result<NodeId, Box<dyn Error>> {
+        let remotes = self.validate_remotes(&parents, requested_remotes)?;
+        let created_at = Local::now();
+        let node = Node {
+            id: id.clone(),
+            parents,
+            children: HashSet::new(),
+            author,
+            co_authors: Vec::new(),
+            message,
+            created_at: created_at.with_nanosecond(0).unwrap_or(created_at).to_utc(),
+            commit: None,
+            tz_offset: created_at.offset().local_minus_utc(),
+            payload: NodePayload { tree_id },
+            remotes,
+            tags: HashMap::new(),
+            metadata: HashMap::new(),
+        };
+
+        let mut tx = Transaction::begin(self.storage.as_mut())?;
+        if tx.load_node(&id).is_ok() {
+            return Err(format!("Node {} already exists", id.short()).into());
+        }
+        persist_new_node(&mut tx, &node)?;
+        tx.commit()?;
+        Ok(id)
+    }
 
-        for parent_id in &parents {
-            let mut p_node = self.storage.load_node(parent_id)?;
-            p_node.children.insert(commit_id.clone());
-            self.storage.persist_node(&p_node)?;
+    /// Нода и все её предки (follow_parents) или все потомки, в порядке обхода
+    fn walk_from(&self, start: &NodeId, follow_parents: bool) -> Result<Vec<NodeId>, Box<dyn Error>> {
+        let mut result = Vec::new();
+        let mut visited = HashSet::new();
+        let mut queue = VecDeque::from([start.clone()]);
+
+        while let Some(current_id) = queue.pop_front() {
+            if !visited.insert(current_id.clone()) {
+                continue;
+            }
+            let node = self.storage.load_node(&current_id)?;
+            if follow_parents {
+                queue.extend(node.parents);
+            } else {
+                queue.extend(node.children);
+            }
+            result.push(current_id);
         }
-        self.storage.commit_tx(tx)?;
 
-        Ok(commit_id)
+        Ok(result)
     }
 
+    /// Выдаёт ноде право пушить в remote. С recursive право получает и вся цепочка предков,
+    /// иначе push всё равно упрётся в первого предка без права. Возвращает число затронутых нод.
     pub fn add_remote_permission(
         &mut self,
         node_id: &NodeId,
-        remote: RemoteRef
-    ) -> Result<(), Box<dyn Error>> {
-        let tx = self.storage.begin_tx()?;
+        remote: RemoteRef,
+        recursive: bool,
+    ) -> Result<usize, Box<dyn Error>> {
+        self.check_remote_spelling(&remote.name)?;
+        let targets = if recursive { self.walk_from(node_id, true)? } else { vec![node_id.clone()] };
+        self.update_nodes(&targets, |node| node.add_remote(remote.clone()))
+    }
+
+    /// Меняет ноды targets одной транзакцией: каждая загружается, меняется и записывается, коммит - в самом конце.
+    /// Ошибка на любой ноде откатывает и те, что уже записаны, иначе recursive chrm оставил бы цепочку наполовину.
+    fn update_nodes(&mut self, targets: &[NodeId], change: impl Fn(&mut Node)) -> Result<usize, Box<dyn Error>> {
+        let mut tx = Transaction::begin(self.storage.as_mut())?;
+        for id in targets {
+            let mut node = tx.load_node(id)?;
+            change(&mut node);
+            tx.persist_node(&node)?;
+        }
+        tx.commit()?;
+        Ok(targets.len())
+    }
+
+    /// add_remote_permission без записи: какие ноды получат право и какими станут их ремоуты
+    pub fn preview_add_remote_permission(
+        &self,
+        node_id: &NodeId,
+        remote: &RemoteRef,
+        recursive: bool,
+    ) -> Result<Vec<RemoteChange>, Box<dyn Error>> {
+        self.check_remote_spelling(&remote.name)?;
+        let targets = if recursive { self.walk_from(node_id, true)? } else { vec![node_id.clone()] };
+        self.preview_remotes(&targets, |node| node.add_remote(remote.clone()))
+    }
+
+    /// То же для remove_remote_permission
+    pub fn preview_remove_remote_permission(
+        &self,
+        node_id: &NodeId,
+        remote_name: &str,
+        recursive: bool,
+    ) -> Result<Vec<RemoteChange>, Box<dyn Error>> {
+        let targets = if recursive { self.walk_from(node_id, false)? } else { vec![node_id.clone()] };
+        self.preview_remotes(&targets, |node| node.remove_remote(remote_name))
+    }
 
-        let mut node = self.storage.load_node(node_id)?;
-        node.add_remote(remote);
-        self.storage.persist_node(&node)?;
+    /// Применяет change к копиям нод и оставляет только те, у которых набор ремоутов поменялся
+    fn preview_remotes(&self, targets: &[NodeId], change: impl Fn(&mut Node)) -> Result<Vec<RemoteChange>, Box<dyn Error>> {
+        let names = |node: &Node| {
+            let mut names: Vec<String> = node.remotes.iter().map(|r| r.name.clone()).collect();
+            names.sort();
+            names
+        };
+        let mut changes = Vec::new();
+        for id in targets {
+            let mut node = self.storage.load_node(id)?;
+            let before = node.remotes.clone();
+            let before_names = names(&node);
+            change(&mut node);
+            if node.remotes != before {
+                changes.push(RemoteChange { node: id.clone(), before: before_names, after: names(&node) });
+            }
+        }
+        Ok(changes)
+    }
 
-        self.storage.commit_tx(tx)?;
+    /// Новый ремоут не должен отличаться от уже известного графу только регистром
+    fn check_remote_spelling(&self, name: &str) -> Result<(), Box<dyn Error>> {
+        if let Some(existing) = self.canonical_remote_name(name)? {
+            if existing != name {
+                return Err(format!(
+                    "Remote '{}' differs only by case from existing remote '{}' (remote names are case-insensitive)",
+                    name, existing
+                ).into());
+            }
+        }
         Ok(())
     }
 
+    /// Потомки node_id (без неё самой), которым всё ещё разрешён remote_name. Если снять право только
+    /// с node_id, у них в цепочке предков появится дыра и push на этот ремоут перестанет проходить.
+    pub fn descendants_with_remote(&self, node_id: &NodeId, remote_name: &str) -> Result<Vec<NodeId>, Box<dyn Error>> {
+        let key = remote_name_key(remote_name);
+        let mut found = Vec::new();
+        for id in self.walk_from(node_id, false)?.into_iter().skip(1) {
+            let node = self.storage.load_node(&id)?;
+            if node.remotes.iter().any(|r| remote_name_key(&r.name) == key) {
+                found.push(id);
+            }
+        }
+        Ok(found)
+    }
+
+    /// Забирает право пушить в remote. С recursive - ещё и у всех потомков.
     pub fn remove_remote_permission(
         &mut self,
         node_id: &NodeId,
-        remote_name: &str
-    ) -> Result<(), Box<dyn Error>> {
-        let tx = self.storage.begin_tx()?;
+        remote_name: &str,
+        recursive: bool,
+    ) -> Result<usize, Box<dyn Error>> {
+        let targets = if recursive { self.walk_from(node_id, false)? } else { vec![node_id.clone()] };
+        self.update_nodes(&targets, |node| node.remove_remote(remote_name))
+    }
 
-        let mut node = self.storage.load_node(node_id)?;
-        node.remove_remote(remote_name);
-        self.storage.persist_node(&node)?;
+    /// Строит ноды по готовой истории git (id ноды = хеш коммита), все они получают право на `remote`.
+    /// Уже известные графу коммиты пропускаются. Возвращает число новых нод.
+    pub fn import_history(&mut self, history: Vec<HistoryEntry>, remote: RemoteRef) -> Result<usize, Box<dyn Error>> {
+        let mut tx = Transaction::begin(self.storage.as_mut())?;
+        let mut imported = 0;
 
-        self.storage.commit_tx(tx)?;
-        Ok(())
+        for entry in history {
+            if tx.load_node(&entry.id).is_ok() {
+                continue;
+            }
+            let node = Node {
+                id: entry.id.clone(),
+                parents: entry.parents.clone(),
+                children: HashSet::new(),
+                author: entry.author,
+                co_authors: Vec::new(),
+                message: entry.message,
+                created_at: entry.created_at.to_utc(),
+                commit: None,
+                tz_offset: entry.created_at.offset().local_minus_utc(),
+                payload: NodePayload { tree_id: entry.tree_id },
+                remotes: HashSet::from([remote.clone()]),
+                tags: HashMap::new(),
+                metadata: HashMap::new(),
+            };
+            tx.persist_node(&node)?;
+
+            for parent_id in &entry.parents {
+                let mut p_node = tx
+                    .load_node(parent_id)
+                    .map_err(|e| format!("History is not in parent-first order: {}", e))?;
+                p_node.children.insert(entry.id.clone());
+                tx.persist_node(&p_node)?;
+            }
+            imported += 1;
+        }
+
+        tx.commit()?;
+        Ok(imported)
+    }
+
+    /// Длина самого длинного пути от корня до ноды (у корня 0)
+    fn depth(&self, id: &NodeId, memo: &mut HashMap<NodeId, usize>) -> Result<usize, Box<dyn Error>> {
+        if let Some(d) = memo.get(id) {
+            return Ok(*d);
+        }
+        let node = self.storage.load_node(id)?;
+        let mut depth = 0;
+        for parent in &node.parents {
+            depth = depth.max(self.depth(parent, memo)? + 1);
+        }
+        memo.insert(id.clone(), depth);
+        Ok(depth)
+    }
+
+    /// Все "лучшие" общие предки a и b: общие предки, которые не являются предками другого общего предка.
+    /// При criss-cross слияниях их может быть несколько. Сначала самые глубокие, при равенстве - по id.
+    pub fn merge_bases(&self, a: &NodeId, b: &NodeId) -> Result<Vec<NodeId>, Box<dyn Error>> {
+        let ancestors_a: HashSet<NodeId> = self.walk_from(a, true)?.into_iter().collect();
+        let common: Vec<NodeId> = self.walk_from(b, true)?
+            .into_iter()
+            .filter(|id| ancestors_a.contains(id))
+            .collect();
+
+        // у "лучших" кандидатов нет потомков среди остальных общих предков
+        let mut dominated = HashSet::new();
+        for id in &common {
+            if dominated.contains(id) {
+                continue;
+            }
+            dominated.extend(self.walk_from(id, true)?.into_iter().skip(1));
+        }
+
+        let mut memo = HashMap::new();
+        let mut bases = Vec::new();
+        for id in common.into_iter().filter(|id| !dominated.contains(id)) {
+            bases.push((self.depth(&id, &mut memo)?, id));
+        }
+        bases.sort_by(|(da, ia), (db, ib)| db.cmp(da).then_with(|| ia.cmp(ib)));
+        Ok(bases.into_iter().map(|(_, id)| id).collect())
+    }
+
+    /// Насколько local разошёлся с ремоутом, как "ahead 3, behind 1" у git status: сколько предков local
+    /// (включая её саму) ремоуту неизвестны и сколько нод за remote_tips нет в истории local.
+    pub fn ahead_behind(&self, local: &NodeId, remote_tips: &[NodeId]) -> Result<(usize, usize), Box<dyn Error>> {
+        let local_set: HashSet<NodeId> = self.walk_from(local, true)?.into_iter().collect();
+        let mut remote_set = HashSet::new();
+        for tip in remote_tips {
+            if self.storage.load_node(tip).is_err() {
+                return Err(format!(
+                    "Remote tip {} is not in the graph: the remote has commits gpp does not know about",
+                    tip.short()
+                ).into());
+            }
+            remote_set.extend(self.walk_from(tip, true)?);
+        }
+        Ok((local_set.difference(&remote_set).count(), remote_set.difference(&local_set).count()))
     }
 
-    pub fn checkout(&self, node_id: &NodeId) -> Result<(), Box<dyn Error>> {
+    /// Нода и все её предки, от неё к корням
+    pub fn ancestors(&self, node_id: &NodeId) -> Result<Vec<NodeId>, Box<dyn Error>> {
+        self.walk_from(node_id, true)
+    }
+
+    /// Одна общая база для merge/rebase/diff: самая глубокая из merge_bases (детерминированно)
+    pub fn merge_base(&self, a: &NodeId, b: &NodeId) -> Result<Option<NodeId>, Box<dyn Error>> {
+        Ok(self.merge_bases(a, b)?.into_iter().next())
+    }
+
+    /// Сохраняет рабочую директорию как отдельное дерево (без коммита) и откатывает её к head.
+    /// Возвращает id сохранённого дерева.
+    pub fn stash_worktree(&self, head: &NodeId) -> Result<String, Box<dyn Error>> {
+        let head_node = self.storage.load_node(head)?;
+        let tree_id = self.worktree_tree()?;
+        if tree_id == head_node.payload.tree_id {
+            return Err("No local changes to stash".into());
+        }
+        self.backend.restore_tree(&head_node.payload.tree_id)?;
+        Ok(tree_id)
+    }
+
+    /// Возвращает сохранённое дерево в рабочую директорию.
+    /// Отказывается, если поверх head уже есть изменения - read-tree их молча затёр бы.
+    pub fn unstash_worktree(&self, tree_id: &str, head: Option<&NodeId>) -> Result<(), Box<dyn Error>> {
+        if let Some(head) = head {
+            let head_tree = self.storage.load_node(head)?.payload.tree_id;
+            if self.worktree_tree()? != head_tree {
+                return Err("Working tree has uncommitted changes, commit or stash them first".into());
+            }
+        }
+        self.backend.restore_tree(tree_id)
+    }
+
+    /// Что в рабочей директории изменилось относительно ноды (без ноды - относительно пустого дерева)
+    pub fn changed_paths(&self, base: Option<&NodeId>) -> Result<Vec<String>, Box<dyn Error>> {
+        let base_tree = match base {
+            Some(id) => Some(self.storage.load_node(id)?.payload.tree_id),
+            None => None,
+        };
+        self.backend.changed_paths(base_tree.as_deref())
+    }
+
+    /// Размер ноды относительно первого родителя, корень - относительно пустого дерева
+    pub fn diff_stat(&self, node: &Node) -> Result<DiffStat, Box<dyn Error>> {
+        let base_tree = match node.parents.first() {
+            Some(parent) => Some(self.storage.load_node(parent)?.payload.tree_id),
+            None => None,
+        };
+        self.backend.diff_stat(base_tree.as_deref(), &node.payload.tree_id)
+    }
+
+    /// Разворачивает ноду в рабочую директорию. До того как что-то менять, по порядку проверяет:
+    /// нода есть в графе, есть контекст с её объектами, рабочая директория чистая относительно head
+    /// (read-tree --reset молча затёр бы изменения). head None - сверять не с чем (свежий clone, orphan).
+    pub fn checkout(&self, node_id: &NodeId, head: Option<&NodeId>) -> Result<(), Box<dyn Error>> {
         let node = self.storage.load_node(node_id)?;
-        self.backend.checkout_node(&node)?;
-        Ok(())
+        let context = self.backend.checkout_context(&node)?;
+        if let Some(head) = head {
+            let head_tree = self.storage.load_node(head)?.payload.tree_id;
+            if self.worktree_tree()? != head_tree {
+                return Err("Working tree has uncommitted changes, commit or stash them first".into());
+            }
+        }
+        self.backend.checkout_node(&node, &context)
     }
 
     pub fn list_roots(&self) -> Result<Vec<NodeId>, Box<dyn Error>> {
         Ok(self.storage.list_roots()?)
     }
+
+    /// Все ноды графа (обход от корней вниз)
+    pub fn all_nodes(&self) -> Result<Vec<NodeId>, Box<dyn Error>> {
+        let mut result = Vec::new();
+        let mut seen = HashSet::new();
+        for root in self.list_roots()? {
+            for id in self.walk_from(&root, false)? {
+                if seen.insert(id.clone()) {
+                    result.push(id);
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    /// Все ноды графа по id, уже загруженные (с миграциями и проверками хранилища).
+    /// Для читателей, которым нужен граф целиком: GUI, статистика.
+    pub fn load_all_nodes(&self) -> Result<HashMap<NodeId, Node>, Box<dyn Error>> {
+        let mut nodes = HashMap::new();
+        for id in self.all_nodes()? {
+            let node = self.storage.load_node(&id)?;
+            nodes.insert(id, node);
+        }
+        Ok(nodes)
+    }
+
+    /// Написание ремоута, под которым он уже записан в графе (сравнение без учёта регистра)
+    pub fn canonical_remote_name(&self, name: &str) -> Result<Option<String>, Box<dyn Error>> {
+        let key = remote_name_key(name);
+        for id in self.all_nodes()? {
+            if let Some(remote) = self.storage.load_node(&id)?.remotes.into_iter().find(|r| remote_name_key(&r.name) == key) {
+                return Ok(Some(remote.name));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Запоминает node_id как то, что сейчас лежит на ремоуте. Хранится в graph.json отдельно от нод,
+    /// поэтому переживает потерю .git_<remote> с его tracking-ссылками.
+    pub fn record_remote_tip(&mut self, node_id: &NodeId, remote_name: &str) -> Result<(), Box<dyn Error>> {
+        let mut tx = Transaction::begin(self.storage.as_mut())?;
+        tx.load_node(node_id)?;
+        tx.set_remote_tip(&remote_name_key(remote_name), node_id)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// То, что видно на ремоуте: ноды, которым он разрешён, со связями только между ними.
+    /// Ребро к родителю (или ребёнку) без этого ремоута отбрасывается, а не остаётся висеть,
+    /// так что у каждой ноды результата все parents и children тоже в результате.
+    pub fn remote_subgraph(&self, remote: &str) -> Result<Vec<Node>, Box<dyn Error>> {
+        let key = remote_name_key(remote);
+        let mut nodes = Vec::new();
+        for id in self.all_nodes()? {
+            let node = self.storage.load_node(&id)?;
+            if node.remotes.iter().any(|r| remote_name_key(&r.name) == key) {
+                nodes.push(node);
+            }
+        }
+
+        let permitted: HashSet<NodeId> = nodes.iter().map(|n| n.id.clone()).collect();
+        for node in &mut nodes {
+            node.parents.retain(|p| permitted.contains(p));
+            node.children.retain(|c| permitted.contains(c));
+        }
+        Ok(nodes)
+    }
+
+    /// Нода, на которой висит тег. Имена тегов уникальны на весь граф, как в git.
+    pub fn find_tag(&self, name: &str) -> Result<Option<NodeId>, Box<dyn Error>> {
+        match self.find_nodes(|node| node.tags.contains_key(name))?.next() {
+            Some(node) => Ok(Some(node?.id)),
+            None => Ok(None),
+        }
+    }
+
+    /// Сверяет Node.parents каждой ноды с родителями коммита в git. Пустой список - граф и git согласны.
+    /// Второе значение - сколько нод проверено.
+    pub fn verify_parents(&self) -> Result<(Vec<ParentMismatch>, usize), Box<dyn Error>> {
+        let ids = self.all_nodes()?;
+        let mut mismatches = Vec::new();
+        for id in &ids {
+            let node = self.storage.load_node(id)?;
+            let git = self.backend.commit_parents(node.commit_id())?;
+            // у нод с id не из git (graph.ids = content) сверяем коммиты родителей
+            let mut graph = Vec::new();
+            for parent in &node.parents {
+                graph.push(self.storage.load_node(parent).map(|p| p.commit_id().clone()).unwrap_or_else(|_| parent.clone()));
+            }
+            if git.as_ref() != Some(&graph) {
+                mismatches.push(ParentMismatch { node: id.clone(), graph: node.parents, git });
+            }
+        }
+        Ok((mismatches, ids.len()))
+    }
+
+    /// Ноды, у которых одно имя ремоута встречается несколько раз (в порядке обхода от корней)
+    pub fn remote_conflicts(&self) -> Result<Vec<RemoteConflict>, Box<dyn Error>> {
+        let mut conflicts = Vec::new();
+        for id in self.all_nodes()? {
+            let node = self.storage.load_node(&id)?;
+            for (remote, urls) in node.duplicate_remotes() {
+                conflicts.push(RemoteConflict { node: id.clone(), remote, urls });
+            }
+        }
+        Ok(conflicts)
+    }
+
+    /// Подписи коммитов всех нод или только тех, что уходят на remote. Возвращает ноды без верной
+    /// подписи (в порядке обхода от корней) и сколько нод проверено.
+    pub fn verify_signatures(&self, remote: Option<&str>) -> Result<(Vec<SignatureProblem>, usize), Box<dyn Error>> {
+        let ids = match remote {
+            Some(remote) => self.remote_subgraph(remote)?.into_iter().map(|n| n.id).collect(),
+            None => self.all_nodes()?,
+        };
+        let mut problems = Vec::new();
+        for id in &ids {
+            let node = self.storage.load_node(id)?;
+            match self.backend.verify_signature(node.commit_id())? {
+                SignatureStatus::Good { .. } => {}
+                status => problems.push(SignatureProblem { node: id.clone(), status }),
+            }
+        }
+        Ok((problems, ids.len()))
+    }
+
+    /// Ноды, сгруппированные по tree_id. Одно дерево у разных нод бывает после revert и повторного
+    /// применения или коммита без изменений. Внутри группы - порядок обхода от корней.
+    pub fn nodes_by_tree(&self) -> Result<HashMap<String, Vec<NodeId>>, Box<dyn Error>> {
+        let mut groups: HashMap<String, Vec<NodeId>> = HashMap::new();
+        for id in self.all_nodes()? {
+            let node = self.storage.load_node(&id)?;
+            groups.entry(node.payload.tree_id).or_default().push(id);
+        }
+        Ok(groups)
+    }
+
+    /// Ноды, подходящие под predicate, в порядке обхода от корней. В памяти держатся только id,
+    /// ноды грузятся по одной по мере чтения итератора.
+    pub fn find_nodes<'a>(
+        &'a self,
+        predicate: impl Fn(&Node) -> bool + 'a,
+    ) -> Result<impl Iterator<Item = Result<Node, Box<dyn Error>>> + 'a, Box<dyn Error>> {
+        Ok(self.all_nodes()?.into_iter().filter_map(move |id| match self.storage.load_node(&id) {
+            Ok(node) => predicate(&node).then_some(Ok(node)),
+            Err(e) => Some(Err(e.into())),
+        }))
+    }
+
+    /// Все теги графа вместе с нодами, на которых они висят, по имени
+    pub fn list_tags(&self) -> Result<Vec<(NodeId, Tag)>, Box<dyn Error>> {
+        let mut tags = Vec::new();
+        for id in self.all_nodes()? {
+            let node = self.storage.load_node(&id)?;
+            tags.extend(node.tags.into_values().map(|tag| (id.clone(), tag)));
+        }
+        tags.sort_by(|a, b| a.1.name.cmp(&b.1.name));
+        Ok(tags)
+    }
+
+    /// Разрешает ревизию (id, префикс id, тег, HEAD, "-", суффиксы ~N и ^N) в ноду.
+    /// Веток в графе нет, так что именованные ревизии - это теги.
+    pub fn resolve_rev(&self, rev: &str, ctx: &RevContext) -> Result<NodeId, Box<dyn Error>> {
+        let (base, steps) = revision::split_rev(rev)?;
+
+        let mut current = match base {
+            "HEAD" | "@" => ctx.head.clone().ok_or("HEAD is not set")?,
+            "-" => ctx.previous_head.clone().ok_or("No previous HEAD to go back to")?,
+            _ => self.resolve_name(base)?,
+        };
+
+        for step in steps {
+            let node = self.storage.load_node(&current)?;
+            current = match step {
+                RevStep::Parent(0) => current,
+                RevStep::Parent(n) => node.parents.get(n - 1).cloned().ok_or_else(|| {
+                    format!("Revision '{}': node {} has no parent {}", rev, current.short(), n)
+                })?,
+                RevStep::Ancestor(n) => {
+                    let mut id = current;
+                    for _ in 0..n {
+                        id = self.storage.load_node(&id)?.parents.first().cloned().ok_or_else(|| {
+                            format!("Revision '{}' goes past the root node {}", rev, id.short())
+                        })?;
+                    }
+                    id
+                }
+            };
+        }
+        Ok(current)
+    }
+
+    /// Основа ревизии без суффиксов: полный id, тег или однозначный префикс id
+    fn resolve_name(&self, name: &str) -> Result<NodeId, Box<dyn Error>> {
+        let exact = NodeId(name.to_string());
+        if self.storage.load_node(&exact).is_ok() {
+            return Ok(exact);
+        }
+        if let Some(tagged) = self.find_tag(name)? {
+            return Ok(tagged);
+        }
+        if name.len() >= revision::MIN_PREFIX_LEN {
+            let mut matches: Vec<NodeId> = self.all_nodes()?.into_iter().filter(|id| id.0.starts_with(name)).collect();
+            matches.sort();
+            match matches.len() {
+                0 => {}
+                1 => return Ok(matches.remove(0)),
+                _ => {
+                    let candidates: Vec<&str> = matches.iter().map(|id| id.short()).collect();
+                    return Err(format!("Ambiguous revision '{}': matches {}", name, candidates.join(", ")).into());
+                }
+            }
+        }
+        Err(format!("Unknown revision '{}'", name).into())
+    }
+
+    /// Вешает тег на ноду. Имя потом уходит на ремоут как refs/tags/<name>, поэтому проверяем его по правилам git.
+    pub fn tag_node(&mut self, node_id: &NodeId, name: &str, message: Option<&str>) -> Result<(), Box<dyn Error>> {
+        let bad_char = |c: char| c.is_whitespace() || c.is_control() || "~^:?*[\\".contains(c);
+        if name.is_empty() || name.starts_with(['-', '.', '/']) || name.ends_with(['/', '.'])
+            || name.contains("..") || name.contains("@{") || name.chars().any(bad_char)
+        {
+            return Err(format!("Invalid tag name '{}'", name).into());
+        }
+        if let Some(owner) = self.find_tag(name)? {
+            return Err(format!("Tag '{}' already exists on node {}", name, owner.short()).into());
+        }
+
+        // аннотация - как у git tag -a: просто текст при теге
+        let mut meta = HashMap::new();
+        if let Some(message) = message {
+            meta.insert("message".to_string(), message.to_string());
+        }
+
+        let mut tx = Transaction::begin(self.storage.as_mut())?;
+        let mut node = tx.load_node(node_id)?;
+        node.add_tag(Tag {
+            name: name.to_string(),
+            created_at: Utc::now().with_nanosecond(0).unwrap_or_else(Utc::now),
+            meta,
+        });
+        tx.persist_node(&node)?;
+        tx.commit()?;
+        Ok(())
+    }
 }
 
 impl GraphOps for VersionGraph { // на кой хрен было вводить graphOps я не знаю, кто-нибудь мне объясните?
     fn get_node(&self, id: &NodeId) -> Result<Node, Box<dyn Error>> {
         Ok(self.storage.load_node(id)?)
     }
-}
\ No newline at end of file
+
+    fn recorded_remote_tips(&self, remote_name: &str) -> Result<Vec<NodeId>, Box<dyn Error>> {
+        // вершину могли удалить из графа (rm-node) - тогда её как будто и не было
+        let tip = self.storage.remote_tip(&remote_name_key(remote_name))?;
+        Ok(tip.into_iter().filter(|id| self.storage.load_node(id).is_ok()).collect())
+    }
+
+    fn node_for_commit(&self, commit: &NodeId) -> Result<Option<NodeId>, Box<dyn Error>> {
+        // обычный случай (graph.ids = commit) - без обхода графа
+        if self.storage.load_node(commit).is_ok_and(|n| n.commit.is_none()) {
+            return Ok(Some(commit.clone()));
+        }
+        Ok(self.find_nodes(|n| n.commit.as_ref() == Some(commit))?.next().transpose()?.map(|n| n.id))
+    }
+}
+
+/// Новая нода и ссылка на неё у каждого родителя, внутри уже открытой транзакции
+fn persist_new_node(tx: &mut Transaction, node: &Node) -> Result<(), StorageError> {
+    tx.persist_node(node)?;
+    for parent_id in &node.parents {
+        let mut p_node = tx.load_node(parent_id)?;
+        p_node.children.insert(node.id.clone());
+        tx.persist_node(&p_node)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{mock_graph, test_author, MemoryStorage, MockRepoBackend};
+
+    #[test]
+    fn content_ids_keep_commits_aside() {
+        let (mut graph, backend) = mock_graph();
+        graph.set_id_strategy(Box::new(crate::ids::ContentHashIds));
+        backend.set_worktree("a");
+        let root = graph.add_node(vec![], test_author(), vec![], "root".into(), None).unwrap();
+        backend.set_worktree("b");
+        let child = graph.add_node(vec![root.clone()], test_author(), vec![], "child".into(), None).unwrap();
+
+        let root_node = graph.get_node(&root).unwrap();
+        let root_commit = root_node.commit.clone().expect("content id differs from the commit");
+        assert_ne!(root_commit, root);
+        let child_commit = graph.get_node(&child).unwrap().commit_id().clone();
+        // git видит коммиты родителей, граф - id нод
+        assert_eq!(backend.state().commits[&child_commit.0].parents, vec![root_commit.clone()]);
+        assert!(graph.verify_parents().unwrap().0.is_empty());
+        assert_eq!(graph.node_for_commit(&child_commit).unwrap(), Some(child.clone()));
+        assert_eq!(graph.node_for_commit(&child).unwrap(), None);
+    }
+
+    #[test]
+    fn identical_commit_does_not_overwrite_existing_node() {
+        let (mut graph, backend) = mock_graph();
+        backend.set_worktree("a");
+        let root = graph.add_node(vec![], test_author(), vec![], "root".into(), None).unwrap();
+        backend.set_worktree("b");
+        let first = graph.add_node(vec![root.clone()], test_author(), vec![], "same".into(), None).unwrap();
+        backend.set_worktree("c");
+        let grandchild = graph.add_node(vec![first.clone()], test_author(), vec![], "next".into(), None).unwrap();
+        graph.tag_node(&first, "v1", None).unwrap();
+
+        // вернулись на root и закоммитили то же самое ещё раз
+        backend.state().refs.insert("HEAD".into(), root.clone());
+        backend.set_worktree("b");
+        let err = graph.add_node(vec![root.clone()], test_author(), vec![], "same".into(), None).unwrap_err();
+        assert!(err.to_string().contains(&format!("Identical commit {} already exists", first.short())), "{}", err);
+
+        let node = graph.get_node(&first).unwrap();
+        assert!(node.children.contains(&grandchild));
+        assert!(node.tags.contains_key("v1"));
+        assert_eq!(backend.state().refs.get("HEAD"), Some(&root));
+    }
+
+    #[test]
+    fn raw_nodes_skip_the_backend() {
+        let (mut graph, backend) = mock_graph();
+        let root = graph
+            .add_node_raw(vec![], test_author(), "root".into(), Some(vec!["blobs".into()]), "t1".into(), NodeId("r".into()))
+            .unwrap();
+        let child = graph.add_node_raw(vec![root.clone()], test_author(), "child".into(), None, "t2".into(), NodeId("c".into())).unwrap();
+
+        assert!(backend.state().commits.is_empty());
+        let node = graph.get_node(&child).unwrap();
+        assert_eq!(node.payload.tree_id, "t2");
+        assert_eq!(node.remotes.iter().map(|r| r.name.as_str()).collect::<Vec<_>>(), ["blobs"]);
+        assert!(graph.get_node(&root).unwrap().children.contains(&child));
+
+        let dup = graph.add_node_raw(vec![], test_author(), "again".into(), None, "t3".into(), child.clone()).unwrap_err();
+        assert!(dup.to_string().contains("already exists"), "{}", dup);
+        let orphan = graph.add_node_raw(vec![NodeId("missing".into())], test_author(), "x".into(), None, "t4".into(), NodeId("x".into()));
+        assert!(orphan.is_err());
+        assert!(graph.get_node(&NodeId("x".into())).is_err());
+    }
+
+    #[test]
+    fn root_without_remotes_defaults_to_origin() {
+        let (mut graph, backend) = mock_graph();
+        backend.set_worktree("a");
+        let id = graph.add_node(vec![], test_author(), vec![], "root".into(), None).unwrap();
+
+        let node = graph.get_node(&id).unwrap();
+        let names: Vec<_> = node.remotes.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, vec!["origin"]);
+    }
+
+    #[test]
+    fn child_inherits_parent_remotes_and_links_children() {
+        let (mut graph, backend) = mock_graph();
+        backend.set_worktree("a");
+        let root = graph
+            .add_node(vec![], test_author(), vec![], "root".into(), Some(vec!["a".into(), "b".into()]))
+            .unwrap();
+        backend.set_worktree("b");
+        let child = graph.add_node(vec![root.clone()], test_author(), vec![], "child".into(), None).unwrap();
+
+        let child_node = graph.get_node(&child).unwrap();
+        assert_eq!(child_node.remotes.len(), 2);
+        assert!(graph.get_node(&root).unwrap().children.contains(&child));
+    }
+
+    #[test]
+    fn explicit_date_goes_to_node_and_commit() {
+        let (mut graph, backend) = mock_graph();
+        backend.set_worktree("a");
+        let date = DateTime::parse_from_rfc3339("2020-01-02T03:04:05.250Z").unwrap().with_timezone(&Utc);
+        let options = AddOptions { date: Some(date), ..Default::default() };
+        let id = graph.add_node_with(vec![], test_author(), vec![], "old".into(), None, options).unwrap();
+
+        // доли секунды git не хранит, отбрасываем их и в графе
+        let expected = date.with_nanosecond(0).unwrap();
+        assert_eq!(graph.get_node(&id).unwrap().created_at, expected);
+        assert_eq!(backend.state().commits[&id.0].timestamp, expected);
+
+        let before_epoch = DateTime::parse_from_rfc3339("1969-12-31T23:59:59Z").unwrap().with_timezone(&Utc);
+        let options = AddOptions { date: Some(before_epoch), ..Default::default() };
+        let err = graph.add_node_with(vec![id], test_author(), vec![], "older".into(), None, options).unwrap_err();
+        assert!(err.to_string().contains("before 1970"), "{}", err);
+    }
+
+    #[test]
+    fn requesting_remote_missing_in_parents_fails_validation() {
+        let (mut graph, backend) = mock_graph();
+        backend.set_worktree("a");
+        let root = graph
+            .add_node(vec![], test_author(), vec![], "public".into(), Some(vec!["origin".into()]))
+            .unwrap();
+        backend.set_worktree("b");
+        let err = graph
+            .add_node(vec![root], test_author(), vec![], "hack".into(), Some(vec!["secret".into()]))
+            .unwrap_err();
+        assert!(err.to_string().contains("Validation Error"));
+    }
+
+    #[test]
+    fn recursive_permission_add_covers_ancestors() {
+        let (mut graph, backend) = mock_graph();
+        backend.set_worktree("a");
+        let root = graph.add_node(vec![], test_author(), vec![], "root".into(), None).unwrap();
+        backend.set_worktree("b");
+        let child = graph.add_node(vec![root.clone()], test_author(), vec![], "child".into(), None).unwrap();
+
+        let work = RemoteRef::new("work", "u").unwrap();
+        let touched = graph.add_remote_permission(&child, work.clone(), true).unwrap();
+
+        assert_eq!(touched, 2);
+        assert!(graph.get_node(&root).unwrap().remotes.contains(&work));
+    }
+
+    #[test]
+    fn import_history_links_parents_and_skips_known() {
+        let (mut graph, _backend) = mock_graph();
+        let entry = |id: &str, parents: &[&str]| HistoryEntry {
+            id: NodeId(id.into()),
+            parents: parents.iter().map(|p| NodeId(p.to_string())).collect(),
+            tree_id: format!("tree-{}", id),
+            message: format!("msg {}", id),
+            author: test_author(),
+            created_at: DateTime::parse_from_rfc3339("2024-03-01T12:00:00+03:00").unwrap(),
+        };
+        let upstream = RemoteRef::new("origin", "u").unwrap();
+        let history = vec![entry("a", &[]), entry("b", &["a"]), entry("c", &["a"]), entry("d", &["b", "c"])];
+
+        assert_eq!(graph.import_history(history.clone(), upstream.clone()).unwrap(), 4);
+        assert_eq!(graph.import_history(history, upstream.clone()).unwrap(), 0);
+
+        let root = graph.get_node(&NodeId("a".into())).unwrap();
+        assert_eq!(root.children.len(), 2);
+        // пояс автора из git не теряется: в графе UTC плюс смещение
+        assert_eq!(root.created_at.to_rfc3339(), "2024-03-01T09:00:00+00:00");
+        assert_eq!(root.authored_at().to_rfc3339(), "2024-03-01T12:00:00+03:00");
+        assert!(root.remotes.contains(&upstream));
+        assert_eq!(graph.get_node(&NodeId("d".into())).unwrap().parents.len(), 2);
+        assert_eq!(graph.list_roots().unwrap(), vec![NodeId("a".into())]);
+    }
+
+    #[test]
+    fn merge_base_finds_lowest_common_ancestor() {
+        let (mut graph, backend) = mock_graph();
+        let mut add = |parents: Vec<NodeId>, content: &str| {
+            backend.set_worktree(content);
+            graph.add_node(parents, test_author(), vec![], content.into(), None).unwrap()
+        };
+        let root = add(vec![], "root");
+        let base = add(vec![root.clone()], "base");
+        let left = add(vec![base.clone()], "left");
+        let right = add(vec![base.clone()], "right");
+        let left2 = add(vec![left.clone()], "left2");
+        let other_root = add(vec![], "other");
+
+        assert_eq!(graph.merge_base(&left2, &right).unwrap(), Some(base.clone()));
+        assert_eq!(graph.merge_base(&left2, &left).unwrap(), Some(left.clone()));
+        assert_eq!(graph.merge_base(&left, &other_root).unwrap(), None);
+    }
+
+    #[test]
+    fn merge_bases_reports_all_criss_cross_candidates() {
+        let (mut graph, backend) = mock_graph();
+        let mut add = |parents: Vec<NodeId>, content: &str| {
+            backend.set_worktree(content);
+            graph.add_node(parents, test_author(), vec![], content.into(), None).unwrap()
+        };
+        let root = add(vec![], "root");
+        let a = add(vec![root.clone()], "a");
+        let b = add(vec![root.clone()], "b");
+        let x = add(vec![a.clone(), b.clone()], "x");
+        let y = add(vec![b.clone(), a.clone()], "y");
+
+        let mut expected = vec![a.clone(), b.clone()];
+        expected.sort();
+        assert_eq!(graph.merge_bases(&x, &y).unwrap(), expected);
+        assert_eq!(graph.merge_base(&x, &y).unwrap(), Some(expected[0].clone()));
+    }
+
+    #[test]
+    fn node_timestamp_matches_commit_timestamp() {
+        let (mut graph, backend) = mock_graph();
+        backend.set_worktree("a");
+        let id = graph.add_node(vec![], test_author(), vec![], "a".into(), None).unwrap();
+
+        let node = graph.get_node(&id).unwrap();
+        assert_eq!(backend.state().commits[&id.0].timestamp, node.created_at);
+        assert_eq!(node.created_at.timestamp_subsec_nanos(), 0);
+    }
+
+    #[test]
+    fn stash_round_trip_restores_worktree() {
+        let (mut graph, backend) = mock_graph();
+        backend.set_worktree("committed");
+        let head = graph.add_node(vec![], test_author(), vec![], "c".into(), None).unwrap();
+
+        backend.set_worktree("dirty");
+        let stashed = graph.stash_worktree(&head).unwrap();
+        assert_eq!(backend.state().worktree, "committed");
+        assert!(graph.stash_worktree(&head).is_err());
+
+        backend.set_worktree("other edits");
+        assert!(graph.unstash_worktree(&stashed, Some(&head)).is_err());
+
+        backend.set_worktree("committed");
+        graph.unstash_worktree(&stashed, Some(&head)).unwrap();
+        assert_eq!(backend.state().worktree, "dirty");
+    }
+
+    #[test]
+    fn checkout_checks_object_then_worktree_before_switching() {
+        let (mut graph, backend) = mock_graph();
+        backend.set_worktree("v1");
+        let first = graph.add_node(vec![], test_author(), vec![], "first".into(), None).unwrap();
+        backend.set_worktree("v2");
+        let second = graph.add_node(vec![first.clone()], test_author(), vec![], "second".into(), None).unwrap();
+
+        assert!(graph.checkout(&NodeId("nope".into()), Some(&second)).is_err());
+
+        // незакоммиченные правки поверх HEAD не затираются
+        backend.set_worktree("edited");
+        let err = graph.checkout(&first, Some(&second)).unwrap_err();
+        assert!(err.to_string().contains("uncommitted changes"), "{}", err);
+        assert_eq!(backend.state().checked_out, None);
+
+        // объекта нет ни в одном контексте: до проверки рабочей директории дело не доходит
+        backend.set_worktree("v2");
+        let commit = backend.state().commits.remove(&first.0).unwrap();
+        let err = graph.checkout(&first, Some(&second)).unwrap_err();
+        assert!(err.to_string().contains("not materialized"), "{}", err);
+        assert_eq!(backend.state().checked_out, None);
+
+        backend.state().commits.insert(first.0.clone(), commit);
+        graph.checkout(&first, Some(&second)).unwrap();
+        assert_eq!(backend.state().checked_out, Some(first));
+    }
+
+    #[test]
+    fn intersection_policy_keeps_only_shared_remotes() {
+        let (mut graph, backend) = mock_graph();
+        let add = |graph: &mut VersionGraph, parents: Vec<NodeId>, content: &str, remotes: Option<Vec<String>>| {
+            backend.set_worktree(content);
+            graph.add_node(parents, test_author(), vec![], content.into(), remotes).unwrap()
+        };
+        let public = add(&mut graph, vec![], "public", Some(vec!["origin".into()]));
+        let secret = add(&mut graph, vec![], "secret", Some(vec!["origin".into(), "work".into()]));
+
+        let merged_union = add(&mut graph, vec![public.clone(), secret.clone()], "union", None);
+        assert_eq!(graph.get_node(&merged_union).unwrap().remotes.len(), 2);
+
+        graph.set_inherit_policy(InheritPolicy::Intersection);
+        let merged = add(&mut graph, vec![public.clone(), secret.clone()], "intersection", None);
+        let names: Vec<_> = graph.get_node(&merged).unwrap().remotes.into_iter().map(|r| r.name).collect();
+        assert_eq!(names, vec!["origin".to_string()]);
+
+        backend.set_worktree("explicit");
+        assert!(graph.add_node(vec![public, secret], test_author(), vec![], "x".into(), Some(vec!["work".into()])).is_err());
+    }
+
+    #[test]
+    fn failed_persist_leaves_neither_node_nor_moved_head() {
+        let backend = MockRepoBackend::new();
+        let mut graph = VersionGraph::new(Box::new(MemoryStorage::failing_after(1)), Box::new(backend.clone()));
+        backend.set_worktree("1");
+        let root = graph.add_node(vec![], test_author(), vec![], "root".into(), None).unwrap();
+
+        backend.set_worktree("2");
+        let err = graph.add_node(vec![root.clone()], test_author(), vec![], "lost".into(), None).unwrap_err();
+        assert!(err.to_string().contains("Simulated commit failure"));
+        assert_eq!(backend.read_ref("HEAD").unwrap(), Some(root.clone()));
+        assert!(graph.get_node(&root).unwrap().children.is_empty());
+        assert_eq!(graph.list_roots().unwrap(), vec![root]);
+
+        let backend = MockRepoBackend::new();
+        let mut graph = VersionGraph::new(Box::new(MemoryStorage::failing_after(0)), Box::new(backend.clone()));
+        assert!(graph.add_node(vec![], test_author(), vec![], "root".into(), None).is_err());
+        assert_eq!(backend.read_ref("HEAD").unwrap(), None);
+        assert!(graph.list_roots().unwrap().is_empty());
+    }
+
+    #[test]
+    fn abandoned_or_failed_transactions_leave_no_nodes() {
+        let entry = |id: &str, parents: &[&str]| HistoryEntry {
+            id: NodeId(id.into()),
+            parents: parents.iter().map(|p| NodeId(p.to_string())).collect(),
+            tree_id: format!("tree-{}", id),
+            message: id.into(),
+            author: test_author(),
+            created_at: Utc::now().fixed_offset(),
+        };
+        let origin = RemoteRef::without_url("origin").unwrap();
+
+        // ребёнок раньше родителя: `?` посреди импорта, транзакцию откатывает Drop
+        let (mut graph, _) = mock_graph();
+        let err = graph.import_history(vec![entry("b", &["a"]), entry("a", &[])], origin.clone()).unwrap_err();
+        assert!(err.to_string().contains("parent-first order"));
+        assert!(graph.all_nodes().unwrap().is_empty());
+
+        // сбой самого commit: раньше записанные ноды оставались в памяти хранилища
+        let mut graph = VersionGraph::new(Box::new(MemoryStorage::failing_after(0)), Box::new(MockRepoBackend::new()));
+        assert!(graph.import_history(vec![entry("a", &[])], origin).is_err());
+        assert!(graph.all_nodes().unwrap().is_empty());
+    }
+
+    #[test]
+    fn failed_recursive_chrm_rolls_back_nodes_already_written() {
+        // add корня - одна запись, add ребёнка - две (он и родитель); в chrm ребёнок пишется, а на корне - сбой
+        let backend = MockRepoBackend::new();
+        let mut graph = VersionGraph::new(Box::new(MemoryStorage::failing_persist_after(4)), Box::new(backend.clone()));
+        backend.set_worktree("1");
+        let root = graph.add_node(vec![], test_author(), vec![], "root".into(), None).unwrap();
+        backend.set_worktree("2");
+        let child = graph.add_node(vec![root.clone()], test_author(), vec![], "child".into(), None).unwrap();
+
+        let mirror = RemoteRef::without_url("mirror").unwrap();
+        let err = graph.add_remote_permission(&child, mirror, true).unwrap_err();
+        assert!(err.to_string().contains("Simulated persist failure"));
+        for id in [&child, &root] {
+            assert!(!graph.get_node(id).unwrap().remotes.iter().any(|r| r.name == "mirror"));
+        }
+    }
+
+    #[test]
+    fn remote_subgraph_prunes_edges_to_hidden_nodes() {
+        let (mut graph, backend) = mock_graph();
+        let add = |graph: &mut VersionGraph, parents: Vec<NodeId>, content: &str, remotes: &[&str]| {
+            backend.set_worktree(content);
+            let remotes = remotes.iter().map(|r| r.to_string()).collect();
+            graph.add_node(parents, test_author(), vec![], content.into(), Some(remotes)).unwrap()
+        };
+        let base = add(&mut graph, vec![], "base", &["public", "secret"]);
+        let hidden = add(&mut graph, vec![base.clone()], "hidden", &["secret"]);
+        let side = add(&mut graph, vec![base.clone()], "side", &["public", "secret"]);
+        // мерж с секретной нодой, но сам разрешён на public
+        let merge = add(&mut graph, vec![hidden.clone(), side.clone()], "merge", &["secret"]);
+        graph.add_remote_permission(&merge, RemoteRef::without_url("public").unwrap(), false).unwrap();
+
+        let nodes = graph.remote_subgraph("public").unwrap();
+        let by_id: HashMap<NodeId, Node> = nodes.into_iter().map(|n| (n.id.clone(), n)).collect();
+        assert_eq!(by_id.len(), 3);
+        assert!(!by_id.contains_key(&hidden));
+        assert_eq!(by_id[&merge].parents, vec![side.clone()]);
+        assert_eq!(by_id[&base].children, HashSet::from([side.clone()]));
+        for node in by_id.values() {
+            assert!(node.parents.iter().chain(node.children.iter()).all(|id| by_id.contains_key(id)));
+        }
+
+        assert_eq!(graph.remote_subgraph("secret").unwrap().len(), 4);
+        assert!(graph.remote_subgraph("nowhere").unwrap().is_empty());
+    }
+
+    #[test]
+    fn remote_names_are_case_insensitive() {
+        let (mut graph, backend) = mock_graph();
+        backend.set_worktree("a");
+        assert!(graph.add_node(vec![], test_author(), vec![], "a".into(), Some(vec!["work".into(), "Work".into()])).is_err());
+        let root = graph.add_node(vec![], test_author(), vec![], "a".into(), Some(vec!["Work".into()])).unwrap();
+
+        backend.set_worktree("b");
+        let child = graph.add_node(vec![root.clone()], test_author(), vec![], "b".into(), Some(vec!["work".into()])).unwrap();
+        let names: Vec<_> = graph.get_node(&child).unwrap().remotes.into_iter().map(|r| r.name).collect();
+        assert_eq!(names, vec!["Work".to_string()]);
+
+        backend.set_worktree("c");
+        let other_root = graph.add_node(vec![], test_author(), vec![], "c".into(), Some(vec!["WORK".into()])).unwrap();
+        let names: Vec<_> = graph.get_node(&other_root).unwrap().remotes.into_iter().map(|r| r.name).collect();
+        assert_eq!(names, vec!["Work".to_string()]);
+
+        let variant = RemoteRef::without_url("wOrK").unwrap();
+        let err = graph.add_remote_permission(&root, variant, false).unwrap_err().to_string();
+        assert!(err.contains("differs only by case"));
+
+        graph.remove_remote_permission(&child, "WORK", false).unwrap();
+        assert!(graph.get_node(&child).unwrap().remotes.is_empty());
+    }
+
+    #[test]
+    fn validate_remotes_without_committing() {
+        let (mut graph, backend) = mock_graph();
+        backend.set_worktree("a");
+        let root = graph.add_node(vec![], test_author(), vec![], "a".into(), Some(vec!["origin".into(), "work".into()])).unwrap();
+        let commits_before = backend.state().commits.len();
+
+        let parents = [root];
+        let inherited = graph.validate_remotes(&parents, None).unwrap();
+        assert_eq!(inherited.len(), 2);
+        let subset = graph.validate_remotes(&parents, Some(vec!["work".into()])).unwrap();
+        assert_eq!(subset.into_iter().map(|r| r.name).collect::<Vec<_>>(), vec!["work".to_string()]);
+
+        let err = graph.validate_remotes(&parents, Some(vec!["secret".into()])).unwrap_err();
+        assert!(err.to_string().contains("Validation Error"));
+        assert_eq!(backend.state().commits.len(), commits_before);
+    }
+
+    #[test]
+    fn co_authors_become_commit_trailers() {
+        let (mut graph, backend) = mock_graph();
+        backend.set_worktree("pair");
+        let bob = Author { name: "Bob".into(), email: "bob@example.com".into() };
+        let id = graph.add_node(vec![], test_author(), vec![bob.clone()], "pairing".into(), None).unwrap();
+
+        let node = graph.get_node(&id).unwrap();
+        assert_eq!(node.message, "pairing");
+        assert_eq!(node.co_authors, vec![bob]);
+        assert_eq!(backend.state().commits[&id.0].message, "pairing\n\nCo-authored-by: Bob <bob@example.com>");
+    }
+
+    #[test]
+    fn find_nodes_matches_tags_and_metadata() {
+        let (mut graph, backend) = mock_graph();
+        backend.set_worktree("1");
+        let root = graph.add_node(vec![], test_author(), vec![], "root".into(), None).unwrap();
+        backend.set_worktree("2");
+        let release = graph.add_node(vec![root.clone()], test_author(), vec![], "release".into(), None).unwrap();
+        graph.tag_node(&release, "v1.0", None).unwrap();
+        let mut node = graph.get_node(&root).unwrap();
+        node.metadata.insert("team".into(), "core".into());
+        graph.storage.persist_node(&node).unwrap();
+        // вершина ремоута - служебная запись, в метаданные нод она не попадает
+        graph.record_remote_tip(&release, "origin").unwrap();
+
+        let tagged: Vec<NodeId> = graph.find_nodes(|n| n.tags.contains_key("v1.0")).unwrap().map(|n| n.unwrap().id).collect();
+        assert_eq!(tagged, vec![release.clone()]);
+        let with_meta: Vec<NodeId> = graph.find_nodes(|n| !n.metadata.is_empty()).unwrap().map(|n| n.unwrap().id).collect();
+        assert_eq!(with_meta, vec![root]);
+        assert_eq!(graph.recorded_remote_tips("Origin").unwrap(), vec![release.clone()]);
+        assert_eq!(graph.find_tag("v1.0").unwrap(), Some(release));
+        assert_eq!(graph.find_tag("v2.0").unwrap(), None);
+    }
+
+    #[test]
+    fn verify_signatures_reports_unsigned_and_bad_commits() {
+        let (mut graph, backend) = mock_graph();
+        backend.set_worktree("1");
+        let root = graph.add_node(vec![], test_author(), vec![], "root".into(), Some(vec!["origin".into(), "work".into()])).unwrap();
+        backend.set_worktree("2");
+        let signed = graph.add_node(vec![root.clone()], test_author(), vec![], "signed".into(), None).unwrap();
+        backend.set_worktree("3");
+        let private = graph.add_node(vec![signed.clone()], test_author(), vec![], "private".into(), Some(vec!["origin".into()])).unwrap();
+        backend.state().signatures.insert(signed.0.clone(), SignatureStatus::Good { signer: "t".into() });
+        backend.state().signatures.insert(private.0.clone(), SignatureStatus::Bad("bad signature".into()));
+
+        let (problems, checked) = graph.verify_signatures(None).unwrap();
+        assert_eq!(checked, 3);
+        assert_eq!(problems, vec![
+            SignatureProblem { node: root.clone(), status: SignatureStatus::Unsigned },
+            SignatureProblem { node: private, status: SignatureStatus::Bad("bad signature".into()) },
+        ]);
+
+        // на work уходят только root и signed
+        let (problems, checked) = graph.verify_signatures(Some("Work")).unwrap();
+        assert_eq!(checked, 2);
+        assert_eq!(problems, vec![SignatureProblem { node: root, status: SignatureStatus::Unsigned }]);
+    }
+
+    #[test]
+    fn ahead_behind_counts_both_sides_of_the_fork() {
+        let (mut graph, backend) = mock_graph();
+        backend.set_worktree("root");
+        let root = graph.add_node(vec![], test_author(), vec![], "root".into(), None).unwrap();
+        let mut add = |parent: &NodeId, content: &str| {
+            backend.set_worktree(content);
+            graph.add_node(vec![parent.clone()], test_author(), vec![], content.into(), None).unwrap()
+        };
+        let base = add(&root, "base");
+        let remote_tip = add(&base, "theirs");
+        let local1 = add(&base, "ours1");
+        let local2 = add(&local1, "ours2");
+
+        assert_eq!(graph.ahead_behind(&local2, &[remote_tip]).unwrap(), (2, 1));
+        assert_eq!(graph.ahead_behind(&base, std::slice::from_ref(&base)).unwrap(), (0, 0));
+        assert_eq!(graph.ahead_behind(&local2, &[]).unwrap(), (4, 0));
+        let err = graph.ahead_behind(&local2, &[NodeId("unknown".into())]).unwrap_err();
+        assert!(err.to_string().contains("not in the graph"), "{}", err);
+    }
+
+    #[test]
+    fn verify_parents_catches_edges_that_drifted_from_git() {
+        let (mut graph, backend) = mock_graph();
+        backend.set_worktree("1");
+        let root = graph.add_node(vec![], test_author(), vec![], "root".into(), None).unwrap();
+        backend.set_worktree("2");
+        let child = graph.add_node(vec![root.clone()], test_author(), vec![], "child".into(), None).unwrap();
+        assert_eq!(graph.verify_parents().unwrap(), (vec![], 2));
+
+        // git переписал коммит у нас за спиной: в графе ребро осталось, в git его нет
+        backend.state().commits.get_mut(&child.0).unwrap().parents.clear();
+        backend.state().commits.remove(&root.0);
+        let (mismatches, checked) = graph.verify_parents().unwrap();
+        assert_eq!(checked, 2);
+        assert_eq!(mismatches, vec![
+            ParentMismatch { node: root.clone(), graph: vec![], git: None },
+            ParentMismatch { node: child, graph: vec![root], git: Some(vec![]) },
+        ]);
+    }
+
+    #[test]
+    fn nodes_by_tree_groups_identical_snapshots() {
+        let (mut graph, backend) = mock_graph();
+        backend.set_worktree("a");
+        let root = graph.add_node(vec![], test_author(), vec![], "root".into(), None).unwrap();
+        backend.set_worktree("b");
+        let change = graph.add_node(vec![root.clone()], test_author(), vec![], "change".into(), None).unwrap();
+        backend.set_worktree("a");
+        let revert = graph.add_node(vec![change.clone()], test_author(), vec![], "revert".into(), None).unwrap();
+
+        let groups = graph.nodes_by_tree().unwrap();
+        assert_eq!(groups.len(), 2);
+        let tree = |id: &NodeId| graph.get_node(id).unwrap().payload.tree_id;
+        assert_eq!(groups[&tree(&root)], vec![root.clone(), revert]);
+        assert_eq!(groups[&tree(&change)], vec![change]);
+    }
+
+    #[test]
+    fn tags_are_unique_and_resolvable() {
+        let (mut graph, backend) = mock_graph();
+        backend.set_worktree("v1");
+        let first = graph.add_node(vec![], test_author(), vec![], "first".into(), None).unwrap();
+        backend.set_worktree("v2");
+        let second = graph.add_node(vec![first.clone()], test_author(), vec![], "second".into(), None).unwrap();
+
+        graph.tag_node(&second, "v1.0", Some("First release")).unwrap();
+        graph.tag_node(&first, "alpha", None).unwrap();
+        assert_eq!(graph.find_tag("v1.0").unwrap(), Some(second.clone()));
+        assert_eq!(graph.find_tag("v2.0").unwrap(), None);
+
+        let tags = graph.list_tags().unwrap();
+        let names: Vec<&str> = tags.iter().map(|(_, t)| t.name.as_str()).collect();
+        assert_eq!(names, ["alpha", "v1.0"]);
+        assert_eq!(tags[1].0, second);
+        assert_eq!(tags[1].1.meta.get("message").map(String::as_str), Some("First release"));
+        assert!(tags[0].1.meta.is_empty());
+
+        let err = graph.tag_node(&first, "v1.0", None).unwrap_err().to_string();
+        assert!(err.contains("already exists"));
+        assert!(graph.tag_node(&first, "bad name", None).is_err());
+        assert!(graph.tag_node(&first, "a..b", None).is_err());
+    }
+
+    #[test]
+    fn resolves_revisions() {
+        let (mut graph, backend) = mock_graph();
+        backend.set_worktree("1");
+        let root = graph.add_node(vec![], test_author(), vec![], "root".into(), None).unwrap();
+        backend.set_worktree("2");
+        let side = graph.add_node(vec![root.clone()], test_author(), vec![], "side".into(), None).unwrap();
+        backend.set_worktree("3");
+        let main = graph.add_node(vec![root.clone()], test_author(), vec![], "main".into(), None).unwrap();
+        backend.set_worktree("4");
+        let merge = graph.add_node(vec![main.clone(), side.clone()], test_author(), vec![], "merge".into(), None).unwrap();
+        graph.tag_node(&main, "v1", None).unwrap();
+
+        let ctx = RevContext { head: Some(merge.clone()), previous_head: Some(side.clone()) };
+        let rev = |r: &str| graph.resolve_rev(r, &ctx);
+
+        assert_eq!(rev("HEAD").unwrap(), merge);
+        assert_eq!(rev("HEAD~1").unwrap(), main);
+        assert_eq!(rev("HEAD~2").unwrap(), root);
+        assert_eq!(rev("HEAD^2").unwrap(), side);
+        assert_eq!(rev("@^0").unwrap(), merge);
+        assert_eq!(rev("-").unwrap(), side);
+        assert_eq!(rev("v1~").unwrap(), root);
+        assert_eq!(rev(&merge.0[..8]).unwrap(), merge);
+
+        assert!(rev("HEAD~3").unwrap_err().to_string().contains("goes past the root"));
+        assert!(rev("HEAD^3").is_err());
+        assert!(rev("nope").unwrap_err().to_string().contains("Unknown revision"));
+    }
+}
@@ -1,33 +1,180 @@
-use std::collections::HashMap;
-use std::fs::{self, File};
-use std::io::{BufReader, BufWriter};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::hash::{Hash, Hasher};
 use std::path::{Path, PathBuf};
-use std::sync::{Arc, RwLock};
+use std::sync::{Arc, Mutex, RwLock};
 
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use gpp_core::migrate;
 use gpp_core::types::{Node, NodeId};
-use gpp_core::storage::{GraphStorage, TxHandle, StorageError, Result};
+use gpp_core::storage::{GraphStorage, TxHandle, StorageError, Result, FORMAT_VERSION};
 
 pub struct JsonStorage {
     db_path: PathBuf,
     nodes: Arc<RwLock<HashMap<NodeId, Node>>>,
+    /// remote_name_key -> последняя запушенная нода
+    remote_tips: RwLock<HashMap<String, NodeId>>,
+    /// Хеш graph.json в том виде, в каком мы его прочитали или записали (None - файла не было).
+    /// GUI и второй запуск CLI пишут тот же файл, commit_tx сверяется с ним, чтобы не затереть чужие изменения.
+    disk_hash: Mutex<Option<u64>>,
+}
+
+/// graph.json целиком: ноды по id и вершины ремоутов
+#[derive(Default, Deserialize)]
+struct GraphFile {
+    #[serde(default)]
+    nodes: HashMap<NodeId, Node>,
+    #[serde(default)]
+    remote_tips: HashMap<String, NodeId>,
+}
+
+/// То же для записи: отсортированное, чтобы порядок в файле не зависел от HashMap
+#[derive(Serialize)]
+struct SortedGraphFile<'a> {
+    nodes: BTreeMap<&'a NodeId, &'a Node>,
+    remote_tips: BTreeMap<&'a String, &'a NodeId>,
+}
+
+/// Чем закончился `JsonStorage::repair`
+#[derive(Debug)]
+pub enum RepairOutcome {
+    /// graph.json читается, чинить нечего
+    Healthy,
+    /// graph.json восстановлен из бэкапа, испорченный файл отложен в `corrupt_copy`
+    Restored { backup: PathBuf, corrupt_copy: PathBuf },
 }
 
 impl JsonStorage {
     pub fn new(db_path: impl AsRef<Path>) -> Result<Self> {
         let path = db_path.as_ref().to_path_buf();
-        let nodes = if path.exists() {
-            let file = File::open(&path).map_err(StorageError::Io)?;
-            let reader = BufReader::new(file);
-            serde_json::from_reader(reader).map_err(StorageError::Serde)?
+        // хеш снимаем до чтения: если файл поменяют между ними, commit_tx увидит конфликт, а не затрёт его
+        let disk_hash = Self::disk_hash(&path)?;
+        let graph = if path.exists() {
+            let version = Self::read_format(&path)?;
+            if version > FORMAT_VERSION {
+                return Err(StorageError::UnsupportedFormat { found: version, supported: FORMAT_VERSION });
+            }
+            Self::read_graph(&path, version)?
         } else {
-            HashMap::new()
+            GraphFile::default()
         };
 
+        // старый граф мигрируется только в памяти: на диск (и в файл format) он попадёт с первым commit_tx,
+        // так что log или stats не переписывают репозиторий и работают там, где писать нельзя
         Ok(Self {
             db_path: path,
-            nodes: Arc::new(RwLock::new(nodes)),
+            nodes: Arc::new(RwLock::new(graph.nodes)),
+            remote_tips: RwLock::new(graph.remote_tips),
+            disk_hash: Mutex::new(disk_hash),
         })
     }
+
+    /// Путь бэкапа, который commit_tx оставляет перед каждой перезаписью графа
+    pub fn backup_path(db_path: &Path) -> PathBuf {
+        let mut name = db_path.file_name().unwrap_or_default().to_os_string();
+        name.push(".bak");
+        db_path.with_file_name(name)
+    }
+
+    fn tmp_path(db_path: &Path) -> PathBuf {
+        let mut name = db_path.file_name().unwrap_or_default().to_os_string();
+        name.push(".tmp");
+        db_path.with_file_name(name)
+    }
+
+    fn content_hash(content: &[u8]) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        content.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    fn disk_hash(path: &Path) -> Result<Option<u64>> {
+        match fs::read(path) {
+            Ok(content) => Ok(Some(Self::content_hash(&content))),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(StorageError::Io(e)),
+        }
+    }
+
+    /// Файл с версией формата лежит рядом с графом (.gitpp/format)
+    pub fn format_path(db_path: &Path) -> PathBuf {
+        db_path.with_file_name("format")
+    }
+
+    /// Версия формата графа. Нет файла - репозиторий старше версионирования, это версия 0.
+    pub fn read_format(db_path: &Path) -> Result<u32> {
+        let path = Self::format_path(db_path);
+        if !path.exists() {
+            return Ok(0);
+        }
+        let content = fs::read_to_string(&path).map_err(StorageError::Io)?;
+        content
+            .trim()
+            .parse()
+            .map_err(|_| StorageError::Tx(format!("Invalid format file {}: '{}'", path.display(), content.trim())))
+    }
+
+    /// Помечает граф текущей версией формата (init, clone и каждая запись графа)
+    pub fn write_format(db_path: &Path) -> Result<()> {
+        fs::write(Self::format_path(db_path), format!("{}\n", FORMAT_VERSION)).map_err(StorageError::Io)
+    }
+
+    /// Читает граф версии `version`. Старые версии сначала читаются как сырой JSON и прогоняются через миграции.
+    fn read_graph(path: &Path, version: u32) -> Result<GraphFile> {
+        let content = fs::read_to_string(path).map_err(StorageError::Io)?;
+        if version == FORMAT_VERSION {
+            return Self::parse(path, &content);
+        }
+        let raw = migrate(Self::parse(path, &content)?, version)?;
+        serde_json::from_value(raw).map_err(StorageError::Serde)
+    }
+
+    fn parse<T: DeserializeOwned>(path: &Path, content: &str) -> Result<T> {
+        serde_json::from_str(content).map_err(|e| {
+            let (line, column) = (e.line(), e.column());
+            // serde_json отдаёт только строку/колонку, байтовое смещение считаем сами
+            let offset = content
+                .split_inclusive('\n')
+                .take(line.saturating_sub(1))
+                .map(|l| l.len())
+                .sum::<usize>()
+                + column.saturating_sub(1);
+            StorageError::Corrupt { path: path.to_path_buf(), line, column, offset, source: e }
+        })
+    }
+
+    /// Если graph.json не читается - откладывает его в graph.json.corrupt-<время> и поднимает последний бэкап
+    pub fn repair(db_path: impl AsRef<Path>) -> Result<RepairOutcome> {
+        let path = db_path.as_ref();
+        let version = Self::read_format(path)?;
+        let err = match Self::read_graph(path, version) {
+            Ok(_) => return Ok(RepairOutcome::Healthy),
+            Err(e @ StorageError::Corrupt { .. }) => e,
+            Err(e) => return Err(e),
+        };
+
+        let backup = Self::backup_path(path);
+        if !backup.exists() {
+            return Err(StorageError::Tx(format!("{}. No backup found at {}", err, backup.display())));
+        }
+        Self::read_graph(&backup, version)?;
+
+        let stamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        let mut corrupt_name = path.file_name().unwrap_or_default().to_os_string();
+        corrupt_name.push(format!(".corrupt-{}", stamp));
+        let corrupt_copy = path.with_file_name(corrupt_name);
+
+        fs::rename(path, &corrupt_copy).map_err(StorageError::Io)?;
+        fs::copy(&backup, path).map_err(StorageError::Io)?;
+
+        Ok(RepairOutcome::Restored { backup, corrupt_copy })
+    }
 }
 
 impl GraphStorage for JsonStorage {
@@ -52,6 +199,17 @@ impl GraphStorage for JsonStorage {
             .collect())
     }
 
+    fn remote_tip(&self, remote: &str) -> Result<Option<NodeId>> {
+        let tips = self.remote_tips.read().map_err(|_| StorageError::Tx("Lock poisoned".into()))?;
+        Ok(tips.get(remote).cloned())
+    }
+
+    fn set_remote_tip(&mut self, remote: &str, tip: &NodeId) -> Result<()> {
+        let mut tips = self.remote_tips.write().map_err(|_| StorageError::Tx("Lock poisoned".into()))?;
+        tips.insert(remote.to_string(), tip.clone());
+        Ok(())
+    }
+
     fn begin_tx(&self) -> Result<TxHandle> {
         Ok(TxHandle {
             path: self.db_path.clone(),
@@ -60,28 +218,154 @@ impl GraphStorage for JsonStorage {
 
     fn commit_tx(&self, _tx: TxHandle) -> Result<()> {
         let map = self.nodes.read().map_err(|_| StorageError::Tx("Lock poisoned".into()))?;
+        let tips = self.remote_tips.read().map_err(|_| StorageError::Tx("Lock poisoned".into()))?;
+        let mut disk_hash = self.disk_hash.lock().map_err(|_| StorageError::Tx("Lock poisoned".into()))?;
+
+        if Self::disk_hash(&self.db_path)? != *disk_hash {
+            return Err(StorageError::Conflict(self.db_path.clone()));
+        }
 
         if let Some(parent) = self.db_path.parent() {
             fs::create_dir_all(parent).map_err(StorageError::Io)?;
         }
 
-        let file = File::create(&self.db_path).map_err(StorageError::Io)?;
-        let writer = BufWriter::new(file);
+        // предыдущая версия файла заведомо читалась (мы её загрузили), сохраняем её для gpp repair
+        if self.db_path.exists() {
+            fs::copy(&self.db_path, Self::backup_path(&self.db_path)).map_err(StorageError::Io)?;
+        }
 
-        serde_json::to_writer_pretty(writer, &*map).map_err(StorageError::Serde)?;
-        Ok(())
+        let sorted = SortedGraphFile { nodes: map.iter().collect(), remote_tips: tips.iter().collect() };
+        let content = serde_json::to_vec_pretty(&sorted).map_err(StorageError::Serde)?;
+        // пишем рядом и переименовываем: упавший посреди записи процесс оставит старый граф, а не половину нового
+        let tmp_path = Self::tmp_path(&self.db_path);
+        fs::write(&tmp_path, &content).map_err(StorageError::Io)?;
+        fs::rename(&tmp_path, &self.db_path).map_err(StorageError::Io)?;
+        *disk_hash = Some(Self::content_hash(&content));
+
+        Self::write_format(&self.db_path)
     }
 
     fn rollback_tx(&self, _tx: TxHandle) -> Result<()> {
         let mut map = self.nodes.write().map_err(|_| StorageError::Tx("Lock poisoned".into()))?;
+        let mut tips = self.remote_tips.write().map_err(|_| StorageError::Tx("Lock poisoned".into()))?;
+        *self.disk_hash.lock().map_err(|_| StorageError::Tx("Lock poisoned".into()))? = Self::disk_hash(&self.db_path)?;
 
-        if self.db_path.exists() {
-            let file = File::open(&self.db_path).map_err(StorageError::Io)?;
-            let reader = BufReader::new(file);
-            *map = serde_json::from_reader(reader).map_err(StorageError::Serde)?;
+        let graph = if self.db_path.exists() {
+            Self::read_graph(&self.db_path, Self::read_format(&self.db_path)?)?
         } else {
-            map.clear();
-        }
+            GraphFile::default()
+        };
+        *map = graph.nodes;
+        *tips = graph.remote_tips;
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gpp_core::types::{Author, NodePayload};
+
+    fn node(id: &str) -> Node {
+        Node {
+            id: NodeId(id.into()),
+            parents: vec![],
+            children: Default::default(),
+            author: Author { name: "a".into(), email: "a@b".into() },
+            co_authors: vec![],
+            message: id.into(),
+            created_at: chrono::DateTime::<chrono::Utc>::MIN_UTC,
+            commit: None,
+            tz_offset: 0,
+            payload: NodePayload { tree_id: "t".into() },
+            remotes: Default::default(),
+            tags: Default::default(),
+            metadata: Default::default(),
+        }
+    }
+
+    fn temp_db(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("gpp-json-storage-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir.join("graph.json")
+    }
+
+    #[test]
+    fn commit_refuses_to_overwrite_external_changes() {
+        let path = temp_db("conflict");
+        let mut first = JsonStorage::new(&path).unwrap();
+        let mut second = JsonStorage::new(&path).unwrap();
+
+        first.persist_node(&node("from_gui")).unwrap();
+        first.commit_tx(first.begin_tx().unwrap()).unwrap();
+
+        second.persist_node(&node("from_cli")).unwrap();
+        let err = second.commit_tx(second.begin_tx().unwrap()).unwrap_err();
+        assert!(matches!(err, StorageError::Conflict(_)));
+
+        // запись первого осталась на месте, а после rollback второй снова может писать
+        let on_disk = JsonStorage::new(&path).unwrap();
+        assert!(on_disk.load_node(&NodeId("from_gui".into())).is_ok());
+        second.rollback_tx(second.begin_tx().unwrap()).unwrap();
+        second.persist_node(&node("from_cli")).unwrap();
+        second.commit_tx(second.begin_tx().unwrap()).unwrap();
+
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn old_format_is_upgraded_on_first_commit_only() {
+        let path = temp_db("upgrade");
+        let v1 = serde_json::json!({ "old": node("old") }).to_string();
+        fs::write(&path, &v1).unwrap();
+        fs::write(JsonStorage::format_path(&path), "1\n").unwrap();
+
+        let mut storage = JsonStorage::new(&path).unwrap();
+        assert!(storage.load_node(&NodeId("old".into())).is_ok());
+        assert_eq!(fs::read_to_string(&path).unwrap(), v1);
+        assert_eq!(JsonStorage::read_format(&path).unwrap(), 1);
+
+        storage.persist_node(&node("new")).unwrap();
+        storage.commit_tx(storage.begin_tx().unwrap()).unwrap();
+        assert_eq!(JsonStorage::read_format(&path).unwrap(), FORMAT_VERSION);
+        let reloaded = JsonStorage::new(&path).unwrap();
+        assert!(reloaded.load_node(&NodeId("old".into())).is_ok());
+
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn remote_tips_are_written_next_to_nodes() {
+        let path = temp_db("tips");
+        let mut storage = JsonStorage::new(&path).unwrap();
+        storage.persist_node(&node("tip")).unwrap();
+        storage.set_remote_tip("origin", &NodeId("tip".into())).unwrap();
+        storage.commit_tx(storage.begin_tx().unwrap()).unwrap();
+
+        let reloaded = JsonStorage::new(&path).unwrap();
+        assert_eq!(reloaded.remote_tip("origin").unwrap(), Some(NodeId("tip".into())));
+        assert_eq!(reloaded.remote_tip("mirror").unwrap(), None);
+
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn commit_replaces_graph_without_leaving_temp_file() {
+        let path = temp_db("atomic");
+        let mut storage = JsonStorage::new(&path).unwrap();
+        storage.persist_node(&node("first")).unwrap();
+        storage.commit_tx(storage.begin_tx().unwrap()).unwrap();
+        storage.persist_node(&node("second")).unwrap();
+        storage.commit_tx(storage.begin_tx().unwrap()).unwrap();
+
+        assert!(!JsonStorage::tmp_path(&path).exists());
+        let reloaded = JsonStorage::new(&path).unwrap();
+        assert!(reloaded.load_node(&NodeId("second".into())).is_ok());
+        // бэкап - предыдущая запись
+        let backup = JsonStorage::new(JsonStorage::backup_path(&path)).unwrap();
+        assert!(backup.load_node(&NodeId("second".into())).is_err());
+
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+}